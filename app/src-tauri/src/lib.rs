@@ -11,12 +11,22 @@ use ssh_service::{
     ssh_test_connection, ssh_execute_command, ssh_upload_file, ssh_check_printer_queue,
     ssh_debug_command, check_network_connectivity, exit_app
 };
-use pdf_service::{pdf_get_info, pdf_generate_booklet_layout, pdf_create_booklet, pdf_create_nup};
+use pdf_service::{
+    pdf_get_info, pdf_generate_booklet_layout, pdf_create_booklet, pdf_create_nup,
+    pdf_create_nup_grid, pdf_fit_to_paper, pdf_images_to_pdf,
+    pdf_text_to_pdf, pdf_stamp, pdf_arrange_copies, pdf_decrypt, pdf_parse_page_selection,
+    pdf_resolve_page_range, pdf_search_text, pdf_detect_blank_pages,
+    pdf_convert_grayscale, pdf_detect_color_pages, pdf_detect_overlays,
+    pdf_trim_margins, pdf_create_handout, pdf_create_poster,
+};
 use print_service::{
-    print_create_job, print_get_all_jobs, print_get_job, print_update_job_status,
-    print_cancel_job, print_delete_job, print_submit_job, print_get_printers,
-    print_check_printer_status, print_check_active_jobs,
-    print_save_history, print_get_backup_path, print_cleanup_history, print_get_storage_info,
+    print_create_job, print_create_merged_job, print_create_split_jobs,
+    print_get_all_jobs, print_get_job,
+    print_update_job_status, print_cancel_job, print_delete_job, print_submit_job, print_get_printers,
+    print_check_printer_status, print_check_active_jobs, print_estimate_job,
+    print_continue_manual_duplex,
+    print_save_history, print_get_backup_path, print_get_converted_path, print_cleanup_history,
+    print_get_storage_info,
 };
 
 // Import Manager trait for window methods
@@ -48,20 +58,42 @@ pub fn run() {
             pdf_generate_booklet_layout,
             pdf_create_booklet,
             pdf_create_nup,
+            pdf_create_nup_grid,
+            pdf_fit_to_paper,
+            pdf_images_to_pdf,
+            pdf_text_to_pdf,
+            pdf_stamp,
+            pdf_arrange_copies,
+            pdf_decrypt,
+            pdf_parse_page_selection,
+            pdf_resolve_page_range,
+            pdf_search_text,
+            pdf_detect_blank_pages,
+            pdf_convert_grayscale,
+            pdf_detect_color_pages,
+            pdf_detect_overlays,
+            pdf_trim_margins,
+            pdf_create_handout,
+            pdf_create_poster,
             // Print job operations
             print_create_job,
+            print_create_merged_job,
+            print_create_split_jobs,
             print_get_all_jobs,
             print_get_job,
             print_update_job_status,
             print_cancel_job,
             print_delete_job,
             print_submit_job,
+            print_continue_manual_duplex,
             print_get_printers,
             print_check_printer_status,
             print_check_active_jobs,
+            print_estimate_job,
             // Storage operations
             print_save_history,
             print_get_backup_path,
+            print_get_converted_path,
             print_cleanup_history,
             print_get_storage_info,
         ])
@@ -70,6 +102,10 @@ pub fn run() {
             if let Err(e) = storage_service::ensure_directories() {
                 eprintln!("[App] Warning: Failed to initialize storage directories: {}", e);
             }
+            // Converted images and text files are backed up with their jobs
+            if let Err(e) = storage_service::cleanup_converted_files(7) {
+                eprintln!("[App] Warning: Failed to clean up converted files: {}", e);
+            }
 
             // Get the main window
             let _window = app.get_webview_window("main").unwrap();
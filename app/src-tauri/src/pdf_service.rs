@@ -1,11 +1,13 @@
 use crate::types::*;
-use lopdf::Document;
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
 /// Get PDF file information
 #[tauri::command]
-pub fn pdf_get_info(file_path: String) -> ApiResponse<PDFInfo> {
-    match get_pdf_info_internal(&file_path) {
+pub fn pdf_get_info(file_path: String, password: Option<String>) -> ApiResponse<PDFInfo> {
+    match get_pdf_info_internal(&file_path, password.as_deref()) {
         Ok(info) => ApiResponse::success(info),
         Err(e) => ApiResponse::error(e.to_string()),
     }
@@ -13,15 +15,22 @@ pub fn pdf_get_info(file_path: String) -> ApiResponse<PDFInfo> {
 
 /// Generate booklet page order for a PDF
 #[tauri::command]
-pub fn pdf_generate_booklet_layout(num_pages: u32) -> ApiResponse<BookletLayout> {
-    let layout = generate_booklet_layout_internal(num_pages);
+pub fn pdf_generate_booklet_layout(
+    num_pages: u32,
+    sheets_per_signature: Option<u32>,
+) -> ApiResponse<BookletLayout> {
+    let layout = generate_booklet_layout_internal(num_pages, sheets_per_signature.unwrap_or(0));
     ApiResponse::success(layout)
 }
 
 /// Process PDF for booklet printing (creates new PDF with reordered pages)
 #[tauri::command]
-pub fn pdf_create_booklet(input_path: String, output_path: String) -> ApiResponse<String> {
-    match create_booklet_pdf_internal(&input_path, &output_path) {
+pub fn pdf_create_booklet(
+    input_path: String,
+    output_path: String,
+    settings: Option<BookletSettings>,
+) -> ApiResponse<String> {
+    match create_booklet_pdf_internal(&input_path, &output_path, settings.as_ref()) {
         Ok(_) => ApiResponse::success(format!("Booklet PDF created at {}", output_path)),
         Err(e) => ApiResponse::error(e.to_string()),
     }
@@ -33,16 +42,258 @@ pub fn pdf_create_nup(
     input_path: String,
     output_path: String,
     pages_per_sheet: u32,
+    orientation: Option<Orientation>,
 ) -> ApiResponse<String> {
-    match create_nup_pdf_internal(&input_path, &output_path, pages_per_sheet) {
+    let orientation = orientation.unwrap_or(Orientation::Auto);
+    match create_nup_pdf_internal(&input_path, &output_path, pages_per_sheet, orientation) {
         Ok(_) => ApiResponse::success(format!("N-up PDF created at {}", output_path)),
         Err(e) => ApiResponse::error(e.to_string()),
     }
 }
 
+/// Process PDF for n-up printing on an explicit columns x rows grid
+#[tauri::command]
+pub fn pdf_create_nup_grid(
+    input_path: String,
+    output_path: String,
+    cols: u32,
+    rows: u32,
+    orientation: Option<Orientation>,
+) -> ApiResponse<String> {
+    let orientation = orientation.unwrap_or(Orientation::Auto);
+    match create_nup_grid_pdf_internal(&input_path, &output_path, cols, rows, orientation) {
+        Ok(_) => ApiResponse::success(format!("{}x{} N-up PDF created at {}", cols, rows, output_path)),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Scale every page onto the selected paper size
+#[tauri::command]
+pub fn pdf_fit_to_paper(
+    input_path: String,
+    output_path: String,
+    paper_size: PaperSize,
+    scale_mode: ScaleMode,
+    orientation: Option<Orientation>,
+) -> ApiResponse<String> {
+    let options = FitOptions {
+        paper_size,
+        scale_mode,
+        orientation: orientation.unwrap_or(Orientation::Auto),
+        short_edge_flip: false,
+    };
+    match fit_to_paper_pdf_internal(&input_path, &output_path, &options) {
+        Ok(_) => ApiResponse::success(format!("Scaled PDF created at {}", output_path)),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Convert PNG/JPEG images into a PDF, one image per page
+#[tauri::command]
+pub fn pdf_images_to_pdf(
+    image_paths: Vec<String>,
+    output_path: String,
+    paper_size: PaperSize,
+    margin_mm: Option<f32>,
+    orientation: Option<Orientation>,
+) -> ApiResponse<String> {
+    let options = ImageToPdfOptions {
+        paper_size,
+        margin_mm: margin_mm.unwrap_or(0.0),
+        orientation: orientation.unwrap_or(Orientation::Auto),
+    };
+    match images_to_pdf_internal(&image_paths, &output_path, &options) {
+        Ok(_) => ApiResponse::success(format!("PDF created from images at {}", output_path)),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Render a plain text or source file as a PDF listing with line numbers
+#[tauri::command]
+pub fn pdf_text_to_pdf(
+    input_path: String,
+    output_path: String,
+    paper_size: PaperSize,
+    font_size: Option<f32>,
+    tab_width: Option<u32>,
+    wrap_lines: Option<bool>,
+    line_numbers: Option<bool>,
+) -> ApiResponse<TextListing> {
+    let defaults = TextToPdfOptions::default();
+    let options = TextToPdfOptions {
+        paper_size,
+        font_size: font_size.unwrap_or(defaults.font_size),
+        tab_width: tab_width.unwrap_or(defaults.tab_width),
+        wrap_lines: wrap_lines.unwrap_or(defaults.wrap_lines),
+        line_numbers: line_numbers.unwrap_or(defaults.line_numbers),
+    };
+    match text_to_pdf_internal(&input_path, &output_path, &options) {
+        Ok(listing) => ApiResponse::success(listing),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Stamp header/footer text and an optional watermark onto every page
+#[tauri::command]
+pub fn pdf_stamp(input_path: String, output_path: String, stamp: StampSettings) -> ApiResponse<String> {
+    match stamp_pdf_internal(&input_path, &output_path, &stamp) {
+        Ok(_) => ApiResponse::success(format!("Stamped PDF created at {}", output_path)),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Repeat and reorder pages for collated/uncollated copies and reverse order
+#[tauri::command]
+pub fn pdf_arrange_copies(
+    input_path: String,
+    output_path: String,
+    copies: u32,
+    collate: bool,
+    reverse: bool,
+    duplex: bool,
+) -> ApiResponse<String> {
+    let options = CopyOptions {
+        copies,
+        collate,
+        reverse,
+        duplex,
+    };
+    match arrange_copies_internal(&input_path, &output_path, &options) {
+        Ok(_) => ApiResponse::success(format!("Arranged PDF created at {}", output_path)),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Write a decrypted copy of a password-protected or restricted PDF
+#[tauri::command]
+pub fn pdf_decrypt(input_path: String, output_path: String, password: Option<String>) -> ApiResponse<String> {
+    match decrypt_pdf_internal(&input_path, &output_path, password.as_deref()) {
+        Ok(_) => ApiResponse::success(format!("Decrypted PDF created at {}", output_path)),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Parse a page selection such as "1-3,7,10-", "odd" or "iv-x" into page numbers
+#[tauri::command]
+pub fn pdf_parse_page_selection(
+    file_path: String,
+    expression: String,
+    password: Option<String>,
+) -> ApiResponse<Vec<u32>> {
+    match parse_page_selection_internal(&file_path, &expression, password.as_deref()) {
+        Ok(pages) => ApiResponse::success(pages),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Pages a page range selects, resolved exactly as when the job is printed
+#[tauri::command]
+pub fn pdf_resolve_page_range(
+    file_path: String,
+    page_range: PageRange,
+    password: Option<String>,
+) -> ApiResponse<Vec<u32>> {
+    match resolve_file_page_range(&file_path, &page_range, password.as_deref()) {
+        Ok(pages) => ApiResponse::success(pages),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Search every page's text for a keyword or regular expression
+#[tauri::command]
+pub fn pdf_search_text(
+    file_path: String,
+    pattern: String,
+    regex: bool,
+    case_sensitive: bool,
+    password: Option<String>,
+) -> ApiResponse<Vec<PageTextMatch>> {
+    match search_text_internal(&file_path, &pattern, regex, case_sensitive, password.as_deref()) {
+        Ok(pages) => ApiResponse::success(pages),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Find blank pages in a PDF
+#[tauri::command]
+pub fn pdf_detect_blank_pages(file_path: String, password: Option<String>) -> ApiResponse<Vec<BlankPage>> {
+    match detect_blank_pages_internal(&file_path, password.as_deref()) {
+        Ok(pages) => ApiResponse::success(pages),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Convert a PDF's colours and images to grayscale
+#[tauri::command]
+pub fn pdf_convert_grayscale(
+    input_path: String,
+    output_path: String,
+    boost_contrast: Option<bool>,
+) -> ApiResponse<String> {
+    match grayscale_pdf_internal(&input_path, &output_path, boost_contrast.unwrap_or(false)) {
+        Ok(_) => ApiResponse::success(format!("Grayscale PDF created at {}", output_path)),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Enlarge pages across several sheets to print a poster
+#[tauri::command]
+pub fn pdf_create_poster(
+    input_path: String,
+    output_path: String,
+    poster: PosterSettings,
+    paper_size: PaperSize,
+) -> ApiResponse<String> {
+    match create_poster_pdf_internal(&input_path, &output_path, &poster, &paper_size) {
+        Ok(_) => ApiResponse::success(format!("Poster PDF created at {}", output_path)),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Crop pages to their content, leaving some padding
+#[tauri::command]
+pub fn pdf_trim_margins(input_path: String, output_path: String, trim: TrimSettings) -> ApiResponse<String> {
+    match trim_margins_pdf_internal(&input_path, &output_path, &trim) {
+        Ok(_) => ApiResponse::success(format!("Trimmed PDF created at {}", output_path)),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Lay slides out as a printed handout with room for notes
+#[tauri::command]
+pub fn pdf_create_handout(
+    input_path: String,
+    output_path: String,
+    handout: HandoutSettings,
+    paper_size: PaperSize,
+) -> ApiResponse<String> {
+    match create_handout_pdf_internal(&input_path, &output_path, &handout, &paper_size) {
+        Ok(_) => ApiResponse::success(format!("Handout PDF created at {}", output_path)),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Find slide build frames that the next page repeats, and how many pages dropping them saves
+#[tauri::command]
+pub fn pdf_detect_overlays(file_path: String, password: Option<String>) -> ApiResponse<OverlayAnalysis> {
+    match detect_overlay_pages_internal(&file_path, password.as_deref()) {
+        Ok(analysis) => ApiResponse::success(analysis),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
+/// Find the pages of a PDF that use colour
+#[tauri::command]
+pub fn pdf_detect_color_pages(file_path: String, password: Option<String>) -> ApiResponse<ColorAnalysis> {
+    match detect_color_pages_internal(&file_path, password.as_deref()) {
+        Ok(analysis) => ApiResponse::success(analysis),
+        Err(e) => ApiResponse::error(e.to_string()),
+    }
+}
+
 // ========== Internal Implementation ==========
 
-fn get_pdf_info_internal(file_path: &str) -> Result<PDFInfo, Box<dyn std::error::Error>> {
+pub fn get_pdf_info_internal(file_path: &str, password: Option<&str>) -> Result<PDFInfo, Box<dyn std::error::Error>> {
     // Check if file exists first
     let path = Path::new(file_path);
     if !path.exists() {
@@ -59,174 +310,6448 @@ fn get_pdf_info_internal(file_path: &str) -> Result<PDFInfo, Box<dyn std::error:
     }
 
     // Try to load with lopdf
-    let doc = Document::load(path)
-        .map_err(|e| format!("Failed to parse PDF {}: {}. The file may be corrupted or use unsupported features.", file_path, e))?;
+    let doc = load_document(file_path, password).map_err(|e| match e {
+        lopdf::Error::InvalidPassword => format!("Incorrect password for {}", file_path),
+        e => format!("Failed to parse PDF {}: {}. The file may be corrupted or use unsupported features.", file_path, e),
+    })?;
+
+    // Without the user password only the encryption dictionary is readable
+    if doc.is_encrypted() {
+        let flags = doc
+            .get_encrypted()
+            .and_then(|dict| dict.get(b"P"))
+            .and_then(Object::as_i64)
+            .unwrap_or(0);
+        return Ok(PDFInfo {
+            num_pages: 0,
+            page_size: (0.0, 0.0),
+            file_size,
+            pages: Vec::new(),
+            mixed_page_sizes: false,
+            has_non_a4_pages: false,
+            encrypted: true,
+            password_required: true,
+            permissions: Some(permissions_from_flags(flags)),
+            outline: Vec::new(),
+        });
+    }
+    let permissions = doc
+        .encryption_state
+        .as_ref()
+        .map(|state| permissions_from_flags(state.permissions().bits() as i64));
 
     let num_pages = doc.get_pages().len() as u32;
     if num_pages == 0 {
         return Err(format!("PDF has no pages: {}", file_path).into());
     }
 
-    // Default A4 page size
-    let page_size = (595.0, 842.0);
+    let labels = page_labels(&doc);
+    let pages: Vec<PageInfo> = doc
+        .get_pages()
+        .iter()
+        .zip(labels)
+        .map(|((number, page_id), label)| page_info(&doc, *number, *page_id, label))
+        .collect();
+
+    let page_size = (pages[0].width, pages[0].height);
+    let mixed_page_sizes = pages
+        .iter()
+        .any(|page| !same_size((page.width, page.height), page_size));
+    let has_non_a4_pages = pages.iter().any(|page| page.format != PageFormat::A4);
 
     Ok(PDFInfo {
         num_pages,
         page_size,
         file_size,
+        pages,
+        mixed_page_sizes,
+        has_non_a4_pages,
+        encrypted: permissions.is_some(),
+        password_required: false,
+        permissions,
+        outline: document_outline(&doc),
     })
 }
 
-fn generate_booklet_layout_internal(num_pages: u32) -> BookletLayout {
-    // Calculate total sheets needed (4 pages per sheet in booklet mode)
-    let pages_per_sheet = 4;
-    let total_sheets = ((num_pages as f32) / pages_per_sheet as f32).ceil() as u32;
-    let total_pages_needed = total_sheets * pages_per_sheet;
+/// Load a PDF, decrypting it with `password` if given. Files that only carry
+/// an owner password are decrypted automatically; files that need a user
+/// password come back still encrypted (see `Document::is_encrypted`).
+fn load_document(path: &str, password: Option<&str>) -> lopdf::Result<Document> {
+    match password {
+        Some(password) => Document::load_with_password(path, password),
+        None => Document::load(path),
+    }
+}
 
-    let mut page_order: Vec<Vec<Option<u32>>> = Vec::new();
+/// Decode the /P permission flags of an encryption dictionary
+fn permissions_from_flags(flags: i64) -> PdfPermissions {
+    let has = |bit: u32| flags & (1 << (bit - 1)) != 0;
+    PdfPermissions {
+        print: has(3),
+        print_high_quality: has(3) && has(12),
+        modify: has(4),
+        copy: has(5),
+        annotate: has(6),
+        fill_forms: has(9),
+        assemble: has(11),
+    }
+}
 
-    // Generate booklet page order
-    // For a booklet, pages are arranged as:
-    // Sheet 1: [n, 1, 2, n-1]
-    // Sheet 2: [n-2, 3, 4, n-3]
-    // etc.
+/// Bytes read from each region of a file when looking for an /Encrypt entry
+const TRAILER_SCAN_BYTES: u64 = 8192;
 
-    for sheet in 0..total_sheets {
-        let mut sheet_pages: Vec<Option<u32>> = Vec::new();
+/// Cheap check for an /Encrypt entry in the trailer, without reading the
+/// whole file. Looks at the end of the file, the cross-reference section
+/// `startxref` points to (its trailer or xref stream dictionary), and the
+/// start of the file, where linearized files keep their first trailer.
+pub fn may_be_encrypted(path: &str) -> bool {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let Ok(mut file) = std::fs::File::open(path) else {
+        return false;
+    };
+    let Ok(len) = file.metadata().map(|metadata| metadata.len()) else {
+        return false;
+    };
+    let mut read_at = |offset: u64| {
+        let mut bytes = Vec::new();
+        if file.seek(SeekFrom::Start(offset)).is_ok() {
+            let _ = (&mut file).take(TRAILER_SCAN_BYTES).read_to_end(&mut bytes);
+        }
+        bytes
+    };
 
-        // Right side of sheet (when folded)
-        let right_outer = total_pages_needed - (sheet * 2);
-        let right_inner = sheet * 2 + 1;
+    let tail = read_at(len.saturating_sub(TRAILER_SCAN_BYTES));
+    let mut regions = vec![read_at(0)];
+    if let Some(offset) = startxref_offset(&tail).filter(|&offset| offset < len) {
+        regions.push(read_at(offset));
+    }
+    regions.push(tail);
+    regions
+        .iter()
+        .any(|bytes| bytes.windows(8).any(|window| window == b"/Encrypt"))
+}
 
-        // Left side of sheet (when folded)
-        let left_inner = sheet * 2 + 2;
-        let left_outer = total_pages_needed - (sheet * 2) - 1;
+/// Offset after the last `startxref` keyword in the end of a file
+fn startxref_offset(tail: &[u8]) -> Option<u64> {
+    let keyword = b"startxref";
+    let position = tail.windows(keyword.len()).rposition(|window| window == keyword)?;
+    String::from_utf8_lossy(&tail[position + keyword.len()..])
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
 
-        // Check if pages exist (not blank)
-        sheet_pages.push(if right_outer <= num_pages {
-            Some(right_outer)
-        } else {
-            None
-        });
-        sheet_pages.push(if right_inner <= num_pages {
-            Some(right_inner)
-        } else {
-            None
-        });
-        sheet_pages.push(if left_inner <= num_pages {
-            Some(left_inner)
+/// Write a decrypted copy of a PDF so later stages and the printer can read it.
+///
+/// Unencrypted files are copied unchanged. Files restricted only by an owner
+/// password need no `password`.
+pub fn decrypt_pdf_internal(
+    input_path: &str,
+    output_path: &str,
+    password: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Verify input file exists
+    if !Path::new(input_path).exists() {
+        return Err(format!("Input PDF not found: {}", input_path).into());
+    }
+
+    let mut doc = load_document(input_path, password).map_err(|e| match e {
+        lopdf::Error::InvalidPassword => format!("Incorrect password for {}", input_path),
+        e => format!("Failed to parse PDF for decryption: {}", e),
+    })?;
+    if doc.is_encrypted() {
+        return Err(format!("PDF is password protected: {}", input_path).into());
+    }
+    if !doc.was_encrypted() {
+        std::fs::copy(input_path, output_path)
+            .map_err(|e| format!("Failed to copy PDF: {}", e))?;
+        return Ok(());
+    }
+
+    eprintln!("[PDF] Decrypted {}", input_path);
+    save_document(&mut doc, output_path)
+}
+
+/// Tolerance in points when comparing page sizes
+const SIZE_TOLERANCE: f64 = 3.0;
+
+fn same_size(a: (f64, f64), b: (f64, f64)) -> bool {
+    (a.0 - b.0).abs() <= SIZE_TOLERANCE && (a.1 - b.1).abs() <= SIZE_TOLERANCE
+}
+
+fn page_info(doc: &Document, number: u32, page_id: ObjectId, label: Option<String>) -> PageInfo {
+    let geometry = page_geometry(doc, page_id);
+    let media_box = media_box(doc, page_id);
+    let (width, height) = geometry.size();
+    let (width, height) = (width as f64, height as f64);
+
+    PageInfo {
+        number,
+        media_box: media_box.map(|v| v as f64),
+        crop_box: geometry.bbox.map(|v| v as f64),
+        rotate: geometry.rotate as u32,
+        width,
+        height,
+        orientation: if width > height {
+            Orientation::Landscape
         } else {
-            None
-        });
-        sheet_pages.push(if left_outer <= num_pages {
-            Some(left_outer)
+            Orientation::Portrait
+        },
+        format: classify_page_format(width, height),
+        label,
+    }
+}
+
+/// Recognize common paper sizes (either orientation), then slide aspect ratios
+fn classify_page_format(width: f64, height: f64) -> PageFormat {
+    let short = width.min(height);
+    let long = width.max(height);
+
+    const PAPER: [(PageFormat, f64, f64); 5] = [
+        (PageFormat::A3, 842.0, 1191.0),
+        (PageFormat::A4, 595.0, 842.0),
+        (PageFormat::A5, 420.0, 595.0),
+        (PageFormat::Letter, 612.0, 792.0),
+        (PageFormat::Legal, 612.0, 1008.0),
+    ];
+    for (format, paper_short, paper_long) in PAPER {
+        if same_size((short, long), (paper_short, paper_long)) {
+            return format;
+        }
+    }
+
+    // Slides are only recognized in landscape
+    if width > height {
+        let ratio = width / height;
+        for (format, slide_ratio) in [
+            (PageFormat::Slide4x3, 4.0 / 3.0),
+            (PageFormat::Slide16x9, 16.0 / 9.0),
+            (PageFormat::Slide16x10, 16.0 / 10.0),
+        ] {
+            if (ratio - slide_ratio).abs() / slide_ratio < 0.01 {
+                return format;
+            }
+        }
+    }
+    PageFormat::Other
+}
+
+fn generate_booklet_layout_internal(num_pages: u32, sheets_per_signature: u32) -> BookletLayout {
+    // Calculate total sheets needed (4 pages per sheet in booklet mode)
+    let pages_per_sheet = 4;
+    let total_sheets = ((num_pages as f32) / pages_per_sheet as f32).ceil() as u32;
+
+    // Full signatures first; the last one takes the remaining sheets.
+    // 0 keeps the whole document in a single signature.
+    let mut signature_sheets = Vec::new();
+    let mut remaining = total_sheets;
+    while remaining > 0 {
+        let sheets = if sheets_per_signature == 0 {
+            remaining
         } else {
-            None
-        });
+            remaining.min(sheets_per_signature)
+        };
+        signature_sheets.push(sheets);
+        remaining -= sheets;
+    }
 
-        page_order.push(sheet_pages);
+    let mut page_order: Vec<Vec<Option<u32>>> = Vec::new();
+    let mut first_page = 1;
+    for &sheets in &signature_sheets {
+        page_order.extend(signature_page_order(first_page, sheets, num_pages));
+        first_page += sheets * pages_per_sheet;
     }
 
     BookletLayout {
         total_sheets,
         pages_per_sheet,
         page_order,
+        signature_sheets,
     }
 }
 
+/// Page order for one saddle-stitched signature of `sheets` sheets holding
+/// pages `first_page` onwards; pages beyond `num_pages` are blank (`None`).
+fn signature_page_order(first_page: u32, sheets: u32, num_pages: u32) -> Vec<Vec<Option<u32>>> {
+    let total_pages_needed = sheets * 4;
+    let page = |n: u32| {
+        let number = first_page + n - 1;
+        if number <= num_pages {
+            Some(number)
+        } else {
+            None
+        }
+    };
+
+    // For a booklet, pages are arranged as:
+    // Sheet 1: [n, 1, 2, n-1]
+    // Sheet 2: [n-2, 3, 4, n-3]
+    // etc.
+    (0..sheets)
+        .map(|sheet| {
+            vec![
+                // Right side of sheet (when folded)
+                page(total_pages_needed - sheet * 2),
+                page(sheet * 2 + 1),
+                // Left side of sheet (when folded)
+                page(sheet * 2 + 2),
+                page(total_pages_needed - sheet * 2 - 1),
+            ]
+        })
+        .collect()
+}
+
+/// Impose a document as a 2-up saddle-stitch booklet.
+///
+/// Each sheet of `generate_booklet_layout_internal` becomes two output pages
+/// (front and back), each holding two source pages side by side on a landscape
+/// sheet. `None` slots are left blank. Print duplex, flipping on the short edge.
+///
+/// With `settings`, the document is split into signatures that are folded
+/// separately, and creep compensation moves the pages of inner sheets away
+/// from the fold so the text block lines up once the signature is folded.
 pub fn create_booklet_pdf_internal(
     input_path: &str,
     output_path: &str,
+    settings: Option<&BookletSettings>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Verify input file exists
     if !Path::new(input_path).exists() {
         return Err(format!("Input PDF not found for booklet: {}", input_path).into());
     }
 
-    // For now, just copy the document
-    // Full implementation would require complex page reordering
-    eprintln!("[PDF] Creating booklet (currently just copying original)");
-    std::fs::copy(input_path, output_path)
-        .map_err(|e| format!("Failed to copy PDF for booklet: {}", e))?;
-    Ok(())
+    let mut doc = Document::load(input_path)
+        .map_err(|e| format!("Failed to parse PDF for booklet: {}", e))?;
+
+    let page_ids: Vec<ObjectId> = doc.get_pages().values().copied().collect();
+    if page_ids.is_empty() {
+        return Err(format!("PDF has no pages: {}", input_path).into());
+    }
+
+    let sheets_per_signature = settings.map_or(0, |s| s.sheets_per_signature);
+    let creep = settings.map_or(0.0, |s| s.creep_mm) * 72.0 / 25.4;
+    let layout = generate_booklet_layout_internal(page_ids.len() as u32, sheets_per_signature);
+    let forms = pages_to_forms(&mut doc, &page_ids)?;
+
+    // Creep for each sheet: none on the outer sheet of a signature, the full
+    // amount on its innermost sheet
+    let creep_shifts: Vec<f32> = layout
+        .signature_sheets
+        .iter()
+        .flat_map(|&sheets| {
+            (0..sheets).map(move |sheet| {
+                if sheets > 1 {
+                    creep * sheet as f32 / (sheets - 1) as f32
+                } else {
+                    0.0
+                }
+            })
+        })
+        .collect();
+
+    // Sheet is the first page's paper turned landscape, split into two halves
+    let (first_w, first_h) = forms[0].1.size();
+    let sheet_w = first_w.max(first_h);
+    let sheet_h = first_w.min(first_h);
+    let half = sheet_w / 2.0;
+
+    let mut sheets = Vec::new();
+    for (sheet, shift) in layout.page_order.iter().zip(&creep_shifts) {
+        // page_order is [front-left, front-right, back-left, back-right]
+        for side in sheet.chunks(2) {
+            let mut page = ImposedPage::new(sheet_w, sheet_h);
+            for (slot, page_number) in side.iter().enumerate() {
+                if let Some(page_number) = page_number {
+                    let (form_id, geometry) = forms[*page_number as usize - 1];
+                    // Left pages move left and right pages move right, away from the fold
+                    let x = slot as f32 * half + if slot == 0 { -shift } else { *shift };
+                    let cell = Rect::new(x, 0.0, half, sheet_h);
+                    page.place_form(form_id, geometry, cell);
+                }
+            }
+            sheets.push(page);
+        }
+    }
+
+    eprintln!(
+        "[PDF] Booklet: {} pages -> {} sheets in {} signatures ({} sides)",
+        page_ids.len(),
+        layout.total_sheets,
+        layout.signature_sheets.len(),
+        sheets.len()
+    );
+    replace_page_tree(&mut doc, sheets)?;
+    save_document(&mut doc, output_path)
 }
 
+/// Impose `pages_per_sheet` source pages onto each output sheet.
+///
+/// Supported values map to fixed grids: 2 (2x1), 4 (2x2), 6 (3x2), 8 (4x2),
+/// 9 (3x3) and 16 (4x4). The grid may be transposed when that fits the pages better.
+/// `orientation` fixes the sheet orientation; `Auto` picks the one that fits best.
 pub fn create_nup_pdf_internal(
     input_path: &str,
     output_path: &str,
     pages_per_sheet: u32,
+    orientation: Orientation,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (cols, rows) = nup_grid(pages_per_sheet)
+        .ok_or_else(|| format!("Unsupported pages per sheet: {}", pages_per_sheet))?;
+    impose_nup(input_path, output_path, cols, rows, true, orientation)
+}
+
+/// Impose source pages onto an exact `cols` x `rows` grid per sheet
+pub fn create_nup_grid_pdf_internal(
+    input_path: &str,
+    output_path: &str,
+    cols: u32,
+    rows: u32,
+    orientation: Orientation,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if cols == 0 || rows == 0 {
+        return Err(format!("Invalid n-up grid: {}x{}", cols, rows).into());
+    }
+    impose_nup(input_path, output_path, cols, rows, false, orientation)
+}
+
+/// Grid (columns, rows) used for a pages-per-sheet preset
+fn nup_grid(pages_per_sheet: u32) -> Option<(u32, u32)> {
+    match pages_per_sheet {
+        1 => Some((1, 1)),
+        2 => Some((2, 1)),
+        4 => Some((2, 2)),
+        6 => Some((3, 2)),
+        8 => Some((4, 2)),
+        9 => Some((3, 3)),
+        16 => Some((4, 4)),
+        _ => None,
+    }
+}
+
+fn impose_nup(
+    input_path: &str,
+    output_path: &str,
+    cols: u32,
+    rows: u32,
+    allow_transpose: bool,
+    orientation: Orientation,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Verify input file exists
     if !Path::new(input_path).exists() {
         return Err(format!("Input PDF not found for n-up: {}", input_path).into());
     }
 
-    // Use pdfjam command for n-up printing
-    // pdfjam --nup [columns]x[rows] input.pdf -o output.pdf
+    let mut doc = Document::load(input_path)
+        .map_err(|e| format!("Failed to parse PDF for n-up: {}", e))?;
+
+    let page_ids: Vec<ObjectId> = doc.get_pages().values().copied().collect();
+    if page_ids.is_empty() {
+        return Err(format!("PDF has no pages: {}", input_path).into());
+    }
+
+    let forms = pages_to_forms(&mut doc, &page_ids)?;
+    let layout = choose_nup_layout(forms[0].1.size(), cols, rows, allow_transpose, orientation);
 
-    let (cols, rows) = match pages_per_sheet {
-        2 => (2, 1),
-        4 => (2, 2),
-        6 => (3, 2),
-        9 => (3, 3),
-        _ => {
-            // Fallback: just copy the document
-            eprintln!("[PDF] Unsupported pages_per_sheet {}, copying original", pages_per_sheet);
-            std::fs::copy(input_path, output_path)
-                .map_err(|e| format!("Failed to copy PDF: {}", e))?;
-            return Ok(());
+    let per_sheet = (layout.cols * layout.rows) as usize;
+    let cell_w = layout.sheet_width / layout.cols as f32;
+    let cell_h = layout.sheet_height / layout.rows as f32;
+
+    let mut sheets = Vec::new();
+    for chunk in forms.chunks(per_sheet) {
+        let mut page = ImposedPage::new(layout.sheet_width, layout.sheet_height);
+        for (index, (form_id, geometry)) in chunk.iter().enumerate() {
+            // Reading order: left to right, top to bottom
+            let col = (index as u32 % layout.cols) as f32;
+            let row = (index as u32 / layout.cols) as f32;
+            let cell = Rect::new(
+                col * cell_w,
+                layout.sheet_height - (row + 1.0) * cell_h,
+                cell_w,
+                cell_h,
+            );
+            page.place_form_auto_rotate(*form_id, *geometry, cell);
         }
-    };
+        sheets.push(page);
+    }
+
+    eprintln!(
+        "[PDF] N-up {}x{} on {:.0}x{:.0}pt: {} pages -> {} sheets",
+        layout.cols,
+        layout.rows,
+        layout.sheet_width,
+        layout.sheet_height,
+        page_ids.len(),
+        sheets.len()
+    );
+    replace_page_tree(&mut doc, sheets)?;
+    save_document(&mut doc, output_path)
+}
+
+/// Margin around handout pages, in points
+const HANDOUT_MARGIN: f32 = 36.0;
+
+/// Distance between ruled note lines, in points
+const NOTE_LINE_SPACING: f32 = 20.0;
+
+/// Lay slides out as a handout on portrait paper: three per page with ruled
+/// note lines beside them, or two stacked with free space below
+pub fn create_handout_pdf_internal(
+    input_path: &str,
+    output_path: &str,
+    handout: &HandoutSettings,
+    paper_size: &PaperSize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !Path::new(input_path).exists() {
+        return Err(format!("Input PDF not found for handout: {}", input_path).into());
+    }
+    let mut doc = Document::load(input_path)
+        .map_err(|e| format!("Failed to parse PDF for handout: {}", e))?;
+
+    let page_ids: Vec<ObjectId> = doc.get_pages().values().copied().collect();
+    if page_ids.is_empty() {
+        return Err(format!("PDF has no pages: {}", input_path).into());
+    }
+    let forms = pages_to_forms(&mut doc, &page_ids)?;
+    let font_id = handout.numbering.then(|| standard_font(&mut doc, "Helvetica"));
 
-    // Try to use pdfjam command
-    let output = std::process::Command::new("pdfjam")
-        .arg("--nup")
-        .arg(format!("{}x{}", cols, rows))
-        .arg(input_path)
-        .arg("-o")
-        .arg(output_path)
-        .arg("--quiet")
-        .output();
+    let (width, height) = paper_size.dimensions();
+    let (width, height) = (width as f32, height as f32);
+    let gap = 18.0;
+    let label_space = if handout.numbering { 14.0 } else { 0.0 };
+    let inner_width = width - 2.0 * HANDOUT_MARGIN;
+    let inner_height = height - 2.0 * HANDOUT_MARGIN;
 
-    match output {
-        Ok(result) if result.status.success() => {
-            eprintln!("[PDF] pdfjam n-up succeeded");
-            Ok(())
+    // Slide cells (and the note area beside each) from top to bottom
+    let cells: Vec<(Rect, Option<Rect>)> = match handout.layout {
+        HandoutLayout::ThreeWithNotes => {
+            let column = (inner_width - gap) / 2.0;
+            let row = inner_height / 3.0;
+            (0..handout.layout.slides_per_page())
+                .map(|index| {
+                    let y = height - HANDOUT_MARGIN - (index + 1) as f32 * row + label_space + gap / 2.0;
+                    let cell_height = row - label_space - gap;
+                    (
+                        Rect::new(HANDOUT_MARGIN, y, column, cell_height),
+                        Some(Rect::new(HANDOUT_MARGIN + column + gap, y, column, cell_height)),
+                    )
+                })
+                .collect()
         }
-        Ok(result) => {
-            let stderr = String::from_utf8_lossy(&result.stderr);
-            eprintln!("[PDF] pdfjam n-up failed: {}", stderr);
-            // Fallback: copy original file
-            eprintln!("[PDF] Falling back to original file (n-up will not be applied)");
-            std::fs::copy(input_path, output_path)
-                .map_err(|e| format!("Failed to copy PDF: {}", e))?;
-            Ok(())
+        HandoutLayout::TwoStacked => {
+            // Slides take the top two thirds; the rest is left for notes
+            let row = inner_height * 2.0 / 3.0 / 2.0;
+            (0..handout.layout.slides_per_page())
+                .map(|index| {
+                    let y = height - HANDOUT_MARGIN - (index + 1) as f32 * row + label_space + gap / 2.0;
+                    (Rect::new(HANDOUT_MARGIN, y, inner_width, row - label_space - gap), None)
+                })
+                .collect()
         }
-        Err(e) => {
-            // pdfjam not available, fall back to copying
-            eprintln!("[PDF] pdfjam not available ({}), copying original file", e);
-            std::fs::copy(input_path, output_path)
-                .map_err(|e| format!("Failed to copy PDF: {}", e))?;
-            Ok(())
+    };
+
+    let mut sheets = Vec::new();
+    for (sheet_index, chunk) in forms.chunks(cells.len()).enumerate() {
+        let mut page = ImposedPage::new(width, height);
+        for (index, ((form_id, geometry), (cell, notes))) in chunk.iter().zip(&cells).enumerate() {
+            // Top-aligned so the number sits right under the slide; centered
+            // across the page when there is no note column
+            let (slide_width, slide_height) = geometry.size();
+            let scale = (cell.width / slide_width).min(cell.height / slide_height);
+            let (placed_width, placed_height) = (slide_width * scale, slide_height * scale);
+            let x = if notes.is_some() {
+                cell.x
+            } else {
+                cell.x + (cell.width - placed_width) / 2.0
+            };
+            let y = cell.y + cell.height - placed_height;
+            page.draw_form(*form_id, [scale, 0.0, 0.0, scale, x, y]);
+
+            if handout.frame {
+                page.draw_rect(Rect::new(x, y, placed_width, placed_height), 0.5);
+            }
+            if let Some(font_id) = font_id {
+                let number = sheet_index * cells.len() + index + 1;
+                page.draw_text(font_id, 8.0, x, y - 10.0, &format!("Slide {}", number));
+            }
+            if let Some(notes) = notes {
+                page.set_gray(0.6);
+                let mut line_y = y + placed_height - NOTE_LINE_SPACING;
+                while line_y >= y {
+                    page.draw_line(notes.x, line_y, notes.x + notes.width, line_y, 0.5);
+                    line_y -= NOTE_LINE_SPACING;
+                }
+                page.set_gray(0.0);
+            }
         }
+        sheets.push(page);
     }
+
+    eprintln!(
+        "[PDF] Handout {:?}: {} slides -> {} pages",
+        handout.layout,
+        forms.len(),
+        sheets.len()
+    );
+    replace_page_tree(&mut doc, sheets)?;
+    save_document(&mut doc, output_path)
 }
 
-/// Extract page range from PDF
-#[allow(dead_code)]
-pub fn extract_page_range(
+/// Margin around each poster tile for crop marks and labels, in points (10 mm)
+const POSTER_MARGIN: f32 = 28.35;
+
+/// Enlarge every page across a grid of sheets for printing as a poster.
+/// Neighbouring tiles repeat `overlap_mm` of content so they can be trimmed
+/// and glued; crop marks show the edges of each tile's area.
+pub fn create_poster_pdf_internal(
     input_path: &str,
     output_path: &str,
-    _page_range: &PageRange,
+    poster: &PosterSettings,
+    paper_size: &PaperSize,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Verify input file exists
+    if !(1..=10).contains(&poster.cols) || !(1..=10).contains(&poster.rows) {
+        return Err(format!("Invalid poster grid: {}x{}", poster.cols, poster.rows).into());
+    }
     if !Path::new(input_path).exists() {
-        return Err(format!("Input PDF not found: {}", input_path).into());
+        return Err(format!("Input PDF not found for poster: {}", input_path).into());
     }
+    let mut doc = Document::load(input_path)
+        .map_err(|e| format!("Failed to parse PDF for poster: {}", e))?;
 
-    // For now, just copy the entire document
-    // Full implementation would filter pages
-    std::fs::copy(input_path, output_path)
-        .map_err(|e| format!("Failed to copy PDF: {}", e))?;
-    Ok(())
+    let page_ids: Vec<ObjectId> = doc.get_pages().values().copied().collect();
+    if page_ids.is_empty() {
+        return Err(format!("PDF has no pages: {}", input_path).into());
+    }
+    let forms = pages_to_forms(&mut doc, &page_ids)?;
+    let font_id = poster.labels.then(|| standard_font(&mut doc, "Helvetica"));
+
+    let (paper_width, paper_height) = paper_size.dimensions();
+    let overlap = poster.overlap_mm.max(0.0) * 72.0 / 25.4;
+    let (cols, rows) = (poster.cols as f32, poster.rows as f32);
+
+    let mut sheets = Vec::new();
+    for (page_index, (form_id, geometry)) in forms.iter().enumerate() {
+        let (page_width, page_height) = geometry.size();
+
+        // Portrait or landscape sheets, whichever enlarges the page more
+        let mut best: Option<(f32, f32, f32)> = None;
+        for (sheet_width, sheet_height) in [
+            (paper_width as f32, paper_height as f32),
+            (paper_height as f32, paper_width as f32),
+        ] {
+            let tile_width = sheet_width - 2.0 * POSTER_MARGIN;
+            let tile_height = sheet_height - 2.0 * POSTER_MARGIN;
+            if overlap * 2.0 >= tile_width.min(tile_height) {
+                return Err(format!("Overlap of {} mm is too large for the paper", poster.overlap_mm).into());
+            }
+            let poster_width = cols * tile_width - (cols - 1.0) * overlap;
+            let poster_height = rows * tile_height - (rows - 1.0) * overlap;
+            let scale = (poster_width / page_width).min(poster_height / page_height);
+            if best.is_none_or(|(best_scale, _, _)| scale > best_scale + 1e-4) {
+                best = Some((scale, sheet_width, sheet_height));
+            }
+        }
+        let Some((scale, sheet_width, sheet_height)) = best else { continue };
+        let tile_width = sheet_width - 2.0 * POSTER_MARGIN;
+        let tile_height = sheet_height - 2.0 * POSTER_MARGIN;
+        let poster_width = cols * tile_width - (cols - 1.0) * overlap;
+        let poster_height = rows * tile_height - (rows - 1.0) * overlap;
+        // Center the enlarged page on the poster
+        let offset_x = (poster_width - page_width * scale) / 2.0;
+        let offset_y = (poster_height - page_height * scale) / 2.0;
+
+        for row in 0..poster.rows {
+            for col in 0..poster.cols {
+                // Poster coordinates of the tile's lower left corner; row 1 is the top
+                let tile_x = col as f32 * (tile_width - overlap);
+                let tile_y = poster_height - tile_height - row as f32 * (tile_height - overlap);
+
+                let mut page = ImposedPage::new(sheet_width, sheet_height);
+                page.save_state();
+                page.clip_rect(Rect::new(POSTER_MARGIN, POSTER_MARGIN, tile_width, tile_height));
+                page.draw_form(
+                    *form_id,
+                    [
+                        scale,
+                        0.0,
+                        0.0,
+                        scale,
+                        POSTER_MARGIN - tile_x + offset_x,
+                        POSTER_MARGIN - tile_y + offset_y,
+                    ],
+                );
+                page.restore_state();
+
+                if poster.crop_marks {
+                    page.draw_crop_marks(Rect::new(POSTER_MARGIN, POSTER_MARGIN, tile_width, tile_height));
+                }
+                if let Some(font_id) = font_id {
+                    let mut label = format!("row {}, col {}", row + 1, col + 1);
+                    if forms.len() > 1 {
+                        label = format!("page {}, {}", page_index + 1, label);
+                    }
+                    page.draw_text(font_id, 8.0, POSTER_MARGIN, POSTER_MARGIN / 2.0 - 3.0, &label);
+                }
+                sheets.push(page);
+            }
+        }
+    }
+
+    eprintln!(
+        "[PDF] Poster {}x{}: {} pages -> {} sheets",
+        poster.cols,
+        poster.rows,
+        forms.len(),
+        sheets.len()
+    );
+    replace_page_tree(&mut doc, sheets)?;
+    save_document(&mut doc, output_path)
+}
+
+/// Sheet orientation and grid picked for an n-up job
+#[derive(Debug, Clone, Copy)]
+struct NupLayout {
+    sheet_width: f32,
+    sheet_height: f32,
+    cols: u32,
+    rows: u32,
+}
+
+/// Pick the sheet orientation (and grid transposition, if allowed) that gives
+/// the largest upright scale for pages of `page_size`. The sheet is the page's
+/// paper size; portrait wins ties unless `orientation` fixes it. Odd-sized
+/// pages are rotated per cell later.
+fn choose_nup_layout(
+    page_size: (f32, f32),
+    cols: u32,
+    rows: u32,
+    allow_transpose: bool,
+    orientation: Orientation,
+) -> NupLayout {
+    let (page_w, page_h) = page_size;
+    let short = page_w.min(page_h);
+    let long = page_w.max(page_h);
+
+    let mut grids = vec![(cols, rows)];
+    if allow_transpose && cols != rows {
+        grids.push((rows, cols));
+    }
+
+    let sheets = match orientation {
+        Orientation::Portrait => vec![(short, long)],
+        Orientation::Landscape => vec![(long, short)],
+        Orientation::Auto => vec![(short, long), (long, short)],
+    };
+
+    let mut best: Option<(f32, NupLayout)> = None;
+    for (sheet_width, sheet_height) in sheets {
+        for &(cols, rows) in &grids {
+            let cell_w = sheet_width / cols as f32;
+            let cell_h = sheet_height / rows as f32;
+            let scale = (cell_w / page_w).min(cell_h / page_h);
+            if best.is_none_or(|(best_scale, _)| scale > best_scale + 1e-4) {
+                best = Some((
+                    scale,
+                    NupLayout {
+                        sheet_width,
+                        sheet_height,
+                        cols,
+                        rows,
+                    },
+                ));
+            }
+        }
+    }
+    best.map(|(_, layout)| layout).unwrap_or(NupLayout {
+        sheet_width: short,
+        sheet_height: long,
+        cols,
+        rows,
+    })
+}
+
+/// Options for normalizing pages onto the selected paper
+#[derive(Debug, Clone)]
+pub struct FitOptions {
+    pub paper_size: PaperSize,
+    pub scale_mode: ScaleMode,
+    /// Sheet orientation content is laid out on; `Auto` follows each page
+    pub orientation: Orientation,
+    /// Turn landscape content on back sides (even pages) the other way, so
+    /// sheets read correctly when a long-edge duplex queue is used as short-edge
+    pub short_edge_flip: bool,
+}
+
+/// Normalize every page onto the selected paper, portrait.
+///
+/// Each page is laid out upright on a portrait or landscape sheet, chosen by
+/// the orientation option (per page for `Auto`); landscape sheets are turned
+/// onto the paper. Content is centered and scaled according to the scale mode.
+/// Documents that already match the paper are copied unchanged.
+pub fn fit_to_paper_pdf_internal(
+    input_path: &str,
+    output_path: &str,
+    options: &FitOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Verify input file exists
+    if !Path::new(input_path).exists() {
+        return Err(format!("Input PDF not found for scaling: {}", input_path).into());
+    }
+
+    let mut doc = Document::load(input_path)
+        .map_err(|e| format!("Failed to parse PDF for scaling: {}", e))?;
+
+    let page_ids: Vec<ObjectId> = doc.get_pages().values().copied().collect();
+    if page_ids.is_empty() {
+        return Err(format!("PDF has no pages: {}", input_path).into());
+    }
+
+    let (paper_w, paper_h) = options.paper_size.dimensions();
+    let (paper_w, paper_h) = (paper_w as f32, paper_h as f32);
+
+    let already_fits = options.orientation != Orientation::Landscape
+        && page_ids.iter().all(|&page_id| {
+            let geometry = page_geometry(&doc, page_id);
+            let (width, height) = geometry.size();
+            geometry.rotate == 0
+                && same_size((width as f64, height as f64), (paper_w as f64, paper_h as f64))
+        });
+    if already_fits {
+        eprintln!("[PDF] All pages already match {:?}, no scaling needed", options.paper_size);
+        std::fs::copy(input_path, output_path)
+            .map_err(|e| format!("Failed to copy PDF: {}", e))?;
+        return Ok(());
+    }
+
+    let forms = pages_to_forms(&mut doc, &page_ids)?;
+    let paper = Rect::new(0.0, 0.0, paper_w, paper_h);
+
+    let mut pages = Vec::with_capacity(forms.len());
+    for (index, (form_id, geometry)) in forms.iter().enumerate() {
+        let (width, height) = geometry.size();
+        let landscape_sheet = match options.orientation {
+            Orientation::Portrait => false,
+            Orientation::Landscape => true,
+            Orientation::Auto => width > height,
+        };
+        let turn = if landscape_sheet {
+            if options.short_edge_flip && index % 2 == 1 {
+                Turn::Cw
+            } else {
+                Turn::Ccw
+            }
+        } else {
+            Turn::None
+        };
+
+        let mut page = ImposedPage::new(paper_w, paper_h);
+        page.draw_form(*form_id, placement_matrix((width, height), paper, turn, options.scale_mode));
+        pages.push(page);
+    }
+
+    eprintln!(
+        "[PDF] Scaled {} pages onto {:?} ({:?})",
+        pages.len(),
+        options.paper_size,
+        options.scale_mode
+    );
+    replace_page_tree(&mut doc, pages)?;
+    save_document(&mut doc, output_path)
+}
+
+/// Extract the pages selected by `page_range` into a new PDF, in selection order
+pub fn extract_page_range(
+    input_path: &str,
+    output_path: &str,
+    page_range: &PageRange,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Verify input file exists
+    if !Path::new(input_path).exists() {
+        return Err(format!("Input PDF not found: {}", input_path).into());
+    }
+
+    let mut doc = Document::load(input_path)
+        .map_err(|e| format!("Failed to parse PDF for page range: {}", e))?;
+
+    let pages = doc.get_pages();
+    let selected = resolve_document_page_range(&doc, page_range)?;
+    let page_ids: Vec<ObjectId> = selected.iter().map(|n| pages[n]).collect();
+
+    eprintln!("[PDF] Extracting {} of {} pages", page_ids.len(), pages.len());
+    rebuild_page_tree(&mut doc, &page_ids)?;
+    save_document(&mut doc, output_path)
+}
+
+/// Resolve a page range against a loaded document, including ranges that
+/// depend on its outline or text
+pub fn resolve_document_page_range(doc: &Document, page_range: &PageRange) -> Result<Vec<u32>, String> {
+    let num_pages = doc.get_pages().len() as u32;
+    match page_range {
+        PageRange::Outline { .. } => resolve_page_range(page_range, num_pages, &document_outline(doc)),
+        PageRange::Keyword {
+            pattern,
+            regex,
+            case_sensitive,
+        } => {
+            let matcher = search_regex(pattern, *regex, *case_sensitive)?;
+            let pages: Vec<u32> = search_document_text(doc, &matcher)
+                .into_iter()
+                .filter(|page| page.matches > 0)
+                .map(|page| page.number)
+                .collect();
+            if pages.is_empty() {
+                return Err(format!("No pages contain '{}'", pattern));
+            }
+            Ok(pages)
+        }
+        _ => resolve_page_range(page_range, num_pages, &[]),
+    }
+}
+
+/// Load a PDF and resolve a page range against it
+pub fn resolve_file_page_range(
+    file_path: &str,
+    page_range: &PageRange,
+    password: Option<&str>,
+) -> Result<Vec<u32>, Box<dyn std::error::Error>> {
+    if !Path::new(file_path).exists() {
+        return Err(format!("PDF file not found: {}", file_path).into());
+    }
+    let doc = load_document(file_path, password).map_err(|e| match e {
+        lopdf::Error::InvalidPassword => format!("Incorrect password for {}", file_path),
+        e => format!("Failed to parse PDF for page range: {}", e),
+    })?;
+    if doc.is_encrypted() {
+        return Err(format!("PDF is password protected: {}", file_path).into());
+    }
+    Ok(resolve_document_page_range(&doc, page_range)?)
+}
+
+/// Resolve a page range into 1-based page numbers, validated against `num_pages`.
+/// `outline` is only needed for `PageRange::Outline`; keyword ranges need the
+/// document text (see `resolve_document_page_range`).
+pub fn resolve_page_range(
+    page_range: &PageRange,
+    num_pages: u32,
+    outline: &[OutlineEntry],
+) -> Result<Vec<u32>, String> {
+    let pages: Vec<u32> = match page_range {
+        PageRange::All => (1..=num_pages).collect(),
+        PageRange::Range { start, end } => {
+            if *start == 0 || start > end {
+                return Err(format!("Invalid page range {}-{}", start, end));
+            }
+            if *end > num_pages {
+                return Err(format!(
+                    "Page range {}-{} exceeds document length ({} pages)",
+                    start, end, num_pages
+                ));
+            }
+            (*start..=*end).collect()
+        }
+        PageRange::Selection { pages } => {
+            if let Some(page) = pages.iter().find(|p| **p == 0 || **p > num_pages) {
+                return Err(format!(
+                    "Page {} is out of range (document has {} pages)",
+                    page, num_pages
+                ));
+            }
+            pages.clone()
+        }
+        PageRange::Outline { entries } => outline_pages(outline, entries)?,
+        PageRange::Keyword { .. } => {
+            return Err("Searching for pages by text needs the PDF itself".to_string());
+        }
+    };
+
+    if pages.is_empty() {
+        return Err("Page selection is empty".to_string());
+    }
+    Ok(pages)
+}
+
+/// Concatenate several PDFs, in the given order, into one document.
+///
+/// With `pad_to_even`, a blank page is appended to every document with an odd
+/// page count so each part starts on a new sheet when printed duplex.
+pub fn merge_pdfs_internal(
+    input_paths: &[String],
+    output_path: &str,
+    pad_to_even: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if input_paths.is_empty() {
+        return Err("No PDFs to merge".into());
+    }
+
+    let mut merged = empty_document();
+    let mut page_ids = Vec::new();
+
+    for path in input_paths {
+        if !Path::new(path).exists() {
+            return Err(format!("Input PDF not found for merging: {}", path).into());
+        }
+        let mut doc = Document::load(path)
+            .map_err(|e| format!("Failed to parse PDF {}: {}", path, e))?;
+
+        // Move the document's objects into the merged one under fresh numbers;
+        // its old catalog and page tree become unreachable and get pruned
+        doc.renumber_objects_with(merged.max_id + 1);
+        let pages: Vec<ObjectId> = doc.get_pages().values().copied().collect();
+        if pages.is_empty() {
+            return Err(format!("PDF has no pages: {}", path).into());
+        }
+        if doc.version > merged.version {
+            merged.version = doc.version.clone();
+        }
+        merged.max_id = doc.max_id;
+        merged.objects.extend(doc.objects);
+
+        let last_page = *pages.last().unwrap();
+        page_ids.extend(&pages);
+        if pad_to_even && pages.len() % 2 == 1 {
+            let (width, height) = page_geometry(&merged, last_page).size();
+            page_ids.push(add_blank_page(&mut merged, width, height));
+        }
+    }
+
+    eprintln!("[PDF] Merged {} documents into {} pages", input_paths.len(), page_ids.len());
+    rebuild_page_tree(&mut merged, &page_ids)?;
+    save_document(&mut merged, output_path)
+}
+
+/// How copies are laid out by `arrange_copies_internal`
+#[derive(Debug, Clone)]
+pub struct CopyOptions {
+    pub copies: u32,
+    /// Repeat the whole document per copy instead of each sheet
+    pub collate: bool,
+    /// Last sheet first
+    pub reverse: bool,
+    /// Pages are printed in pairs on the two sides of a sheet
+    pub duplex: bool,
+}
+
+/// Lay out copies and output order in the document itself, for when lpr
+/// cannot express them.
+///
+/// Pages are grouped into sheets: pairs when duplex, with a blank back added
+/// to an odd page count so no two copies share a sheet. Collated copies repeat
+/// the whole document, uncollated copies repeat each sheet, and reversing
+/// swaps the order of whole sheets so fronts and backs stay together.
+pub fn arrange_copies_internal(
+    input_path: &str,
+    output_path: &str,
+    options: &CopyOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !Path::new(input_path).exists() {
+        return Err(format!("Input PDF not found for copies: {}", input_path).into());
+    }
+    let mut doc = Document::load(input_path)
+        .map_err(|e| format!("Failed to parse PDF for copies: {}", e))?;
+
+    let pages: Vec<ObjectId> = doc.get_pages().values().copied().collect();
+    let Some(&last_page) = pages.last() else {
+        return Err(format!("PDF has no pages: {}", input_path).into());
+    };
+
+    let mut sheets: Vec<Vec<ObjectId>> = if options.duplex {
+        let mut sheets: Vec<Vec<ObjectId>> = pages.chunks(2).map(<[ObjectId]>::to_vec).collect();
+        if pages.len() % 2 == 1 {
+            let (width, height) = page_geometry(&doc, last_page).size();
+            let blank = add_blank_page(&mut doc, width, height);
+            sheets.last_mut().unwrap().push(blank);
+        }
+        sheets
+    } else {
+        pages.iter().map(|&page| vec![page]).collect()
+    };
+    if options.reverse {
+        sheets.reverse();
+    }
+
+    let copies = options.copies.max(1) as usize;
+    let page_ids: Vec<ObjectId> = if options.collate {
+        sheets.concat().repeat(copies)
+    } else {
+        sheets.iter().flat_map(|sheet| sheet.repeat(copies)).collect()
+    };
+
+    eprintln!(
+        "[PDF] Copies: {} pages x {} ({}, {}) -> {} pages",
+        pages.len(),
+        copies,
+        if options.collate { "collated" } else { "uncollated" },
+        if options.reverse { "reversed" } else { "in order" },
+        page_ids.len()
+    );
+    rebuild_page_tree(&mut doc, &page_ids)?;
+    save_document(&mut doc, output_path)
+}
+
+/// Side of the sheets printed by one pass of manual duplex
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SheetSide {
+    /// Odd pages
+    Front,
+    /// Even pages
+    Back,
+}
+
+/// Extract the pages one pass of manual duplex prints.
+///
+/// A blank back is added when the page count is odd, so both passes have one
+/// page per sheet. With `reverse` the pass prints last page first.
+pub fn manual_duplex_side_internal(
+    input_path: &str,
+    output_path: &str,
+    side: SheetSide,
+    reverse: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !Path::new(input_path).exists() {
+        return Err(format!("Input PDF not found for manual duplex: {}", input_path).into());
+    }
+    let mut doc = Document::load(input_path)
+        .map_err(|e| format!("Failed to parse PDF for manual duplex: {}", e))?;
+
+    let pages: Vec<ObjectId> = doc.get_pages().values().copied().collect();
+    let Some(&last_page) = pages.last() else {
+        return Err(format!("PDF has no pages: {}", input_path).into());
+    };
+
+    let first = match side {
+        SheetSide::Front => 0,
+        SheetSide::Back => 1,
+    };
+    let mut side_pages: Vec<Option<ObjectId>> = (first..pages.len().next_multiple_of(2))
+        .step_by(2)
+        .map(|index| pages.get(index).copied())
+        .collect();
+    if reverse {
+        side_pages.reverse();
+    }
+
+    let blank = side_pages.contains(&None).then(|| {
+        let (width, height) = page_geometry(&doc, last_page).size();
+        add_blank_page(&mut doc, width, height)
+    });
+    let page_ids: Vec<ObjectId> = side_pages
+        .into_iter()
+        .map(|page| page.or(blank).unwrap())
+        .collect();
+
+    eprintln!("[PDF] Manual duplex {:?}: {} pages -> {} sheets", side, pages.len(), page_ids.len());
+    rebuild_page_tree(&mut doc, &page_ids)?;
+    save_document(&mut doc, output_path)
+}
+
+/// Add an empty page that is not yet part of the page tree
+fn add_blank_page(doc: &mut Document, width: f32, height: f32) -> ObjectId {
+    doc.add_object(dictionary! {
+        "Type" => "Page",
+        "MediaBox" => vec![0.into(), 0.into(), Object::Real(width), Object::Real(height)],
+        "Resources" => dictionary! {},
+    })
+}
+
+/// Margin between header/footer stamps and the page edge, in points
+const STAMP_MARGIN: f32 = 24.0;
+
+/// Overlay header/footer text and an optional translucent diagonal watermark
+/// on every page.
+///
+/// `{n}` and `{N}` in the template are replaced with the page number and page
+/// count; job-level placeholders are expected to be filled in by the caller.
+/// Text outside Windows-1252 is rejected, since the stamp font can't show it.
+/// Stamps are laid out on the page as displayed, so rotated pages read upright.
+pub fn stamp_pdf_internal(
+    input_path: &str,
+    output_path: &str,
+    stamp: &StampSettings,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Verify input file exists
+    if !Path::new(input_path).exists() {
+        return Err(format!("Input PDF not found for stamping: {}", input_path).into());
+    }
+    if !(4.0..=72.0).contains(&stamp.font_size) {
+        return Err(format!("Stamp font size {} is out of range (4-72)", stamp.font_size).into());
+    }
+
+    let template = stamp.template.replace(['\r', '\n'], " ");
+    let template = template.trim();
+    let watermark = stamp.watermark.as_deref().map(str::trim).unwrap_or_default();
+    if template.is_empty() && watermark.is_empty() {
+        std::fs::copy(input_path, output_path)
+            .map_err(|e| format!("Failed to copy PDF: {}", e))?;
+        return Ok(());
+    }
+    // Helvetica only covers Windows-1252; other characters would print as '?'
+    let mut unprintable = Vec::new();
+    for c in template.chars().chain(watermark.chars()) {
+        if win_ansi_byte(c).is_none() && !unprintable.contains(&c) {
+            unprintable.push(c);
+        }
+    }
+    if !unprintable.is_empty() {
+        let unprintable: String = unprintable.into_iter().collect();
+        return Err(format!("Stamp text contains characters the stamp font cannot print: {}", unprintable).into());
+    }
+
+    let mut doc = Document::load(input_path)
+        .map_err(|e| format!("Failed to parse PDF for stamping: {}", e))?;
+    let page_ids: Vec<ObjectId> = doc.get_pages().values().copied().collect();
+    if page_ids.is_empty() {
+        return Err(format!("PDF has no pages: {}", input_path).into());
+    }
+
+    let font_id = standard_font(&mut doc, "Helvetica");
+    let opacity = stamp.opacity.clamp(0.0, 1.0);
+    let state_id = doc.add_object(dictionary! {
+        "Type" => "ExtGState",
+        "ca" => Object::Real(opacity),
+        "CA" => Object::Real(opacity),
+    });
+
+    let forms = pages_to_forms(&mut doc, &page_ids)?;
+    let page_count = forms.len();
+    let mut pages = Vec::with_capacity(page_count);
+    for (index, (form_id, geometry)) in forms.into_iter().enumerate() {
+        let (width, height) = geometry.size();
+        let mut page = ImposedPage::new(width, height);
+        page.draw_form(form_id, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+        if !watermark.is_empty() {
+            let diagonal = (width * width + height * height).sqrt();
+            let size = (0.7 * diagonal / helvetica_width(watermark, 1.0)).min(height * 0.3);
+            let angle = height.atan2(width);
+            let (sin, cos) = angle.sin_cos();
+            page.save_state();
+            page.set_graphics_state(state_id);
+            page.set_gray(0.5);
+            page.transform([cos, sin, -sin, cos, width / 2.0, height / 2.0]);
+            let text_width = helvetica_width(watermark, size);
+            page.draw_text(font_id, size, -text_width / 2.0, -size * 0.35, watermark);
+            page.restore_state();
+        }
+
+        if !template.is_empty() {
+            let text = template
+                .replace("{n}", &(index + 1).to_string())
+                .replace("{N}", &page_count.to_string());
+            let size = stamp.font_size;
+            let text_width = helvetica_width(&text, size);
+            let x = match stamp.position {
+                StampPosition::TopLeft | StampPosition::BottomLeft => STAMP_MARGIN,
+                StampPosition::TopCenter | StampPosition::BottomCenter => (width - text_width) / 2.0,
+                StampPosition::TopRight | StampPosition::BottomRight => width - STAMP_MARGIN - text_width,
+            };
+            let y = match stamp.position {
+                StampPosition::TopLeft | StampPosition::TopCenter | StampPosition::TopRight => {
+                    height - STAMP_MARGIN - size * 0.75
+                }
+                _ => STAMP_MARGIN,
+            };
+            page.draw_text(font_id, size, x, y, &text);
+        }
+        pages.push(page);
+    }
+
+    eprintln!("[PDF] Stamped {} pages", pages.len());
+    replace_page_tree(&mut doc, pages)?;
+    save_document(&mut doc, output_path)
+}
+
+/// Options for converting images into PDF pages
+#[derive(Debug, Clone)]
+pub struct ImageToPdfOptions {
+    pub paper_size: PaperSize,
+    /// Blank margin around each image, in millimetres
+    pub margin_mm: f32,
+    /// Page orientation; `Auto` follows each image's (EXIF-corrected) aspect ratio
+    pub orientation: Orientation,
+}
+
+/// Build a PDF with one page per image, each fit centered on the paper.
+///
+/// JPEGs are embedded as-is; other images are decoded and stored losslessly.
+/// The EXIF orientation is applied when placing the image, so photos taken
+/// sideways come out upright.
+pub fn images_to_pdf_internal(
+    image_paths: &[String],
+    output_path: &str,
+    options: &ImageToPdfOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if image_paths.is_empty() {
+        return Err("No images to convert".into());
+    }
+
+    let (paper_w, paper_h) = options.paper_size.dimensions();
+    let (paper_w, paper_h) = (paper_w as f32, paper_h as f32);
+    let margin = options.margin_mm.max(0.0) * 72.0 / 25.4;
+    if 2.0 * margin >= paper_w.min(paper_h) {
+        return Err(format!("Margin of {} mm leaves no room on the paper", options.margin_mm).into());
+    }
+
+    let mut doc = empty_document();
+    let mut pages = Vec::with_capacity(image_paths.len());
+    for path in image_paths {
+        let image = embed_image(&mut doc, path)?;
+        let size = image.display_size();
+        let landscape = match options.orientation {
+            Orientation::Portrait => false,
+            Orientation::Landscape => true,
+            Orientation::Auto => size.0 > size.1,
+        };
+        let (width, height) = if landscape { (paper_h, paper_w) } else { (paper_w, paper_h) };
+
+        let area = Rect::new(margin, margin, width - 2.0 * margin, height - 2.0 * margin);
+        let placement = placement_matrix(size, area, Turn::None, ScaleMode::Fit);
+        // Unit square -> upright image box of `size` -> position on the page
+        let matrix = concat_matrix(
+            concat_matrix(exif_matrix(image.orientation), [size.0, 0.0, 0.0, size.1, 0.0, 0.0]),
+            placement,
+        );
+
+        let mut page = ImposedPage::new(width, height);
+        page.draw_form(image.id, matrix);
+        pages.push(page);
+    }
+
+    eprintln!("[PDF] Converted {} images to PDF pages", pages.len());
+    replace_page_tree(&mut doc, pages)?;
+    save_document(&mut doc, output_path)
+}
+
+/// An image XObject added to a document
+struct EmbeddedImage {
+    id: ObjectId,
+    /// Stored size in pixels
+    width: u32,
+    height: u32,
+    orientation: image::metadata::Orientation,
+}
+
+impl EmbeddedImage {
+    /// Size of the image once its EXIF orientation is applied
+    fn display_size(&self) -> (f32, f32) {
+        use image::metadata::Orientation as Exif;
+        match self.orientation {
+            Exif::Rotate90 | Exif::Rotate270 | Exif::Rotate90FlipH | Exif::Rotate270FlipH => {
+                (self.height as f32, self.width as f32)
+            }
+            _ => (self.width as f32, self.height as f32),
+        }
+    }
+}
+
+/// Read a PNG/JPEG file and add it to `doc` as an image XObject
+fn embed_image(doc: &mut Document, path: &str) -> Result<EmbeddedImage, Box<dyn std::error::Error>> {
+    use image::{ColorType, ExtendedColorType, ImageDecoder, ImageFormat, ImageReader};
+
+    let reader = ImageReader::open(path)
+        .map_err(|e| format!("Failed to open image {}: {}", path, e))?
+        .with_guessed_format()?;
+    let format = reader.format();
+    if !matches!(format, Some(ImageFormat::Png) | Some(ImageFormat::Jpeg)) {
+        return Err(format!("Unsupported image format (expected PNG or JPEG): {}", path).into());
+    }
+
+    let mut decoder = reader
+        .into_decoder()
+        .map_err(|e| format!("Failed to read image {}: {}", path, e))?;
+    let orientation = decoder
+        .orientation()
+        .unwrap_or(image::metadata::Orientation::NoTransforms);
+    let (width, height) = decoder.dimensions();
+
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => width as i64,
+        "Height" => height as i64,
+        "BitsPerComponent" => 8,
+    };
+
+    // Baseline grey/RGB JPEGs can be embedded without re-encoding
+    let passthrough_space = match (format, decoder.original_color_type()) {
+        (Some(ImageFormat::Jpeg), ExtendedColorType::L8) => Some("DeviceGray"),
+        (Some(ImageFormat::Jpeg), ExtendedColorType::Rgb8) => Some("DeviceRGB"),
+        _ => None,
+    };
+    if let Some(color_space) = passthrough_space {
+        let data = std::fs::read(path).map_err(|e| format!("Failed to read image {}: {}", path, e))?;
+        dict.set("ColorSpace", color_space);
+        dict.set("Filter", "DCTDecode");
+        let id = doc.add_object(Stream::new(dict, data).with_compression(false));
+        return Ok(EmbeddedImage { id, width, height, orientation });
+    }
+
+    let decoded = image::DynamicImage::from_decoder(decoder)
+        .map_err(|e| format!("Failed to decode image {}: {}", path, e))?;
+    let has_alpha = decoded.color().has_alpha();
+    let grey = matches!(decoded.color(), ColorType::L8 | ColorType::La8 | ColorType::L16 | ColorType::La16);
+
+    if has_alpha {
+        let rgba = decoded.to_rgba8();
+        let alpha: Vec<u8> = rgba.pixels().map(|p| p[3]).collect();
+        let smask_id = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => width as i64,
+                "Height" => height as i64,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 8,
+            },
+            alpha,
+        ));
+        dict.set("SMask", smask_id);
+    }
+
+    let (color_space, samples) = if grey {
+        ("DeviceGray", decoded.into_luma8().into_raw())
+    } else {
+        ("DeviceRGB", decoded.into_rgb8().into_raw())
+    };
+    dict.set("ColorSpace", color_space);
+    let id = doc.add_object(Stream::new(dict, samples));
+    Ok(EmbeddedImage { id, width, height, orientation })
+}
+
+/// Matrix that maps the unit square an image is drawn into onto itself with
+/// the EXIF orientation applied
+fn exif_matrix(orientation: image::metadata::Orientation) -> [f32; 6] {
+    use image::metadata::Orientation as Exif;
+    match orientation {
+        Exif::NoTransforms => [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+        Exif::Rotate90 => [0.0, -1.0, 1.0, 0.0, 0.0, 1.0],
+        Exif::Rotate180 => [-1.0, 0.0, 0.0, -1.0, 1.0, 1.0],
+        Exif::Rotate270 => [0.0, 1.0, -1.0, 0.0, 1.0, 0.0],
+        Exif::FlipHorizontal => [-1.0, 0.0, 0.0, 1.0, 1.0, 0.0],
+        Exif::FlipVertical => [1.0, 0.0, 0.0, -1.0, 0.0, 1.0],
+        Exif::Rotate90FlipH => [0.0, -1.0, -1.0, 0.0, 1.0, 1.0],
+        Exif::Rotate270FlipH => [0.0, 1.0, 1.0, 0.0, 0.0, 0.0],
+    }
+}
+
+/// Options for rendering plain text / source code as a PDF listing
+#[derive(Debug, Clone)]
+pub struct TextToPdfOptions {
+    pub paper_size: PaperSize,
+    pub font_size: f32,
+    /// Columns per tab stop
+    pub tab_width: u32,
+    /// Wrap long lines onto continuation lines instead of cutting them off
+    pub wrap_lines: bool,
+    pub line_numbers: bool,
+}
+
+impl Default for TextToPdfOptions {
+    fn default() -> Self {
+        Self {
+            paper_size: PaperSize::A4,
+            font_size: 9.0,
+            tab_width: 4,
+            wrap_lines: true,
+            line_numbers: true,
+        }
+    }
+}
+
+/// Margin around text listings, in points
+const LISTING_MARGIN: f32 = 36.0;
+/// Advance width of every Courier glyph, as a fraction of the font size
+const COURIER_ADVANCE: f32 = 0.6;
+
+/// Render a text file as a paginated monospace listing.
+///
+/// Each page carries a header with the file name and page number. Tabs are
+/// expanded to the configured width; long lines are wrapped (continuation
+/// lines carry no line number) or cut off at the right margin. Characters
+/// outside Windows-1252, which the standard Courier font covers, print as
+/// '?'; the result says how many there were.
+pub fn text_to_pdf_internal(
+    input_path: &str,
+    output_path: &str,
+    options: &TextToPdfOptions,
+) -> Result<TextListing, Box<dyn std::error::Error>> {
+    let bytes = std::fs::read(input_path)
+        .map_err(|e| format!("Failed to read text file {}: {}", input_path, e))?;
+    if bytes.contains(&0) {
+        return Err(format!("File looks binary, not text: {}", input_path).into());
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(&text);
+
+    if !(4.0..=24.0).contains(&options.font_size) {
+        return Err(format!("Font size {} is out of range (4-24)", options.font_size).into());
+    }
+    let tab_width = options.tab_width.clamp(1, 16) as usize;
+
+    let (page_w, page_h) = options.paper_size.dimensions();
+    let (page_w, page_h) = (page_w as f32, page_h as f32);
+    let font_size = options.font_size;
+    let char_w = font_size * COURIER_ADVANCE;
+    let line_h = font_size * 1.2;
+
+    let source_lines: Vec<&str> = text.lines().collect();
+    let number_width = if options.line_numbers {
+        source_lines.len().max(1).to_string().len()
+    } else {
+        0
+    };
+    let text_x = LISTING_MARGIN + if options.line_numbers { (number_width + 2) as f32 * char_w } else { 0.0 };
+    let columns = ((page_w - LISTING_MARGIN - text_x) / char_w).floor().max(1.0) as usize;
+
+    // Visual lines: (source line number for the first segment, text)
+    let mut lines: Vec<(Option<usize>, String)> = Vec::new();
+    for (index, line) in source_lines.iter().enumerate() {
+        let expanded = expand_tabs(line, tab_width);
+        let chars: Vec<char> = expanded.chars().collect();
+        if chars.len() <= columns || !options.wrap_lines {
+            lines.push((Some(index + 1), chars.into_iter().take(columns).collect()));
+            continue;
+        }
+        for (segment, chunk) in chars.chunks(columns).enumerate() {
+            let number = if segment == 0 { Some(index + 1) } else { None };
+            lines.push((number, chunk.iter().collect()));
+        }
+    }
+
+    let header_y = page_h - LISTING_MARGIN - font_size;
+    let rule_y = header_y - font_size * 0.6;
+    let first_baseline = rule_y - line_h;
+    let lines_per_page = (((first_baseline - LISTING_MARGIN) / line_h).floor() as usize + 1).max(1);
+    let page_count = lines.len().div_ceil(lines_per_page).max(1);
+
+    let file_name = Path::new(input_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| input_path.to_string());
+
+    let mut doc = empty_document();
+    let regular = standard_font(&mut doc, "Courier");
+    let bold = standard_font(&mut doc, "Courier-Bold");
+
+    let mut replaced_chars = unencodable_chars(&file_name);
+    let mut pages = Vec::with_capacity(page_count);
+    for page_index in 0..page_count {
+        let mut page = ImposedPage::new(page_w, page_h);
+
+        let page_label = format!("Page {} of {}", page_index + 1, page_count);
+        let label_x = page_w - LISTING_MARGIN - page_label.chars().count() as f32 * char_w;
+        let max_name = ((label_x - LISTING_MARGIN) / char_w) as usize;
+        let name: String = file_name.chars().take(max_name.saturating_sub(2)).collect();
+        page.draw_text(bold, font_size, LISTING_MARGIN, header_y, &name);
+        page.draw_text(regular, font_size, label_x, header_y, &page_label);
+        page.draw_line(LISTING_MARGIN, rule_y, page_w - LISTING_MARGIN, rule_y, 0.5);
+
+        let start = page_index * lines_per_page;
+        let end = (start + lines_per_page).min(lines.len());
+        for (row, (number, line)) in lines[start.min(end)..end].iter().enumerate() {
+            let y = first_baseline - row as f32 * line_h;
+            if let Some(number) = number {
+                page.set_gray(0.5);
+                let label = format!("{:>width$}", number, width = number_width);
+                page.draw_text(regular, font_size, LISTING_MARGIN, y, &label);
+                page.set_gray(0.0);
+            }
+            if !line.is_empty() {
+                page.draw_text(regular, font_size, text_x, y, line);
+                replaced_chars += unencodable_chars(line);
+            }
+        }
+        pages.push(page);
+    }
+
+    eprintln!(
+        "[PDF] Rendered {} lines of {} onto {} pages ({} characters replaced)",
+        source_lines.len(),
+        file_name,
+        pages.len(),
+        replaced_chars
+    );
+    let num_pages = pages.len() as u32;
+    replace_page_tree(&mut doc, pages)?;
+    save_document(&mut doc, output_path)?;
+    Ok(TextListing {
+        num_pages,
+        replaced_chars: replaced_chars as u32,
+    })
+}
+
+/// Expand tabs to the next multiple of `tab_width` columns and drop other control characters
+fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        match c {
+            '\t' => {
+                let spaces = tab_width - column % tab_width;
+                out.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            c if c.is_control() => {}
+            c => {
+                out.push(c);
+                column += 1;
+            }
+        }
+    }
+    out
+}
+
+/// A document with a catalog and no pages, ready for `replace_page_tree`
+fn empty_document() -> Document {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.add_object(dictionary! { "Type" => "Pages", "Kids" => vec![], "Count" => 0 });
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+    doc
+}
+
+/// Find pages that print nothing useful: no content, content that draws
+/// nothing visible, or only placeholder text. Images are not inspected, so a
+/// blank scan still counts as content.
+pub fn detect_blank_pages_internal(
+    file_path: &str,
+    password: Option<&str>,
+) -> Result<Vec<BlankPage>, Box<dyn std::error::Error>> {
+    if !Path::new(file_path).exists() {
+        return Err(format!("PDF file not found: {}", file_path).into());
+    }
+    let doc = load_document(file_path, password).map_err(|e| match e {
+        lopdf::Error::InvalidPassword => format!("Incorrect password for {}", file_path),
+        e => format!("Failed to parse PDF for blank page detection: {}", e),
+    })?;
+    if doc.is_encrypted() {
+        return Err(format!("PDF is password protected: {}", file_path).into());
+    }
+
+    Ok(doc
+        .get_pages()
+        .into_iter()
+        .filter_map(|(number, page_id)| {
+            blank_reason(&doc, number, page_id).map(|reason| BlankPage { number, reason })
+        })
+        .collect())
+}
+
+/// Copy a PDF without its blank pages (see `detect_blank_pages_internal`)
+pub fn remove_blank_pages_internal(
+    input_path: &str,
+    output_path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !Path::new(input_path).exists() {
+        return Err(format!("Input PDF not found: {}", input_path).into());
+    }
+    let mut doc = Document::load(input_path)
+        .map_err(|e| format!("Failed to parse PDF for blank page removal: {}", e))?;
+
+    let pages = doc.get_pages();
+    let kept: Vec<ObjectId> = pages
+        .iter()
+        .filter(|(number, page_id)| blank_reason(&doc, **number, **page_id).is_none())
+        .map(|(_, page_id)| *page_id)
+        .collect();
+
+    if kept.is_empty() {
+        return Err("Every page of the PDF is blank".into());
+    }
+    if kept.len() == pages.len() {
+        std::fs::copy(input_path, output_path)
+            .map_err(|e| format!("Failed to copy PDF: {}", e))?;
+        return Ok(());
+    }
+
+    eprintln!("[PDF] Removing {} blank pages of {}", pages.len() - kept.len(), pages.len());
+    rebuild_page_tree(&mut doc, &kept)?;
+    save_document(&mut doc, output_path)
+}
+
+/// Tighten each page's CropBox to its content plus padding.
+///
+/// Unless `per_page` is set, pages of the same size share one crop box (the
+/// union of their content), so n-up or fit scaling treats them alike.
+pub fn trim_margins_pdf_internal(
+    input_path: &str,
+    output_path: &str,
+    trim: &TrimSettings,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !Path::new(input_path).exists() {
+        return Err(format!("Input PDF not found: {}", input_path).into());
+    }
+    let mut doc = Document::load(input_path)
+        .map_err(|e| format!("Failed to parse PDF for margin trimming: {}", e))?;
+
+    let pages: Vec<(ObjectId, [f32; 4], Option<[f32; 4]>)> = doc
+        .get_pages()
+        .into_values()
+        .map(|page_id| (page_id, page_geometry(&doc, page_id).bbox, content_bounds(&doc, page_id)))
+        .collect();
+
+    let same_box = |a: [f32; 4], b: [f32; 4]| a.iter().zip(b).all(|(a, b)| (a - b).abs() <= 1.0);
+    let padding = trim.padding_mm.max(0.0) * 72.0 / 25.4;
+    let mut trimmed = 0;
+    for &(page_id, visible, bounds) in &pages {
+        let Some(mut bounds) = bounds else { continue };
+        if !trim.per_page {
+            for &(_, other_visible, other_bounds) in &pages {
+                if let Some(other) = other_bounds.filter(|_| same_box(visible, other_visible)) {
+                    bounds = [
+                        bounds[0].min(other[0]),
+                        bounds[1].min(other[1]),
+                        bounds[2].max(other[2]),
+                        bounds[3].max(other[3]),
+                    ];
+                }
+            }
+        }
+        let padded = [bounds[0] - padding, bounds[1] - padding, bounds[2] + padding, bounds[3] + padding];
+        let Some(crop) = intersect_rect(padded, visible) else { continue };
+        if same_box(crop, visible) {
+            continue;
+        }
+        let page = doc.get_object_mut(page_id).and_then(Object::as_dict_mut)?;
+        page.set("CropBox", crop.iter().map(|v| Object::Real(*v)).collect::<Vec<_>>());
+        trimmed += 1;
+    }
+
+    if trimmed == 0 {
+        std::fs::copy(input_path, output_path)
+            .map_err(|e| format!("Failed to copy PDF: {}", e))?;
+        return Ok(());
+    }
+    eprintln!("[PDF] Trimmed margins of {} of {} pages", trimmed, pages.len());
+    save_document(&mut doc, output_path)
+}
+
+/// Find slide build frames that are repeated in full on the following page
+/// (see `overlay_pages`)
+pub fn detect_overlay_pages_internal(
+    file_path: &str,
+    password: Option<&str>,
+) -> Result<OverlayAnalysis, Box<dyn std::error::Error>> {
+    if !Path::new(file_path).exists() {
+        return Err(format!("PDF file not found: {}", file_path).into());
+    }
+    let doc = load_document(file_path, password).map_err(|e| match e {
+        lopdf::Error::InvalidPassword => format!("Incorrect password for {}", file_path),
+        e => format!("Failed to parse PDF for overlay detection: {}", e),
+    })?;
+    if doc.is_encrypted() {
+        return Err(format!("PDF is password protected: {}", file_path).into());
+    }
+
+    let pages: Vec<(u32, ObjectId)> = doc.get_pages().into_iter().collect();
+    let overlay_pages = overlay_pages(&doc, &pages);
+    Ok(OverlayAnalysis {
+        num_pages: pages.len() as u32,
+        pages_saved: overlay_pages.len() as u32,
+        overlay_pages,
+    })
+}
+
+/// Copy a PDF keeping only the final frame of each slide build sequence
+pub fn remove_overlay_pages_internal(
+    input_path: &str,
+    output_path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !Path::new(input_path).exists() {
+        return Err(format!("Input PDF not found: {}", input_path).into());
+    }
+    let mut doc = Document::load(input_path)
+        .map_err(|e| format!("Failed to parse PDF for overlay removal: {}", e))?;
+
+    let pages: Vec<(u32, ObjectId)> = doc.get_pages().into_iter().collect();
+    let overlays = overlay_pages(&doc, &pages);
+    if overlays.is_empty() {
+        std::fs::copy(input_path, output_path)
+            .map_err(|e| format!("Failed to copy PDF: {}", e))?;
+        return Ok(());
+    }
+
+    let kept: Vec<ObjectId> = pages
+        .iter()
+        .filter(|(number, _)| !overlays.contains(number))
+        .map(|(_, page_id)| *page_id)
+        .collect();
+    eprintln!("[PDF] Removing {} overlay frames of {}", overlays.len(), pages.len());
+    rebuild_page_tree(&mut doc, &kept)?;
+    save_document(&mut doc, output_path)
+}
+
+/// Pages left of a page selection after the removals `process_job_pdf`
+/// runs on it: slide build frames among the selected pages, then blank pages
+pub fn count_kept_pages(
+    file_path: &str,
+    password: Option<&str>,
+    pages: &[u32],
+    remove_overlays: bool,
+    remove_blank_pages: bool,
+) -> Result<u32, Box<dyn std::error::Error>> {
+    if !Path::new(file_path).exists() {
+        return Err(format!("PDF file not found: {}", file_path).into());
+    }
+    let doc = load_document(file_path, password).map_err(|e| match e {
+        lopdf::Error::InvalidPassword => format!("Incorrect password for {}", file_path),
+        e => format!("Failed to parse PDF for page removal: {}", e),
+    })?;
+    if doc.is_encrypted() {
+        return Err(format!("PDF is password protected: {}", file_path).into());
+    }
+
+    let page_ids = doc.get_pages();
+    let mut kept: Vec<(u32, ObjectId)> = pages
+        .iter()
+        .filter_map(|number| page_ids.get(number).map(|page_id| (*number, *page_id)))
+        .collect();
+    if remove_overlays {
+        // Frames are found among the selected pages in order, as in the extracted copy
+        let positions: Vec<(u32, ObjectId)> = (1..).zip(kept.iter().map(|(_, page_id)| *page_id)).collect();
+        let overlays = overlay_pages(&doc, &positions);
+        kept = (1..)
+            .zip(kept)
+            .filter(|(position, _)| !overlays.contains(position))
+            .map(|(_, page)| page)
+            .collect();
+    }
+    if remove_blank_pages {
+        kept.retain(|(number, page_id)| blank_reason(&doc, *number, *page_id).is_none());
+        if kept.is_empty() {
+            return Err("Every page of the PDF is blank".into());
+        }
+    }
+    Ok(kept.len() as u32)
+}
+
+/// Convert a PDF to grayscale for mono queues. Colour operators in page,
+/// form, pattern and annotation content, 8-bit RGB/CMYK images and gradient
+/// shadings are mapped to their luminance. With `boost_contrast`, coloured
+/// text is darkened further so it stays legible.
+pub fn grayscale_pdf_internal(
+    input_path: &str,
+    output_path: &str,
+    boost_contrast: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !Path::new(input_path).exists() {
+        return Err(format!("Input PDF not found: {}", input_path).into());
+    }
+    let mut doc = Document::load(input_path)
+        .map_err(|e| format!("Failed to parse PDF for grayscale conversion: {}", e))?;
+
+    let mut streams = 0;
+    for (target, spaces) in collect_content_streams(&doc) {
+        match target {
+            ContentTarget::Page(page_id) => {
+                // Shared content streams stay as they are; the page gets its own copy
+                let content = page_content_bytes(&doc, page_id);
+                let Some(gray) = grayscale_content(&content, &spaces, ColorKind::Gray, boost_contrast) else {
+                    continue;
+                };
+                let content_id = doc.add_object(Stream::new(dictionary! {}, gray));
+                if let Ok(page) = doc.get_object_mut(page_id).and_then(Object::as_dict_mut) {
+                    page.set("Contents", content_id);
+                    streams += 1;
+                }
+            }
+            ContentTarget::Stream(stream_id, initial) => {
+                let Some(Object::Stream(stream)) = doc.objects.get_mut(&stream_id) else {
+                    continue;
+                };
+                let Ok(content) = stream.get_plain_content() else {
+                    continue;
+                };
+                if let Some(gray) = grayscale_content(&content, &spaces, initial, boost_contrast) {
+                    stream.set_plain_content(gray);
+                    streams += 1;
+                }
+            }
+        }
+    }
+
+    let mut resources = 0;
+    let object_ids: Vec<ObjectId> = doc.objects.keys().copied().collect();
+    for object_id in object_ids {
+        let gray = match doc.objects.get(&object_id) {
+            Some(Object::Stream(stream)) if is_image(stream) => grayscale_image(&doc, stream).map(Object::Stream),
+            Some(Object::Stream(stream)) => grayscale_shading(&doc, &stream.dict).map(|dict| {
+                let mut gray = stream.clone();
+                gray.dict = dict;
+                Object::Stream(gray)
+            }),
+            Some(Object::Dictionary(dict)) => grayscale_shading(&doc, dict).map(Object::Dictionary),
+            _ => None,
+        };
+        if let Some(gray) = gray {
+            doc.objects.insert(object_id, gray);
+            resources += 1;
+        }
+    }
+
+    eprintln!(
+        "[PDF] Grayscale: rewrote {} content streams and {} images/shadings",
+        streams, resources
+    );
+    save_document(&mut doc, output_path)
+}
+
+/// Find the pages that print in colour
+pub fn detect_color_pages_internal(
+    file_path: &str,
+    password: Option<&str>,
+) -> Result<ColorAnalysis, Box<dyn std::error::Error>> {
+    if !Path::new(file_path).exists() {
+        return Err(format!("PDF file not found: {}", file_path).into());
+    }
+    let doc = load_document(file_path, password).map_err(|e| match e {
+        lopdf::Error::InvalidPassword => format!("Incorrect password for {}", file_path),
+        e => format!("Failed to parse PDF for colour detection: {}", e),
+    })?;
+    if doc.is_encrypted() {
+        return Err(format!("PDF is password protected: {}", file_path).into());
+    }
+
+    let pages = doc.get_pages();
+    let mut cache = ColorCache::new();
+    let color_pages = pages
+        .iter()
+        .filter(|(_, page_id)| page_uses_color(&doc, **page_id, &mut cache))
+        .map(|(number, _)| *number)
+        .collect();
+    Ok(ColorAnalysis {
+        num_pages: pages.len() as u32,
+        color_pages,
+    })
+}
+
+// ========== Page Tree Editing ==========
+
+/// Keys a page may inherit from its ancestors in the page tree
+const INHERITABLE_PAGE_KEYS: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Replace the page tree with a flat list of existing pages.
+///
+/// Inherited attributes are copied onto each page so they survive reparenting.
+/// A page listed more than once is cloned, sharing its content streams.
+fn rebuild_page_tree(doc: &mut Document, page_ids: &[ObjectId]) -> Result<(), Box<dyn std::error::Error>> {
+    let pages_id = doc.new_object_id();
+    let mut used = HashSet::new();
+    let mut kids = Vec::with_capacity(page_ids.len());
+
+    for &page_id in page_ids {
+        let mut page = doc.get_dictionary(page_id)?.clone();
+        for key in INHERITABLE_PAGE_KEYS {
+            if !page.has(key) {
+                if let Some(value) = inherited_raw(doc, page_id, key) {
+                    page.set(key, value.clone());
+                }
+            }
+        }
+        page.set("Parent", pages_id);
+
+        let id = if used.insert(page_id) {
+            doc.objects.insert(page_id, Object::Dictionary(page));
+            page_id
+        } else {
+            doc.add_object(page)
+        };
+        kids.push(Object::Reference(id));
+    }
+
+    let count = kids.len() as i64;
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => count,
+        }),
+    );
+    set_catalog_pages(doc, pages_id)
+}
+
+// ========== Page Imposition ==========
+
+/// Axis-aligned rectangle in PDF user space (origin bottom-left)
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl Rect {
+    fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+}
+
+/// Visible box of a source page and its /Rotate value
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PageGeometry {
+    /// CropBox (or MediaBox) as [llx, lly, urx, ury]
+    bbox: [f32; 4],
+    /// Clockwise rotation in degrees, normalized to 0/90/180/270
+    rotate: i64,
+}
+
+impl PageGeometry {
+    /// Size of the page as displayed, i.e. after applying /Rotate
+    fn size(&self) -> (f32, f32) {
+        let width = self.bbox[2] - self.bbox[0];
+        let height = self.bbox[3] - self.bbox[1];
+        if self.rotate % 180 == 0 {
+            (width, height)
+        } else {
+            (height, width)
+        }
+    }
+
+    /// Form matrix mapping the box onto [0, 0, w, h] with /Rotate applied
+    fn form_matrix(&self) -> [f32; 6] {
+        let [x0, y0, x1, y1] = self.bbox;
+        let (width, height) = (x1 - x0, y1 - y0);
+        match self.rotate {
+            90 => [0.0, -1.0, 1.0, 0.0, -y0, width + x0],
+            180 => [-1.0, 0.0, 0.0, -1.0, width + x0, height + y0],
+            270 => [0.0, 1.0, -1.0, 0.0, height + y0, -x0],
+            _ => [1.0, 0.0, 0.0, 1.0, -x0, -y0],
+        }
+    }
+}
+
+/// An output page built from placed Form XObjects and extra drawing operations
+struct ImposedPage {
+    width: f32,
+    height: f32,
+    xobjects: Vec<(String, ObjectId)>,
+    fonts: Vec<(String, ObjectId)>,
+    graphics_states: Vec<(String, ObjectId)>,
+    operations: Vec<Operation>,
+}
+
+impl ImposedPage {
+    fn new(width: f32, height: f32) -> Self {
+        Self {
+            width,
+            height,
+            xobjects: Vec::new(),
+            fonts: Vec::new(),
+            graphics_states: Vec::new(),
+            operations: Vec::new(),
+        }
+    }
+
+    /// Place a form scaled to fit `cell`, centered, keeping its aspect ratio
+    fn place_form(&mut self, form_id: ObjectId, geometry: PageGeometry, cell: Rect) {
+        let matrix = placement_matrix(geometry.size(), cell, Turn::None, ScaleMode::Fit);
+        self.draw_form(form_id, matrix);
+    }
+
+    /// Like `place_form`, but turns the page 90° when that makes it larger
+    fn place_form_auto_rotate(&mut self, form_id: ObjectId, geometry: PageGeometry, cell: Rect) {
+        let (width, height) = geometry.size();
+        let upright = (cell.width / width).min(cell.height / height);
+        let rotated = (cell.width / height).min(cell.height / width);
+        let turn = if rotated > upright + 1e-4 { Turn::Ccw } else { Turn::None };
+        let matrix = placement_matrix((width, height), cell, turn, ScaleMode::Fit);
+        self.draw_form(form_id, matrix);
+    }
+
+    /// Draw a form with an explicit transformation matrix
+    fn draw_form(&mut self, form_id: ObjectId, matrix: [f32; 6]) {
+        let name = format!("P{}", self.xobjects.len());
+        self.operations.push(Operation::new("q", vec![]));
+        self.operations.push(Operation::new(
+            "cm",
+            matrix.iter().map(|v| Object::Real(*v)).collect(),
+        ));
+        self.operations
+            .push(Operation::new("Do", vec![Object::Name(name.clone().into_bytes())]));
+        self.operations.push(Operation::new("Q", vec![]));
+        self.xobjects.push((name, form_id));
+    }
+
+    /// Draw a line of text with its baseline starting at (x, y)
+    fn draw_text(&mut self, font_id: ObjectId, size: f32, x: f32, y: f32, text: &str) {
+        let name = match self.fonts.iter().find(|(_, id)| *id == font_id) {
+            Some((name, _)) => name.clone(),
+            None => {
+                let name = format!("F{}", self.fonts.len());
+                self.fonts.push((name.clone(), font_id));
+                name
+            }
+        };
+        self.operations.push(Operation::new("BT", vec![]));
+        self.operations.push(Operation::new(
+            "Tf",
+            vec![Object::Name(name.into_bytes()), Object::Real(size)],
+        ));
+        self.operations
+            .push(Operation::new("Td", vec![Object::Real(x), Object::Real(y)]));
+        self.operations.push(Operation::new(
+            "Tj",
+            vec![Object::String(encode_win_ansi(text), lopdf::StringFormat::Literal)],
+        ));
+        self.operations.push(Operation::new("ET", vec![]));
+    }
+
+    /// Stroke a straight line from (x0, y0) to (x1, y1)
+    fn draw_line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, width: f32) {
+        self.operations.push(Operation::new("q", vec![]));
+        self.operations.push(Operation::new("w", vec![Object::Real(width)]));
+        self.operations
+            .push(Operation::new("m", vec![Object::Real(x0), Object::Real(y0)]));
+        self.operations
+            .push(Operation::new("l", vec![Object::Real(x1), Object::Real(y1)]));
+        self.operations.push(Operation::new("S", vec![]));
+        self.operations.push(Operation::new("Q", vec![]));
+    }
+
+    /// Stroke the outline of a rectangle
+    fn draw_rect(&mut self, rect: Rect, width: f32) {
+        self.operations.push(Operation::new("q", vec![]));
+        self.operations.push(Operation::new("w", vec![Object::Real(width)]));
+        self.operations.push(Operation::new(
+            "re",
+            vec![
+                Object::Real(rect.x),
+                Object::Real(rect.y),
+                Object::Real(rect.width),
+                Object::Real(rect.height),
+            ],
+        ));
+        self.operations.push(Operation::new("S", vec![]));
+        self.operations.push(Operation::new("Q", vec![]));
+    }
+
+    /// Restrict later drawing to a rectangle (until the state is restored)
+    fn clip_rect(&mut self, rect: Rect) {
+        self.operations.push(Operation::new(
+            "re",
+            vec![
+                Object::Real(rect.x),
+                Object::Real(rect.y),
+                Object::Real(rect.width),
+                Object::Real(rect.height),
+            ],
+        ));
+        self.operations.push(Operation::new("W", vec![]));
+        self.operations.push(Operation::new("n", vec![]));
+    }
+
+    /// Short marks outside each corner of `rect`, in line with its edges
+    fn draw_crop_marks(&mut self, rect: Rect) {
+        const GAP: f32 = 3.0;
+        const LENGTH: f32 = 15.0;
+        let (x0, y0) = (rect.x, rect.y);
+        let (x1, y1) = (rect.x + rect.width, rect.y + rect.height);
+        for (x, y, dx, dy) in [(x0, y0, -1.0, -1.0), (x1, y0, 1.0, -1.0), (x0, y1, -1.0, 1.0), (x1, y1, 1.0, 1.0)] {
+            self.draw_line(x + dx * GAP, y, x + dx * (GAP + LENGTH), y, 0.3);
+            self.draw_line(x, y + dy * GAP, x, y + dy * (GAP + LENGTH), 0.3);
+        }
+    }
+
+    /// Set the grey level for subsequent fills (text) and strokes
+    fn set_gray(&mut self, level: f32) {
+        self.operations.push(Operation::new("g", vec![Object::Real(level)]));
+        self.operations.push(Operation::new("G", vec![Object::Real(level)]));
+    }
+
+    /// Apply an ExtGState dictionary (e.g. for transparency)
+    fn set_graphics_state(&mut self, state_id: ObjectId) {
+        let name = format!("GS{}", self.graphics_states.len());
+        self.operations
+            .push(Operation::new("gs", vec![Object::Name(name.clone().into_bytes())]));
+        self.graphics_states.push((name, state_id));
+    }
+
+    fn save_state(&mut self) {
+        self.operations.push(Operation::new("q", vec![]));
+    }
+
+    fn restore_state(&mut self) {
+        self.operations.push(Operation::new("Q", vec![]));
+    }
+
+    /// Concatenate `matrix` onto the current transformation
+    fn transform(&mut self, matrix: [f32; 6]) {
+        self.operations.push(Operation::new(
+            "cm",
+            matrix.iter().map(|v| Object::Real(*v)).collect(),
+        ));
+    }
+}
+
+/// Add one of the standard 14 Type 1 fonts (e.g. "Courier") to the document
+fn standard_font(doc: &mut Document, base_font: &str) -> ObjectId {
+    doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => base_font,
+        "Encoding" => "WinAnsiEncoding",
+    })
+}
+
+/// Width of `text` set in Helvetica at `size` points
+fn helvetica_width(text: &str, size: f32) -> f32 {
+    // Advance widths of ASCII 32..=126 in thousandths of an em (Helvetica AFM)
+    const WIDTHS: [u16; 95] = [
+        278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, // ' '..'/'
+        556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, // '0'..'?'
+        1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778, // '@'..'O'
+        667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, // 'P'..'_'
+        333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, // '`'..'o'
+        556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // 'p'..'~'
+    ];
+    let units: u32 = text
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => WIDTHS[c as usize - 32] as u32,
+            _ => 556,
+        })
+        .sum();
+    units as f32 * size / 1000.0
+}
+
+/// Encode text for a WinAnsiEncoding font; unmappable characters become '?'
+/// (see `unencodable_chars`)
+fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars().map(|c| win_ansi_byte(c).unwrap_or(b'?')).collect()
+}
+
+/// Number of characters `encode_win_ansi` can't map
+fn unencodable_chars(text: &str) -> usize {
+    text.chars().filter(|c| win_ansi_byte(*c).is_none()).count()
+}
+
+fn win_ansi_byte(c: char) -> Option<u8> {
+    match c {
+        ' '..='~' | '\u{A0}'..='\u{FF}' => Some(c as u8),
+        '€' => Some(0x80),
+        '‚' => Some(0x82),
+        '„' => Some(0x84),
+        '…' => Some(0x85),
+        '‘' => Some(0x91),
+        '’' => Some(0x92),
+        '“' => Some(0x93),
+        '”' => Some(0x94),
+        '•' => Some(0x95),
+        '–' => Some(0x96),
+        '—' => Some(0x97),
+        '™' => Some(0x99),
+        _ => None,
+    }
+}
+
+/// Quarter turn applied when placing content
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Turn {
+    None,
+    /// 90° counter-clockwise: the content's top ends up on the left
+    Ccw,
+    /// 90° clockwise: the content's top ends up on the right
+    Cw,
+}
+
+/// Matrix that places content of `size` (at the origin) centered in `cell`,
+/// turned as requested and scaled according to `mode`
+fn placement_matrix(size: (f32, f32), cell: Rect, turn: Turn, mode: ScaleMode) -> [f32; 6] {
+    let (width, height) = size;
+    // Footprint of the content after turning
+    let (placed_w, placed_h) = if turn == Turn::None {
+        (width, height)
+    } else {
+        (height, width)
+    };
+    let scale = match mode {
+        ScaleMode::Fit => (cell.width / placed_w).min(cell.height / placed_h),
+        ScaleMode::Fill => (cell.width / placed_w).max(cell.height / placed_h),
+        ScaleMode::ActualSize => 1.0,
+    };
+    let tx = cell.x + (cell.width - placed_w * scale) / 2.0;
+    let ty = cell.y + (cell.height - placed_h * scale) / 2.0;
+    match turn {
+        Turn::None => [scale, 0.0, 0.0, scale, tx, ty],
+        Turn::Ccw => [0.0, scale, -scale, 0.0, tx + height * scale, ty],
+        Turn::Cw => [0.0, -scale, scale, 0.0, tx, ty + width * scale],
+    }
+}
+
+/// Look up a page attribute, following the /Parent chain for inheritable keys
+fn inherited_attribute<'a>(doc: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let value = inherited_raw(doc, page_id, key)?;
+    doc.dereference(value).ok().map(|(_, object)| object)
+}
+
+/// Like `inherited_attribute`, but returns the value as stored (possibly a reference)
+fn inherited_raw<'a>(doc: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut node = doc.get_dictionary(page_id).ok()?;
+    let mut seen = HashSet::new();
+    loop {
+        if let Ok(value) = node.get(key) {
+            return Some(value);
+        }
+        let parent_id = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+        if !seen.insert(parent_id) {
+            return None;
+        }
+        node = doc.get_dictionary(parent_id).ok()?;
+    }
+}
+
+/// Parse a PDF rectangle array into normalized [llx, lly, urx, ury]
+fn rect_from_object(doc: &Document, object: &Object) -> Option<[f32; 4]> {
+    let array = object.as_array().ok()?;
+    if array.len() != 4 {
+        return None;
+    }
+    let mut values = [0.0f32; 4];
+    for (value, item) in values.iter_mut().zip(array) {
+        *value = doc.dereference(item).ok()?.1.as_float().ok()?;
+    }
+    Some([
+        values[0].min(values[2]),
+        values[1].min(values[3]),
+        values[0].max(values[2]),
+        values[1].max(values[3]),
+    ])
+}
+
+/// MediaBox of a page, defaulting to A4 when missing or malformed
+fn media_box(doc: &Document, page_id: ObjectId) -> [f32; 4] {
+    inherited_attribute(doc, page_id, b"MediaBox")
+        .and_then(|object| rect_from_object(doc, object))
+        .unwrap_or([0.0, 0.0, 595.0, 842.0])
+}
+
+fn page_geometry(doc: &Document, page_id: ObjectId) -> PageGeometry {
+    let media_box = media_box(doc, page_id);
+    let bbox = inherited_attribute(doc, page_id, b"CropBox")
+        .and_then(|object| rect_from_object(doc, object))
+        .map(|crop| {
+            // CropBox is clipped to the MediaBox
+            [
+                crop[0].max(media_box[0]),
+                crop[1].max(media_box[1]),
+                crop[2].min(media_box[2]),
+                crop[3].min(media_box[3]),
+            ]
+        })
+        .filter(|b| b[2] > b[0] && b[3] > b[1])
+        .unwrap_or(media_box);
+    let rotate = inherited_attribute(doc, page_id, b"Rotate")
+        .and_then(|object| object.as_i64().ok())
+        .unwrap_or(0)
+        .rem_euclid(360)
+        / 90
+        * 90;
+    PageGeometry { bbox, rotate }
+}
+
+/// Concatenate a page's content streams, decompressed
+fn page_content_bytes(doc: &Document, page_id: ObjectId) -> Vec<u8> {
+    let mut content = Vec::new();
+    for stream_id in doc.get_page_contents(page_id) {
+        if let Ok(stream) = doc.get_object(stream_id).and_then(Object::as_stream) {
+            match stream.decompressed_content() {
+                Ok(data) => content.extend_from_slice(&data),
+                Err(_) => content.extend_from_slice(&stream.content),
+            }
+            // Streams may end mid-token without trailing whitespace
+            content.push(b'\n');
+        }
+    }
+    content
+}
+
+/// Wrap each page as a Form XObject so it can be placed on a new page.
+/// Printable annotation appearances (e.g. filled form fields) are drawn into the form.
+fn pages_to_forms(
+    doc: &mut Document,
+    page_ids: &[ObjectId],
+) -> Result<Vec<(ObjectId, PageGeometry)>, Box<dyn std::error::Error>> {
+    let mut forms = Vec::with_capacity(page_ids.len());
+    for &page_id in page_ids {
+        let geometry = page_geometry(doc, page_id);
+        let mut content = page_content_bytes(doc, page_id);
+        let appearances = printable_appearances(doc, page_id);
+
+        let resources = if appearances.is_empty() {
+            inherited_raw(doc, page_id, b"Resources")
+                .cloned()
+                .unwrap_or_else(|| Object::Dictionary(dictionary! {}))
+        } else {
+            // Copy the resources so the appearance streams can be added as XObjects
+            let mut resources = inherited_attribute(doc, page_id, b"Resources")
+                .and_then(|object| object.as_dict().ok())
+                .cloned()
+                .unwrap_or_default();
+            let mut xobjects = resources
+                .get(b"XObject")
+                .ok()
+                .and_then(|object| doc.dereference(object).ok())
+                .and_then(|(_, object)| object.as_dict().ok())
+                .cloned()
+                .unwrap_or_default();
+
+            let mut operations = Vec::new();
+            for (index, (stream_id, matrix)) in appearances.into_iter().enumerate() {
+                let name = format!("PrintSoCAnnot{}", index);
+                operations.push(Operation::new("q", vec![]));
+                operations.push(Operation::new("cm", matrix.iter().map(|v| Object::Real(*v)).collect()));
+                operations.push(Operation::new("Do", vec![Object::Name(name.clone().into_bytes())]));
+                operations.push(Operation::new("Q", vec![]));
+                xobjects.set(name, stream_id);
+            }
+            resources.set("XObject", xobjects);
+
+            // Page content may leave the graphics state unbalanced
+            let mut wrapped = b"q\n".to_vec();
+            wrapped.append(&mut content);
+            wrapped.extend_from_slice(b"Q\n");
+            wrapped.extend(Content { operations }.encode()?);
+            content = wrapped;
+            Object::Dictionary(resources)
+        };
+
+        let dict = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => geometry.bbox.iter().map(|v| Object::Real(*v)).collect::<Vec<_>>(),
+            "Matrix" => geometry.form_matrix().iter().map(|v| Object::Real(*v)).collect::<Vec<_>>(),
+            "Resources" => resources,
+        };
+        let form_id = doc.add_object(Stream::new(dict, content));
+        forms.push((form_id, geometry));
+    }
+    Ok(forms)
+}
+
+/// Appearance streams of a page's printable annotations, with the matrix
+/// that maps each appearance onto its annotation rectangle
+fn printable_appearances(doc: &Document, page_id: ObjectId) -> Vec<(ObjectId, [f32; 6])> {
+    const FLAG_HIDDEN: i64 = 1 << 1;
+    const FLAG_PRINT: i64 = 1 << 2;
+
+    let annotations = match doc.get_page_annotations(page_id) {
+        Ok(annotations) => annotations,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut appearances = Vec::new();
+    for annotation in annotations {
+        let flags = annotation.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+        if flags & FLAG_PRINT == 0 || flags & FLAG_HIDDEN != 0 {
+            continue;
+        }
+        let Some(rect) = annotation.get(b"Rect").ok().and_then(|r| rect_from_object(doc, r)) else {
+            continue;
+        };
+
+        // /AP /N is either a stream or a dictionary of streams keyed by /AS state
+        let Some(normal) = annotation
+            .get_deref(b"AP", doc)
+            .and_then(Object::as_dict)
+            .and_then(|ap| ap.get(b"N"))
+            .ok()
+        else {
+            continue;
+        };
+        let stream_ref = match doc.dereference(normal) {
+            Ok((_, Object::Dictionary(states))) => annotation
+                .get(b"AS")
+                .and_then(Object::as_name)
+                .and_then(|state| states.get(state))
+                .ok(),
+            Ok((_, Object::Stream(_))) => Some(normal),
+            _ => None,
+        };
+        let Some(stream_id) = stream_ref.and_then(|r| r.as_reference().ok()) else {
+            continue;
+        };
+        let Ok(stream) = doc.get_object(stream_id).and_then(Object::as_stream) else {
+            continue;
+        };
+
+        let Some(bbox) = stream.dict.get(b"BBox").ok().and_then(|b| rect_from_object(doc, b)) else {
+            continue;
+        };
+        let matrix = stream
+            .dict
+            .get(b"Matrix")
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|items| {
+                let values: Vec<f32> = items.iter().filter_map(|v| v.as_float().ok()).collect();
+                <[f32; 6]>::try_from(values).ok()
+            })
+            .unwrap_or([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+        // Fit the transformed appearance box onto the annotation rectangle
+        let [bx0, by0, bx1, by1] = transform_rect(bbox, matrix);
+        if bx1 - bx0 <= 0.0 || by1 - by0 <= 0.0 {
+            continue;
+        }
+        let sx = (rect[2] - rect[0]) / (bx1 - bx0);
+        let sy = (rect[3] - rect[1]) / (by1 - by0);
+        appearances.push((stream_id, [sx, 0.0, 0.0, sy, rect[0] - bx0 * sx, rect[1] - by0 * sy]));
+    }
+    appearances
+}
+
+/// Bounding box of a rectangle after applying a matrix
+fn transform_rect(rect: [f32; 4], m: [f32; 6]) -> [f32; 4] {
+    let corners = [
+        (rect[0], rect[1]),
+        (rect[2], rect[1]),
+        (rect[0], rect[3]),
+        (rect[2], rect[3]),
+    ];
+    let mut out = [f32::MAX, f32::MAX, f32::MIN, f32::MIN];
+    for (x, y) in corners {
+        let tx = m[0] * x + m[2] * y + m[4];
+        let ty = m[1] * x + m[3] * y + m[5];
+        out[0] = out[0].min(tx);
+        out[1] = out[1].min(ty);
+        out[2] = out[2].max(tx);
+        out[3] = out[3].max(ty);
+    }
+    out
+}
+
+/// Matrix that applies `first`, then `second`
+fn concat_matrix(first: [f32; 6], second: [f32; 6]) -> [f32; 6] {
+    let [a1, b1, c1, d1, e1, f1] = first;
+    let [a2, b2, c2, d2, e2, f2] = second;
+    [
+        a1 * a2 + b1 * c2,
+        a1 * b2 + b1 * d2,
+        c1 * a2 + d1 * c2,
+        c1 * b2 + d1 * d2,
+        e1 * a2 + f1 * c2 + e2,
+        e1 * b2 + f1 * d2 + f2,
+    ]
+}
+
+/// Replace the document's page tree with freshly imposed pages
+fn replace_page_tree(
+    doc: &mut Document,
+    pages: Vec<ImposedPage>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pages_id = doc.new_object_id();
+    let mut kids = Vec::with_capacity(pages.len());
+
+    for page in pages {
+        let content = Content { operations: page.operations }.encode()?;
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content));
+
+        let mut xobjects = lopdf::Dictionary::new();
+        for (name, form_id) in page.xobjects {
+            xobjects.set(name, form_id);
+        }
+        let mut resources = dictionary! { "XObject" => xobjects };
+        if !page.fonts.is_empty() {
+            let mut fonts = lopdf::Dictionary::new();
+            for (name, font_id) in page.fonts {
+                fonts.set(name, font_id);
+            }
+            resources.set("Font", fonts);
+        }
+        if !page.graphics_states.is_empty() {
+            let mut states = lopdf::Dictionary::new();
+            for (name, state_id) in page.graphics_states {
+                states.set(name, state_id);
+            }
+            resources.set("ExtGState", states);
+        }
+
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), Object::Real(page.width), Object::Real(page.height)],
+            "Contents" => content_id,
+            "Resources" => resources,
+        });
+        kids.push(Object::Reference(page_id));
+    }
+
+    let count = kids.len() as i64;
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => count,
+        }),
+    );
+    set_catalog_pages(doc, pages_id)
+}
+
+/// Point the catalog at a new page tree and drop entries tied to the old pages
+fn set_catalog_pages(doc: &mut Document, pages_id: ObjectId) -> Result<(), Box<dyn std::error::Error>> {
+    let catalog = doc.catalog_mut()?;
+    catalog.set("Pages", pages_id);
+    // Outlines, labels, structure and open actions reference the old pages
+    for key in [&b"Outlines"[..], b"PageLabels", b"StructTreeRoot", b"OpenAction", b"Dests"] {
+        catalog.remove(key);
+    }
+    prune_unreachable(doc);
+    Ok(())
+}
+
+/// Remove objects no longer reachable from the trailer
+fn prune_unreachable(doc: &mut Document) {
+    fn collect(object: &Object, pending: &mut Vec<ObjectId>) {
+        match object {
+            Object::Reference(id) => pending.push(*id),
+            Object::Array(items) => items.iter().for_each(|item| collect(item, pending)),
+            Object::Dictionary(dict) => dict.iter().for_each(|(_, value)| collect(value, pending)),
+            Object::Stream(stream) => stream.dict.iter().for_each(|(_, value)| collect(value, pending)),
+            _ => {}
+        }
+    }
+
+    let mut reachable = HashSet::new();
+    let mut pending = Vec::new();
+    doc.trailer.iter().for_each(|(_, value)| collect(value, &mut pending));
+    while let Some(id) = pending.pop() {
+        if reachable.insert(id) {
+            if let Some(object) = doc.objects.get(&id) {
+                collect(object, &mut pending);
+            }
+        }
+    }
+    doc.objects.retain(|id, _| reachable.contains(id));
+}
+
+fn save_document(doc: &mut Document, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    doc.compress();
+    doc.save(output_path)
+        .map_err(|e| format!("Failed to write PDF {}: {}", output_path, e))?;
+    Ok(())
+}
+
+// ========== Page Analysis ==========
+
+/// Nesting limit for Form XObjects drawn inside each other
+const MAX_FORM_DEPTH: usize = 8;
+
+/// Whether a page looks blank, and why
+fn blank_reason(doc: &Document, page_number: u32, page_id: ObjectId) -> Option<BlankReason> {
+    if !printable_appearances(doc, page_id).is_empty() {
+        return None;
+    }
+    let content = page_content_bytes(doc, page_id);
+    if content.iter().all(u8::is_ascii_whitespace) {
+        return Some(BlankReason::Empty);
+    }
+
+    let resources = inherited_attribute(doc, page_id, b"Resources").and_then(|object| object.as_dict().ok());
+    let mut paint = PaintSummary::default();
+    scan_painting(doc, &content, resources, 0, &mut paint);
+    if !paint.text && !paint.graphics {
+        return Some(BlankReason::Invisible);
+    }
+    if paint.text {
+        let text = doc.extract_text(&[page_number]).unwrap_or_default();
+        match placeholder_text(&text) {
+            Some(Placeholder::Notice) => return Some(BlankReason::Placeholder),
+            // A lone page number next to graphics may be a figure page
+            Some(Placeholder::PageNumber) if !paint.graphics => return Some(BlankReason::Placeholder),
+            _ => {}
+        }
+    }
+    None
+}
+
+enum Placeholder {
+    /// "This page intentionally left blank" and variants
+    Notice,
+    /// Nothing but a page number, e.g. "- 12 -" or "Page 3 of 10"
+    PageNumber,
+}
+
+/// Recognise text that carries no content of its own
+fn placeholder_text(text: &str) -> Option<Placeholder> {
+    const NOTICE_WORDS: [&str; 7] = ["this", "is", "has", "been", "intentionally", "left", "blank"];
+
+    let text = text.to_lowercase();
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .filter(|word| !word.chars().all(|c| c.is_ascii_digit()) && !matches!(*word, "page" | "of"))
+        .collect();
+
+    if words.is_empty() {
+        text.chars().any(|c| c.is_ascii_digit()).then_some(Placeholder::PageNumber)
+    } else if words.contains(&"blank") && words.iter().all(|word| NOTICE_WORDS.contains(word)) {
+        Some(Placeholder::Notice)
+    } else {
+        None
+    }
+}
+
+/// What a content stream visibly paints
+#[derive(Debug, Default)]
+struct PaintSummary {
+    text: bool,
+    /// Paths, images or shadings
+    graphics: bool,
+}
+
+/// Colour state relevant to visibility; marks painted in white are ignored
+#[derive(Debug, Clone, Copy, Default)]
+struct PaintState {
+    fill_white: bool,
+    stroke_white: bool,
+    /// Text render mode 3 (invisible) or 7 (clip only)
+    text_invisible: bool,
+}
+
+/// Walk a content stream, recording whether it paints anything visible
+fn scan_painting(
+    doc: &Document,
+    content: &[u8],
+    resources: Option<&lopdf::Dictionary>,
+    depth: usize,
+    paint: &mut PaintSummary,
+) {
+    let operations = match Content::decode(content) {
+        Ok(content) => content.operations,
+        Err(_) => {
+            // Can't tell what an unparsable stream draws, so assume something
+            paint.graphics = true;
+            return;
+        }
+    };
+
+    let mut state = PaintState::default();
+    let mut saved = Vec::new();
+    for operation in &operations {
+        let operands = &operation.operands;
+        match operation.operator.as_str() {
+            "q" => saved.push(state),
+            "Q" => state = saved.pop().unwrap_or_default(),
+            "g" | "rg" | "k" | "sc" | "scn" => state.fill_white = is_white(operands),
+            "G" | "RG" | "K" | "SC" | "SCN" => state.stroke_white = is_white(operands),
+            "cs" => state.fill_white = false,
+            "CS" => state.stroke_white = false,
+            "Tr" => {
+                let mode = operands.first().and_then(|mode| mode.as_i64().ok()).unwrap_or(0);
+                state.text_invisible = mode == 3 || mode == 7;
+            }
+            "Tj" | "TJ" | "'" | "\"" if !state.text_invisible && !state.fill_white => {
+                paint.text |= shows_glyphs(operands);
+            }
+            "f" | "F" | "f*" => paint.graphics |= !state.fill_white,
+            "S" | "s" => paint.graphics |= !state.stroke_white,
+            "B" | "B*" | "b" | "b*" => paint.graphics |= !state.fill_white || !state.stroke_white,
+            "sh" | "BI" => paint.graphics = true,
+            "Do" => {
+                let xobject = operands
+                    .first()
+                    .and_then(|name| name.as_name().ok())
+                    .and_then(|name| resource_stream(doc, resources, b"XObject", name));
+                match xobject {
+                    Some(stream) if is_form(stream) => {
+                        if depth >= MAX_FORM_DEPTH {
+                            paint.graphics = true;
+                            continue;
+                        }
+                        let form_resources = stream
+                            .dict
+                            .get(b"Resources")
+                            .ok()
+                            .and_then(|object| doc.dereference(object).ok())
+                            .and_then(|(_, object)| object.as_dict().ok())
+                            .or(resources);
+                        let data = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+                        scan_painting(doc, &data, form_resources, depth + 1, paint);
+                    }
+                    Some(stream) => {
+                        // Stencil masks paint in the fill colour
+                        let mask = stream
+                            .dict
+                            .get(b"ImageMask")
+                            .and_then(Object::as_bool)
+                            .unwrap_or(false);
+                        paint.graphics |= !mask || !state.fill_white;
+                    }
+                    None => paint.graphics = true,
+                }
+            }
+            _ => {}
+        }
+        if paint.text && paint.graphics {
+            return;
+        }
+    }
+}
+
+/// Whether colour operands describe white: gray 1, RGB 1 1 1 or CMYK 0 0 0 0
+fn is_white(operands: &[Object]) -> bool {
+    let values: Option<Vec<f32>> = operands.iter().map(|value| value.as_float().ok()).collect();
+    match values.as_deref() {
+        Some([gray]) => *gray >= 0.999,
+        Some([r, g, b]) => [r, g, b].iter().all(|v| **v >= 0.999),
+        Some([c, m, y, k]) => [c, m, y, k].iter().all(|v| **v <= 0.001),
+        _ => false,
+    }
+}
+
+/// Whether text-showing operands contain at least one non-space character
+fn shows_glyphs(operands: &[Object]) -> bool {
+    operands.iter().any(|operand| match operand {
+        Object::String(bytes, _) => bytes.iter().any(|b| !b.is_ascii_whitespace()),
+        Object::Array(items) => shows_glyphs(items),
+        _ => false,
+    })
+}
+
+fn is_form(stream: &Stream) -> bool {
+    matches!(stream.dict.get(b"Subtype").and_then(Object::as_name), Ok(b"Form"))
+}
+
+/// Look up a named stream (e.g. an XObject) in a resource dictionary
+fn resource_stream<'a>(
+    doc: &'a Document,
+    resources: Option<&'a lopdf::Dictionary>,
+    category: &[u8],
+    name: &[u8],
+) -> Option<&'a Stream> {
+    resource_object(doc, resources, category, name)?.1.as_stream().ok()
+}
+
+/// Look up a named resource, with its object ID when stored indirectly
+fn resource_object<'a>(
+    doc: &'a Document,
+    resources: Option<&'a lopdf::Dictionary>,
+    category: &[u8],
+    name: &[u8],
+) -> Option<(Option<ObjectId>, &'a Object)> {
+    let category = resources?.get(category).ok()?;
+    let (_, category) = doc.dereference(category).ok()?;
+    let entry = category.as_dict().ok()?.get(name).ok()?;
+    doc.dereference(entry).ok()
+}
+
+/// Operation counts of a page with resource names resolved, for comparing pages
+type PageFingerprint = HashMap<String, usize>;
+
+/// Find intermediate build frames: pages whose drawing is entirely repeated
+/// on the next page, as Beamer overlays and exported slide animations are.
+/// Colour and graphics state changes are ignored, so items uncovered by
+/// switching from transparent or highlighted to normal still match.
+/// `pages` are numbered pages in print order; the numbers of the frames are returned.
+fn overlay_pages(doc: &Document, pages: &[(u32, ObjectId)]) -> Vec<u32> {
+    let fingerprints: Vec<Option<PageFingerprint>> = pages
+        .iter()
+        .map(|(_, page_id)| page_fingerprint(doc, *page_id))
+        .collect();
+
+    let mut overlays = Vec::new();
+    for (index, pair) in pages.windows(2).enumerate() {
+        let [(number, page_id), (_, next_id)] = pair else { continue };
+        let (Some(current), Some(next)) = (&fingerprints[index], &fingerprints[index + 1]) else {
+            continue;
+        };
+        let same_geometry = page_geometry(doc, *page_id) == page_geometry(doc, *next_id);
+        let contained = current
+            .iter()
+            .all(|(operation, count)| next.get(operation).is_some_and(|next_count| next_count >= count));
+        if same_geometry && !current.is_empty() && contained {
+            overlays.push(*number);
+        }
+    }
+    overlays
+}
+
+/// `None` for pages whose content can't be parsed
+fn page_fingerprint(doc: &Document, page_id: ObjectId) -> Option<PageFingerprint> {
+    const IGNORED: [&str; 13] = ["gs", "g", "G", "rg", "RG", "k", "K", "cs", "CS", "sc", "SC", "scn", "SCN"];
+
+    let content = Content::decode(&page_content_bytes(doc, page_id)).ok()?;
+    let resources = inherited_attribute(doc, page_id, b"Resources").and_then(|object| object.as_dict().ok());
+
+    let mut fingerprint = PageFingerprint::new();
+    for operation in content.operations {
+        let operator = operation.operator.as_str();
+        if IGNORED.contains(&operator) {
+            continue;
+        }
+        // Resource names are per page; compare the objects they name
+        let category: &[u8] = match operator {
+            "Do" => b"XObject",
+            "Tf" => b"Font",
+            "sh" => b"Shading",
+            "BDC" | "DP" => b"Properties",
+            _ => b"",
+        };
+        let operands: Vec<Object> = operation
+            .operands
+            .into_iter()
+            .map(|operand| {
+                let resolved = match &operand {
+                    Object::Name(name) if !category.is_empty() => {
+                        resource_object(doc, resources, category, name).and_then(|(id, _)| id)
+                    }
+                    _ => None,
+                };
+                resolved.map_or(operand, Object::Reference)
+            })
+            .collect();
+        *fingerprint.entry(format!("{:?} {}", operands, operator)).or_default() += 1;
+    }
+    Some(fingerprint)
+}
+
+// ========== Colour Detection and Conversion ==========
+
+/// Colour model of a colour space, as far as grayscale conversion cares
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorKind {
+    Gray,
+    Rgb,
+    Cmyk,
+    Pattern,
+    /// Indexed, Lab, Separation, DeviceN or unknown; left alone
+    Other,
+    /// Whatever was current where a form is drawn
+    Inherited,
+}
+
+/// Colour space kinds by resource name, from a /ColorSpace resource dictionary
+type ColorSpaces = HashMap<Vec<u8>, ColorKind>;
+
+fn color_kind(doc: &Document, space: &Object) -> ColorKind {
+    let space = doc.dereference(space).map(|(_, object)| object).unwrap_or(space);
+    match space {
+        Object::Name(name) => device_color_kind(name).unwrap_or(ColorKind::Other),
+        Object::Array(items) => match items.first().and_then(|family| family.as_name().ok()) {
+            Some(b"Pattern") => ColorKind::Pattern,
+            Some(b"CalGray") => ColorKind::Gray,
+            Some(b"CalRGB") => ColorKind::Rgb,
+            Some(b"ICCBased") => {
+                let components = items
+                    .get(1)
+                    .and_then(|profile| doc.dereference(profile).ok())
+                    .and_then(|(_, profile)| profile.as_stream().ok())
+                    .and_then(|profile| profile.dict.get(b"N").and_then(Object::as_i64).ok());
+                match components {
+                    Some(1) => ColorKind::Gray,
+                    Some(3) => ColorKind::Rgb,
+                    Some(4) => ColorKind::Cmyk,
+                    _ => ColorKind::Other,
+                }
+            }
+            _ => ColorKind::Other,
+        },
+        _ => ColorKind::Other,
+    }
+}
+
+fn device_color_kind(name: &[u8]) -> Option<ColorKind> {
+    match name {
+        b"DeviceGray" | b"G" => Some(ColorKind::Gray),
+        b"DeviceRGB" | b"RGB" => Some(ColorKind::Rgb),
+        b"DeviceCMYK" | b"CMYK" => Some(ColorKind::Cmyk),
+        b"Pattern" => Some(ColorKind::Pattern),
+        _ => None,
+    }
+}
+
+fn color_spaces(doc: &Document, resources: Option<&lopdf::Dictionary>) -> ColorSpaces {
+    let spaces = resources
+        .and_then(|resources| resources.get(b"ColorSpace").ok())
+        .and_then(|spaces| doc.dereference(spaces).ok())
+        .and_then(|(_, spaces)| spaces.as_dict().ok());
+    spaces
+        .map(|spaces| {
+            spaces
+                .iter()
+                .map(|(name, space)| (name.clone(), color_kind(doc, space)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Content converted to grayscale in one piece
+#[derive(Debug, Clone, Copy)]
+enum ContentTarget {
+    /// A page's content streams, taken together since the colour state
+    /// carries over from one to the next
+    Page(ObjectId),
+    /// A Form XObject, tiling pattern or annotation appearance, and the
+    /// colour space it starts in
+    Stream(ObjectId, ColorKind),
+}
+
+/// Everything drawn on the pages (page contents, Form XObjects, tiling
+/// patterns and printable annotation appearances), each with the colour
+/// spaces its resources define
+fn collect_content_streams(doc: &Document) -> Vec<(ContentTarget, ColorSpaces)> {
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    for page_id in doc.get_pages().into_values() {
+        let resources = inherited_attribute(doc, page_id, b"Resources").and_then(|object| object.as_dict().ok());
+        found.push((ContentTarget::Page(page_id), color_spaces(doc, resources)));
+        collect_resource_streams(doc, resources, 1, &mut seen, &mut found);
+        for (stream_id, _) in printable_appearances(doc, page_id) {
+            collect_content_stream(doc, stream_id, ColorKind::Gray, None, 0, &mut seen, &mut found);
+        }
+    }
+    found
+}
+
+fn collect_content_stream(
+    doc: &Document,
+    stream_id: ObjectId,
+    initial: ColorKind,
+    inherited_resources: Option<&lopdf::Dictionary>,
+    depth: usize,
+    seen: &mut HashSet<ObjectId>,
+    found: &mut Vec<(ContentTarget, ColorSpaces)>,
+) {
+    if depth > MAX_FORM_DEPTH || !seen.insert(stream_id) {
+        return;
+    }
+    let Ok(stream) = doc.get_object(stream_id).and_then(Object::as_stream) else {
+        return;
+    };
+    let resources = stream
+        .dict
+        .get(b"Resources")
+        .ok()
+        .and_then(|object| doc.dereference(object).ok())
+        .and_then(|(_, object)| object.as_dict().ok())
+        .or(inherited_resources);
+    found.push((ContentTarget::Stream(stream_id, initial), color_spaces(doc, resources)));
+    collect_resource_streams(doc, resources, depth + 1, seen, found);
+}
+
+/// Forms and tiling patterns named in a resource dictionary, which carry
+/// content of their own
+fn collect_resource_streams(
+    doc: &Document,
+    resources: Option<&lopdf::Dictionary>,
+    depth: usize,
+    seen: &mut HashSet<ObjectId>,
+    found: &mut Vec<(ContentTarget, ColorSpaces)>,
+) {
+    for category in [&b"XObject"[..], b"Pattern"] {
+        let entries = resources
+            .and_then(|resources| resources.get(category).ok())
+            .and_then(|entries| doc.dereference(entries).ok())
+            .and_then(|(_, entries)| entries.as_dict().ok());
+        for (_, entry) in entries.into_iter().flat_map(|entries| entries.iter()) {
+            let Ok(id) = entry.as_reference() else { continue };
+            let Ok(child) = doc.get_object(id).and_then(Object::as_stream) else {
+                continue;
+            };
+            let tiling = child.dict.get(b"PatternType").and_then(Object::as_i64).ok() == Some(1);
+            // Forms draw in the graphics state of whoever paints them
+            if is_form(child) {
+                collect_content_stream(doc, id, ColorKind::Inherited, resources, depth, seen, found);
+            } else if tiling {
+                collect_content_stream(doc, id, ColorKind::Gray, resources, depth, seen, found);
+            }
+        }
+    }
+}
+
+/// Rewrite the colour operators of content in DeviceGray, starting in the
+/// `initial` fill and stroke colour space. Inline images are kept byte for
+/// byte (lopdf can't write them back out), along with their own colours.
+/// Returns `None` when nothing changed or the content can't be rewritten
+/// safely, in which case none of it is.
+fn grayscale_content(
+    content: &[u8],
+    spaces: &ColorSpaces,
+    initial: ColorKind,
+    boost_contrast: bool,
+) -> Option<Vec<u8>> {
+    let mut runs = Vec::new();
+    for (operators, image) in split_inline_images(content) {
+        runs.push((Content::decode(operators).ok()?.operations, image));
+    }
+
+    // First pass: which fill colour operations end up colouring text
+    let mut text_fills = HashSet::new();
+    if boost_contrast {
+        let mut fill = None;
+        let mut saved = Vec::new();
+        for (run, (operations, _)) in runs.iter().enumerate() {
+            for (index, operation) in operations.iter().enumerate() {
+                match operation.operator.as_str() {
+                    "q" => saved.push(fill),
+                    "Q" => fill = saved.pop().flatten(),
+                    "g" | "rg" | "k" | "sc" | "scn" => fill = Some((run, index)),
+                    "cs" => fill = None,
+                    "Tj" | "TJ" | "'" | "\"" => {
+                        text_fills.extend(fill);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let mut changed = false;
+    let mut fill_space = initial;
+    let mut stroke_space = initial;
+    let mut saved = Vec::new();
+    for (run, (operations, _)) in runs.iter_mut().enumerate() {
+        for (index, operation) in operations.iter_mut().enumerate() {
+            let text = text_fills.contains(&(run, index));
+            let (kind, gray_operator) = match operation.operator.as_str() {
+                "q" => {
+                    saved.push((fill_space, stroke_space));
+                    continue;
+                }
+                "Q" => {
+                    (fill_space, stroke_space) = saved.pop().unwrap_or((initial, initial));
+                    continue;
+                }
+                "cs" | "CS" => {
+                    let kind = operation
+                        .operands
+                        .first()
+                        .and_then(|name| name.as_name().ok())
+                        .and_then(|name| device_color_kind(name).or_else(|| spaces.get(name).copied()))
+                        .unwrap_or(ColorKind::Other);
+                    if operation.operator == "cs" {
+                        fill_space = kind;
+                    } else {
+                        stroke_space = kind;
+                    }
+                    if matches!(kind, ColorKind::Rgb | ColorKind::Cmyk) {
+                        operation.operands = vec![Object::Name(b"DeviceGray".to_vec())];
+                        changed = true;
+                    }
+                    continue;
+                }
+                "rg" => (ColorKind::Rgb, "g"),
+                "RG" => (ColorKind::Rgb, "G"),
+                "k" => (ColorKind::Cmyk, "g"),
+                "K" => (ColorKind::Cmyk, "G"),
+                "g" => (ColorKind::Gray, "g"),
+                "G" => (ColorKind::Gray, "G"),
+                "sc" | "scn" => (fill_space, "sc"),
+                "SC" | "SCN" => (stroke_space, "SC"),
+                _ => continue,
+            };
+            // An inherited colour space was converted where it was set if it
+            // was RGB or CMYK, which the number of components tells apart
+            let kind = match (kind, operation.operands.len()) {
+                (ColorKind::Inherited, 3) => ColorKind::Rgb,
+                (ColorKind::Inherited, 4) => ColorKind::Cmyk,
+                (kind, _) => kind,
+            };
+            if !matches!(kind, ColorKind::Rgb | ColorKind::Cmyk) {
+                continue;
+            }
+
+            let values: Option<Vec<f32>> = operation.operands.iter().map(|value| value.as_float().ok()).collect();
+            let Some(rgb) = values.and_then(|values| to_rgb(kind, &values)) else {
+                continue;
+            };
+            *operation = Operation::new(
+                gray_operator,
+                vec![Object::Real(gray_level(rgb, text && boost_contrast))],
+            );
+            changed = true;
+        }
+    }
+
+    if !changed {
+        return None;
+    }
+    let mut gray = Vec::with_capacity(content.len());
+    for (operations, image) in runs {
+        gray.extend(Content { operations }.encode().ok()?);
+        if let Some(image) = image {
+            gray.extend_from_slice(image);
+            gray.push(b'\n');
+        }
+    }
+    Some(gray)
+}
+
+/// Split content into runs of operators, each followed by the inline image
+/// (`BI … ID … EI`) that ends it, if any
+fn split_inline_images(content: &[u8]) -> Vec<(&[u8], Option<&[u8]>)> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+    while let Some((token_start, token_end)) = next_keyword(content, pos) {
+        pos = token_end;
+        if &content[token_start..token_end] != b"BI" {
+            continue;
+        }
+        // The image dictionary runs up to ID, which one whitespace byte
+        // separates from the image data
+        let mut data_start = None;
+        while let Some((keyword_start, keyword_end)) = next_keyword(content, pos) {
+            pos = keyword_end;
+            if &content[keyword_start..keyword_end] == b"ID" {
+                data_start = Some(keyword_end + 1);
+                break;
+            }
+        }
+        let end = data_start.map_or(content.len(), |data_start| inline_image_end(content, data_start));
+        runs.push((&content[start..token_start], Some(&content[token_start..end])));
+        start = end;
+        pos = end;
+    }
+    runs.push((&content[start..], None));
+    runs
+}
+
+/// End of inline image data: just past the first `EI` set off by whitespace
+fn inline_image_end(content: &[u8], data_start: usize) -> usize {
+    (data_start.max(1)..content.len().saturating_sub(1))
+        .find(|&index| {
+            content[index..].starts_with(b"EI")
+                && is_pdf_whitespace(content[index - 1])
+                && content.get(index + 2).is_none_or(|&byte| !is_regular_byte(byte))
+        })
+        .map_or(content.len(), |index| index + 2)
+}
+
+/// Span of the next bare keyword or number in content, skipping strings,
+/// names and comments so that e.g. `(BI)` or `/BI` isn't taken for one
+fn next_keyword(content: &[u8], mut pos: usize) -> Option<(usize, usize)> {
+    while pos < content.len() {
+        match content[pos] {
+            b'%' => {
+                while pos < content.len() && !matches!(content[pos], b'\r' | b'\n') {
+                    pos += 1;
+                }
+            }
+            b'(' => {
+                let mut depth = 0;
+                while pos < content.len() {
+                    match content[pos] {
+                        b'\\' => pos += 1,
+                        b'(' => depth += 1,
+                        b')' => depth -= 1,
+                        _ => {}
+                    }
+                    pos += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            b'<' if content.get(pos + 1) != Some(&b'<') => {
+                pos = content[pos..].iter().position(|&byte| byte == b'>').map_or(content.len(), |end| pos + end + 1);
+            }
+            b'/' => {
+                pos += 1;
+                while pos < content.len() && is_regular_byte(content[pos]) {
+                    pos += 1;
+                }
+            }
+            byte if is_regular_byte(byte) => {
+                let start = pos;
+                while pos < content.len() && is_regular_byte(content[pos]) {
+                    pos += 1;
+                }
+                return Some((start, pos));
+            }
+            _ => pos += 1,
+        }
+    }
+    None
+}
+
+fn is_pdf_whitespace(byte: u8) -> bool {
+    matches!(byte, b'\0' | b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+/// Neither whitespace nor a delimiter, so part of a keyword, number or name
+fn is_regular_byte(byte: u8) -> bool {
+    !is_pdf_whitespace(byte) && !b"()<>[]{}/%".contains(&byte)
+}
+
+fn to_rgb(kind: ColorKind, values: &[f32]) -> Option<[f32; 3]> {
+    match (kind, values) {
+        (ColorKind::Gray, [gray]) => Some([*gray; 3]),
+        (ColorKind::Rgb, [r, g, b]) => Some([*r, *g, *b]),
+        (ColorKind::Cmyk, [c, m, y, k]) => Some([(1.0 - c) * (1.0 - k), (1.0 - m) * (1.0 - k), (1.0 - y) * (1.0 - k)]),
+        _ => None,
+    }
+}
+
+/// Luminance of an RGB colour (ITU-R BT.601 weights). Boosted colours
+/// (coloured text) are darkened to half their luminance, so that yellow or
+/// light green text doesn't fade into the paper.
+fn gray_level(rgb: [f32; 3], boost: bool) -> f32 {
+    let [r, g, b] = rgb.map(|value| value.clamp(0.0, 1.0));
+    let luminance = 0.299 * r + 0.587 * g + 0.114 * b;
+    let gray = if boost && chroma(rgb) > 0.1 { luminance * 0.5 } else { luminance };
+    // Keep the written numbers short
+    (gray * 1000.0).round() / 1000.0
+}
+
+/// How far a colour is from gray: 0 for neutral colours, 1 for pure hues
+fn chroma(rgb: [f32; 3]) -> f32 {
+    let [r, g, b] = rgb.map(|value| value.clamp(0.0, 1.0));
+    r.max(g).max(b) - r.min(g).min(b)
+}
+
+fn is_image(stream: &Stream) -> bool {
+    matches!(stream.dict.get(b"Subtype").and_then(Object::as_name), Ok(b"Image"))
+}
+
+/// Grayscale copy of an 8-bit RGB, CMYK or indexed image. Stencil masks,
+/// images with a /Decode array and exotic encodings are left unchanged.
+fn grayscale_image(doc: &Document, stream: &Stream) -> Option<Stream> {
+    let dict = &stream.dict;
+    if dict.get(b"ImageMask").and_then(Object::as_bool).unwrap_or(false) || dict.has(b"Decode") {
+        return None;
+    }
+    if dict.get(b"BitsPerComponent").and_then(Object::as_i64).ok() != Some(8) {
+        return None;
+    }
+    let space = dict.get(b"ColorSpace").ok()?;
+    let space = doc.dereference(space).ok()?.1;
+
+    // Indexed images only need their palette converted
+    if let Ok(items) = space.as_array() {
+        if items.first().and_then(|family| family.as_name().ok()) == Some(b"Indexed") {
+            let [_, base, hival, lookup] = items.as_slice() else {
+                return None;
+            };
+            let kind = color_kind(doc, base);
+            let components = match kind {
+                ColorKind::Rgb => 3,
+                ColorKind::Cmyk => 4,
+                _ => return None,
+            };
+            let palette = match doc.dereference(lookup).ok()?.1 {
+                Object::String(bytes, _) => bytes.clone(),
+                Object::Stream(lookup) => lookup.get_plain_content().ok()?,
+                _ => return None,
+            };
+            let gray_palette = grayscale_samples(&palette, kind, components)?;
+            let mut gray = stream.clone();
+            gray.dict.set(
+                "ColorSpace",
+                vec![
+                    Object::Name(b"Indexed".to_vec()),
+                    Object::Name(b"DeviceGray".to_vec()),
+                    hival.clone(),
+                    Object::String(gray_palette, lopdf::StringFormat::Hexadecimal),
+                ],
+            );
+            return Some(gray);
+        }
+    }
+
+    let kind = color_kind(doc, space);
+    let components = match kind {
+        ColorKind::Rgb => 3,
+        ColorKind::Cmyk => 4,
+        _ => return None,
+    };
+    let width = dict.get(b"Width").and_then(Object::as_i64).ok()?;
+    let height = dict.get(b"Height").and_then(Object::as_i64).ok()?;
+
+    let filters = stream.filters().unwrap_or_default();
+    let mut gray = stream.clone();
+    if filters == [b"DCTDecode".as_slice()] {
+        // Re-encode JPEGs as JPEG so photos don't balloon in size
+        let decoded = image::load_from_memory_with_format(&stream.content, image::ImageFormat::Jpeg).ok()?;
+        let luma = decoded.to_luma8();
+        if i64::from(luma.width()) != width || i64::from(luma.height()) != height {
+            return None;
+        }
+        let mut jpeg = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, 85)
+            .encode_image(&luma)
+            .ok()?;
+        gray.dict.remove(b"DecodeParms");
+        gray.dict.set("Length", jpeg.len() as i64);
+        gray.content = jpeg;
+    } else {
+        // Other filters (JPX, JBIG2, CCITT) fail to decompress and are skipped
+        let samples = stream.get_plain_content().ok()?;
+        let expected = usize::try_from(width * height).ok()? * components;
+        if samples.len() < expected {
+            return None;
+        }
+        gray.set_plain_content(grayscale_samples(&samples[..expected], kind, components)?);
+    }
+    gray.dict.set("ColorSpace", "DeviceGray");
+    Some(gray)
+}
+
+/// Map interleaved 8-bit RGB or CMYK samples to one luminance byte each
+fn grayscale_samples(samples: &[u8], kind: ColorKind, components: usize) -> Option<Vec<u8>> {
+    let gray = samples
+        .chunks_exact(components)
+        .map(|pixel| {
+            let mut values = [0.0f32; 4];
+            for (value, &sample) in values.iter_mut().zip(pixel) {
+                *value = f32::from(sample) / 255.0;
+            }
+            to_rgb(kind, &values[..components]).map(|rgb| (gray_level(rgb, false) * 255.0).round() as u8)
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    Some(gray)
+}
+
+/// Grayscale copy of a shading dictionary, or of a shading pattern with the
+/// shading inline. Only shadings coloured by exponential or stitching
+/// functions (what gradient exporters write) are converted; sampled
+/// functions and mesh vertex colours stay in colour.
+fn grayscale_shading(doc: &Document, dict: &lopdf::Dictionary) -> Option<lopdf::Dictionary> {
+    if dict.get(b"PatternType").and_then(Object::as_i64).ok() == Some(2) {
+        let Ok(Object::Dictionary(shading)) = dict.get(b"Shading") else {
+            return None;
+        };
+        let mut pattern = dict.clone();
+        pattern.set("Shading", grayscale_shading(doc, shading)?);
+        return Some(pattern);
+    }
+    if !dict.has(b"ShadingType") {
+        return None;
+    }
+
+    let kind = color_kind(doc, dict.get(b"ColorSpace").ok()?);
+    if !matches!(kind, ColorKind::Rgb | ColorKind::Cmyk) {
+        return None;
+    }
+    let function = grayscale_function(doc, dict.get(b"Function").ok()?, kind, 0)?;
+
+    let mut gray = dict.clone();
+    gray.set("ColorSpace", "DeviceGray");
+    gray.set("Function", function);
+    if let Ok(background) = dict.get(b"Background").and_then(Object::as_array) {
+        let values: Option<Vec<f32>> = background.iter().map(|value| value.as_float().ok()).collect();
+        match values.and_then(|values| to_rgb(kind, &values)) {
+            Some(rgb) => gray.set("Background", vec![Object::Real(gray_level(rgb, false))]),
+            None => {
+                gray.remove(b"Background");
+            }
+        }
+    }
+    Some(gray)
+}
+
+/// Single-output version of an exponential (type 2) or stitching (type 3)
+/// colour function
+fn grayscale_function(doc: &Document, function: &Object, kind: ColorKind, depth: usize) -> Option<Object> {
+    if depth > MAX_FORM_DEPTH {
+        return None;
+    }
+    let function = doc.dereference(function).ok()?.1.as_dict().ok()?;
+    let mut gray = function.clone();
+    match function.get(b"FunctionType").and_then(Object::as_i64).ok()? {
+        2 => {
+            for key in [&b"C0"[..], b"C1"] {
+                let values: Option<Vec<f32>> = function
+                    .get(key)
+                    .and_then(Object::as_array)
+                    .ok()?
+                    .iter()
+                    .map(|value| value.as_float().ok())
+                    .collect();
+                let rgb = to_rgb(kind, &values?)?;
+                gray.set(key, vec![Object::Real(gray_level(rgb, false))]);
+            }
+        }
+        3 => {
+            let functions = function
+                .get(b"Functions")
+                .and_then(Object::as_array)
+                .ok()?
+                .iter()
+                .map(|function| grayscale_function(doc, function, kind, depth + 1))
+                .collect::<Option<Vec<Object>>>()?;
+            gray.set("Functions", functions);
+        }
+        _ => return None,
+    }
+    gray.remove(b"Range");
+    Some(Object::Dictionary(gray))
+}
+
+/// Chroma above which a colour operator counts as colour
+const NEUTRAL_TOLERANCE: f32 = 0.04;
+
+/// Chroma (0-255) above which an image pixel counts as colour; JPEG noise in
+/// gray scans stays below it
+const PIXEL_CHROMA_THRESHOLD: f32 = 24.0;
+
+/// Results of colour checks on shared forms, images and patterns, by object
+type ColorCache = HashMap<ObjectId, bool>;
+
+/// Whether anything on a page prints in colour. Content that can't be
+/// inspected (unknown colour spaces, undecodable images) counts as colour.
+fn page_uses_color(doc: &Document, page_id: ObjectId, cache: &mut ColorCache) -> bool {
+    let resources = inherited_attribute(doc, page_id, b"Resources").and_then(|object| object.as_dict().ok());
+    let content = page_content_bytes(doc, page_id);
+    scan_color(doc, &content, resources, 0, cache)
+        || printable_appearances(doc, page_id)
+            .into_iter()
+            .any(|(stream_id, _)| content_stream_uses_color(doc, stream_id, None, 1, cache))
+}
+
+/// Walk a content stream, stopping at the first colour it paints with
+fn scan_color(
+    doc: &Document,
+    content: &[u8],
+    resources: Option<&lopdf::Dictionary>,
+    depth: usize,
+    cache: &mut ColorCache,
+) -> bool {
+    let Ok(content) = Content::decode(content) else {
+        return true;
+    };
+    let spaces = color_spaces(doc, resources);
+
+    let mut fill_space = ColorKind::Gray;
+    let mut stroke_space = ColorKind::Gray;
+    let mut saved = Vec::new();
+    for operation in &content.operations {
+        let operands = &operation.operands;
+        let uses_color = match operation.operator.as_str() {
+            "q" => {
+                saved.push((fill_space, stroke_space));
+                false
+            }
+            "Q" => {
+                (fill_space, stroke_space) = saved.pop().unwrap_or((ColorKind::Gray, ColorKind::Gray));
+                false
+            }
+            "cs" | "CS" => {
+                let kind = operands
+                    .first()
+                    .and_then(|name| name.as_name().ok())
+                    .and_then(|name| device_color_kind(name).or_else(|| spaces.get(name).copied()))
+                    .unwrap_or(ColorKind::Other);
+                if operation.operator == "cs" {
+                    fill_space = kind;
+                } else {
+                    stroke_space = kind;
+                }
+                false
+            }
+            "rg" | "RG" => is_colored(ColorKind::Rgb, operands),
+            "k" | "K" => is_colored(ColorKind::Cmyk, operands),
+            "sc" | "scn" | "SC" | "SCN" => {
+                let space = if operation.operator.starts_with('s') { fill_space } else { stroke_space };
+                match space {
+                    ColorKind::Gray => false,
+                    ColorKind::Rgb | ColorKind::Cmyk => is_colored(space, operands),
+                    ColorKind::Pattern => operands
+                        .last()
+                        .and_then(|name| name.as_name().ok())
+                        .and_then(|name| resource_object(doc, resources, b"Pattern", name))
+                        .is_some_and(|(id, pattern)| {
+                            cached(cache, id, |cache| pattern_uses_color(doc, pattern, resources, depth, cache))
+                        }),
+                    ColorKind::Other | ColorKind::Inherited => true,
+                }
+            }
+            "sh" => operands
+                .first()
+                .and_then(|name| name.as_name().ok())
+                .and_then(|name| resource_object(doc, resources, b"Shading", name))
+                .is_some_and(|(_, shading)| shading_uses_color(doc, shading)),
+            "Do" => operands
+                .first()
+                .and_then(|name| name.as_name().ok())
+                .and_then(|name| resource_object(doc, resources, b"XObject", name))
+                .is_some_and(|(id, xobject)| match (id, xobject.as_stream()) {
+                    (Some(id), Ok(stream)) if is_form(stream) => {
+                        content_stream_uses_color(doc, id, resources, depth + 1, cache)
+                    }
+                    (id, Ok(stream)) => cached(cache, id, |_| image_uses_color(doc, stream)),
+                    _ => false,
+                }),
+            "BI" => match operands.first() {
+                Some(Object::Stream(image)) => {
+                    let space = image.dict.get(b"ColorSpace").or_else(|_| image.dict.get(b"CS"));
+                    let kind = space.map(|space| match space {
+                        Object::Name(name) => device_color_kind(name)
+                            .or_else(|| spaces.get(name.as_slice()).copied())
+                            .unwrap_or(ColorKind::Other),
+                        space => color_kind(doc, space),
+                    });
+                    // Image masks take the fill colour, which was checked when set
+                    !matches!(kind, Ok(ColorKind::Gray) | Err(_))
+                }
+                _ => false,
+            },
+            _ => false,
+        };
+        if uses_color {
+            return true;
+        }
+    }
+    false
+}
+
+/// Check a form or tiling pattern's content, with its own resources if it has any
+fn content_stream_uses_color(
+    doc: &Document,
+    stream_id: ObjectId,
+    inherited_resources: Option<&lopdf::Dictionary>,
+    depth: usize,
+    cache: &mut ColorCache,
+) -> bool {
+    if depth > MAX_FORM_DEPTH {
+        return true;
+    }
+    if let Some(&uses_color) = cache.get(&stream_id) {
+        return uses_color;
+    }
+    let Ok(stream) = doc.get_object(stream_id).and_then(Object::as_stream) else {
+        return false;
+    };
+    let resources = stream
+        .dict
+        .get(b"Resources")
+        .ok()
+        .and_then(|object| doc.dereference(object).ok())
+        .and_then(|(_, object)| object.as_dict().ok())
+        .or(inherited_resources);
+    let uses_color = match stream.get_plain_content() {
+        Ok(content) => scan_color(doc, &content, resources, depth, cache),
+        Err(_) => true,
+    };
+    cache.insert(stream_id, uses_color);
+    uses_color
+}
+
+/// Run a colour check once per indirect object
+fn cached<F>(cache: &mut ColorCache, id: Option<ObjectId>, check: F) -> bool
+where
+    F: FnOnce(&mut ColorCache) -> bool,
+{
+    if let Some(uses_color) = id.and_then(|id| cache.get(&id)) {
+        return *uses_color;
+    }
+    let uses_color = check(cache);
+    if let Some(id) = id {
+        cache.insert(id, uses_color);
+    }
+    uses_color
+}
+
+fn is_colored(kind: ColorKind, operands: &[Object]) -> bool {
+    let values: Option<Vec<f32>> = operands.iter().map(|value| value.as_float().ok()).collect();
+    values
+        .and_then(|values| to_rgb(kind, &values))
+        .is_some_and(|rgb| chroma(rgb) > NEUTRAL_TOLERANCE)
+}
+
+fn pattern_uses_color(
+    doc: &Document,
+    pattern: &Object,
+    resources: Option<&lopdf::Dictionary>,
+    depth: usize,
+    cache: &mut ColorCache,
+) -> bool {
+    let (dict, stream) = match pattern {
+        Object::Stream(stream) => (&stream.dict, Some(stream)),
+        Object::Dictionary(dict) => (dict, None),
+        _ => return false,
+    };
+    match dict.get(b"PatternType").and_then(Object::as_i64) {
+        Ok(1) => {
+            // Uncoloured tiling patterns (PaintType 2) are painted in the scn colour
+            if dict.get(b"PaintType").and_then(Object::as_i64).ok() == Some(2) {
+                return false;
+            }
+            let Some(stream) = stream else { return true };
+            let resources = dict
+                .get(b"Resources")
+                .ok()
+                .and_then(|object| doc.dereference(object).ok())
+                .and_then(|(_, object)| object.as_dict().ok())
+                .or(resources);
+            match stream.get_plain_content() {
+                Ok(content) => depth >= MAX_FORM_DEPTH || scan_color(doc, &content, resources, depth + 1, cache),
+                Err(_) => true,
+            }
+        }
+        Ok(2) => dict
+            .get(b"Shading")
+            .ok()
+            .and_then(|shading| doc.dereference(shading).ok())
+            .is_none_or(|(_, shading)| shading_uses_color(doc, shading)),
+        _ => true,
+    }
+}
+
+fn shading_uses_color(doc: &Document, shading: &Object) -> bool {
+    let dict = match shading {
+        Object::Stream(stream) => &stream.dict,
+        Object::Dictionary(dict) => dict,
+        _ => return false,
+    };
+    let Ok(space) = dict.get(b"ColorSpace") else {
+        return true;
+    };
+    let kind = color_kind(doc, space);
+    match kind {
+        ColorKind::Gray => false,
+        ColorKind::Rgb | ColorKind::Cmyk => match dict.get(b"Function") {
+            Ok(function) => function_uses_color(doc, function, kind, 0),
+            // Mesh shadings with colours in the vertex data
+            Err(_) => true,
+        },
+        _ => true,
+    }
+}
+
+/// Whether an exponential or stitching function produces any colour; other
+/// function types count as colour
+fn function_uses_color(doc: &Document, function: &Object, kind: ColorKind, depth: usize) -> bool {
+    if depth > MAX_FORM_DEPTH {
+        return true;
+    }
+    let Some(function) = doc.dereference(function).ok().and_then(|(_, f)| f.as_dict().ok()) else {
+        return true;
+    };
+    match function.get(b"FunctionType").and_then(Object::as_i64) {
+        Ok(2) => [&b"C0"[..], b"C1"].iter().any(|key| {
+            function
+                .get(key)
+                .and_then(Object::as_array)
+                .map_or(true, |values| is_colored(kind, values))
+        }),
+        Ok(3) => function
+            .get(b"Functions")
+            .and_then(Object::as_array)
+            .map_or(true, |functions| {
+                functions
+                    .iter()
+                    .any(|function| function_uses_color(doc, function, kind, depth + 1))
+            }),
+        _ => true,
+    }
+}
+
+/// Whether an image has a noticeable share of coloured pixels. RGB images of
+/// gray scans count as gray.
+fn image_uses_color(doc: &Document, stream: &Stream) -> bool {
+    let dict = &stream.dict;
+    if dict.get(b"ImageMask").and_then(Object::as_bool).unwrap_or(false) {
+        return false;
+    }
+    // JPX images may carry their colour space in the codestream
+    let Some(space) = dict.get(b"ColorSpace").ok().and_then(|space| doc.dereference(space).ok()) else {
+        return true;
+    };
+    let space = space.1;
+
+    if let Ok([family, base, _, lookup]) = space.as_array().map(Vec::as_slice) {
+        if family.as_name().ok() == Some(b"Indexed") {
+            let kind = color_kind(doc, base);
+            let palette = match doc.dereference(lookup).map(|(_, lookup)| lookup) {
+                Ok(Object::String(bytes, _)) => Some(bytes.clone()),
+                Ok(Object::Stream(lookup)) => lookup.get_plain_content().ok(),
+                _ => None,
+            };
+            return match (kind, palette) {
+                (ColorKind::Gray, _) => false,
+                (ColorKind::Rgb, Some(palette)) => samples_use_color(&palette, kind, 3),
+                (ColorKind::Cmyk, Some(palette)) => samples_use_color(&palette, kind, 4),
+                _ => true,
+            };
+        }
+    }
+
+    let kind = color_kind(doc, space);
+    let components = match kind {
+        ColorKind::Gray => return false,
+        ColorKind::Rgb => 3,
+        ColorKind::Cmyk => 4,
+        _ => return true,
+    };
+    if stream.filters().unwrap_or_default() == [b"DCTDecode".as_slice()] {
+        return match image::load_from_memory_with_format(&stream.content, image::ImageFormat::Jpeg) {
+            Ok(decoded) => samples_use_color(decoded.to_rgb8().as_raw(), ColorKind::Rgb, 3),
+            Err(_) => true,
+        };
+    }
+    if dict.get(b"BitsPerComponent").and_then(Object::as_i64).ok() != Some(8) {
+        return true;
+    }
+    match stream.get_plain_content() {
+        Ok(samples) => samples_use_color(&samples, kind, components),
+        Err(_) => true,
+    }
+}
+
+/// Whether more than 0.1% of interleaved 8-bit RGB or CMYK samples are coloured
+fn samples_use_color(samples: &[u8], kind: ColorKind, components: usize) -> bool {
+    let pixels = samples.len() / components;
+    let colored = samples
+        .chunks_exact(components)
+        .filter(|pixel| {
+            let mut values = [0.0f32; 4];
+            for (value, &sample) in values.iter_mut().zip(*pixel) {
+                *value = f32::from(sample) / 255.0;
+            }
+            to_rgb(kind, &values[..components]).is_some_and(|rgb| chroma(rgb) * 255.0 > PIXEL_CHROMA_THRESHOLD)
+        })
+        .count();
+    colored * 1000 > pixels
+}
+
+// ========== Content Bounds ==========
+
+/// Glyph widths of a font, enough to estimate where a run of text ends
+struct FontMetrics {
+    /// Type0 fonts are read as two-byte codes (Identity-H and most CJK CMaps)
+    two_byte: bool,
+    first_char: u32,
+    /// Advance widths in thousandths of an em, from `first_char` on
+    widths: Vec<f32>,
+    /// Sparse widths of Type0 fonts
+    cid_widths: HashMap<u32, f32>,
+    default_width: f32,
+}
+
+impl FontMetrics {
+    fn width(&self, code: u32) -> f32 {
+        if let Some(width) = self.cid_widths.get(&code) {
+            return *width;
+        }
+        code.checked_sub(self.first_char)
+            .and_then(|index| self.widths.get(index as usize))
+            .copied()
+            .unwrap_or(self.default_width)
+    }
+}
+
+fn font_metrics(doc: &Document, font: &lopdf::Dictionary) -> FontMetrics {
+    let number = |object: &Object| doc.dereference(object).ok().and_then(|(_, value)| value.as_float().ok());
+    let mut metrics = FontMetrics {
+        two_byte: false,
+        first_char: 0,
+        widths: Vec::new(),
+        cid_widths: HashMap::new(),
+        // Wide enough for most Latin text; overestimating only keeps more margin
+        default_width: 600.0,
+    };
+
+    if font.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Type0") {
+        metrics.two_byte = true;
+        let descendant = font
+            .get(b"DescendantFonts")
+            .ok()
+            .and_then(|fonts| doc.dereference(fonts).ok())
+            .and_then(|(_, fonts)| fonts.as_array().ok()?.first())
+            .and_then(|descendant| doc.dereference(descendant).ok())
+            .and_then(|(_, descendant)| descendant.as_dict().ok());
+        let Some(descendant) = descendant else {
+            return metrics;
+        };
+        metrics.default_width = descendant.get(b"DW").ok().and_then(number).unwrap_or(1000.0);
+
+        // /W holds runs of `first [w1 w2 ...]` and ranges of `first last w`
+        let entries = descendant
+            .get(b"W")
+            .ok()
+            .and_then(|widths| doc.dereference(widths).ok())
+            .and_then(|(_, widths)| widths.as_array().ok());
+        let mut entries = entries.into_iter().flatten();
+        while let Some(first) = entries.next().and_then(|first| first.as_i64().ok()) {
+            let Some(next) = entries.next() else { break };
+            match doc.dereference(next).map(|(_, next)| next) {
+                Ok(Object::Array(widths)) => {
+                    for (offset, width) in widths.iter().enumerate() {
+                        if let Some(width) = number(width) {
+                            metrics.cid_widths.insert(first as u32 + offset as u32, width);
+                        }
+                    }
+                }
+                Ok(last) => {
+                    let (Ok(last), Some(width)) = (last.as_i64(), entries.next().and_then(number)) else {
+                        break;
+                    };
+                    // Guard against absurd ranges in broken files
+                    for code in first..=last.min(first + 0xFFFF) {
+                        metrics.cid_widths.insert(code as u32, width);
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        return metrics;
+    }
+
+    // Type3 glyph widths are in glyph space, scaled by the font matrix
+    let scale = font
+        .get(b"FontMatrix")
+        .and_then(Object::as_array)
+        .ok()
+        .and_then(|matrix| matrix.first())
+        .and_then(number)
+        .map_or(1.0, |a| a * 1000.0);
+    metrics.first_char = font.get(b"FirstChar").ok().and_then(number).unwrap_or(0.0) as u32;
+    if let Some(widths) = font
+        .get(b"Widths")
+        .ok()
+        .and_then(|widths| doc.dereference(widths).ok())
+        .and_then(|(_, widths)| widths.as_array().ok())
+    {
+        metrics.widths = widths.iter().map(|width| number(width).unwrap_or(0.0) * scale).collect();
+    }
+    let missing = font
+        .get(b"FontDescriptor")
+        .ok()
+        .and_then(|descriptor| doc.dereference(descriptor).ok())
+        .and_then(|(_, descriptor)| descriptor.as_dict().ok())
+        .and_then(|descriptor| descriptor.get(b"MissingWidth").ok())
+        .and_then(number);
+    if let Some(missing) = missing.filter(|width| *width > 0.0) {
+        metrics.default_width = missing * scale;
+    }
+    metrics
+}
+
+/// Text state operators that survive q/Q
+#[derive(Debug, Clone, Copy)]
+struct TextState {
+    font: Option<ObjectId>,
+    size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    /// Tz as a fraction
+    horizontal_scale: f32,
+    leading: f32,
+    rise: f32,
+    invisible: bool,
+}
+
+impl Default for TextState {
+    fn default() -> Self {
+        TextState {
+            font: None,
+            size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+            invisible: false,
+        }
+    }
+}
+
+/// Graphics state relevant to where marks land on the page
+#[derive(Debug, Clone, Copy)]
+struct BoundsState {
+    ctm: [f32; 6],
+    /// Clipping region in page space, as a bounding box
+    clip: [f32; 4],
+    line_width: f32,
+    fill_white: bool,
+    stroke_white: bool,
+    text: TextState,
+}
+
+/// Collects the bounding box of everything visibly painted on a page
+struct BoundsScanner<'a> {
+    doc: &'a Document,
+    fonts: HashMap<ObjectId, FontMetrics>,
+    bounds: Option<[f32; 4]>,
+}
+
+impl<'a> BoundsScanner<'a> {
+    fn add(&mut self, rect: [f32; 4], clip: [f32; 4]) {
+        let Some(rect) = intersect_rect(rect, clip) else { return };
+        self.bounds = Some(match self.bounds {
+            Some(bounds) => [
+                bounds[0].min(rect[0]),
+                bounds[1].min(rect[1]),
+                bounds[2].max(rect[2]),
+                bounds[3].max(rect[3]),
+            ],
+            None => rect,
+        });
+    }
+
+    fn scan(
+        &mut self,
+        content: &[u8],
+        resources: Option<&'a lopdf::Dictionary>,
+        initial: BoundsState,
+        depth: usize,
+    ) {
+        let operations = match Content::decode(content) {
+            Ok(content) => content.operations,
+            Err(_) => {
+                // Unknown content may be anywhere it is allowed to paint
+                self.add(initial.clip, initial.clip);
+                return;
+            }
+        };
+
+        let mut state = initial;
+        let mut saved = Vec::new();
+        let mut path: Vec<(f32, f32)> = Vec::new();
+        let mut clip_pending = false;
+        let mut text_matrix = IDENTITY_MATRIX;
+        let mut line_matrix = IDENTITY_MATRIX;
+
+        for operation in &operations {
+            let operands = &operation.operands;
+            let numbers: Vec<f32> = operands.iter().filter_map(|value| value.as_float().ok()).collect();
+            match operation.operator.as_str() {
+                "q" => saved.push(state),
+                "Q" => state = saved.pop().unwrap_or(initial),
+                "cm" => {
+                    if let Ok(matrix) = <[f32; 6]>::try_from(numbers) {
+                        state.ctm = concat_matrix(matrix, state.ctm);
+                    }
+                }
+                "w" => state.line_width = numbers.first().copied().unwrap_or(1.0),
+                "g" | "rg" | "k" | "sc" | "scn" => state.fill_white = is_white(operands),
+                "G" | "RG" | "K" | "SC" | "SCN" => state.stroke_white = is_white(operands),
+                "cs" => state.fill_white = false,
+                "CS" => state.stroke_white = false,
+
+                "m" | "l" | "c" | "v" | "y" => {
+                    path.extend(numbers.chunks_exact(2).map(|point| (point[0], point[1])));
+                }
+                "re" => {
+                    if let [x, y, width, height] = numbers[..] {
+                        path.extend([(x, y), (x + width, y), (x, y + height), (x + width, y + height)]);
+                    }
+                }
+                "W" | "W*" => clip_pending = true,
+                "f" | "F" | "f*" | "S" | "s" | "B" | "B*" | "b" | "b*" | "n" => {
+                    let operator = operation.operator.as_str();
+                    let fills = !matches!(operator, "S" | "s" | "n") && !state.fill_white;
+                    let strokes = matches!(operator, "S" | "s" | "B" | "B*" | "b" | "b*") && !state.stroke_white;
+                    if let Some(user_box) = points_box(&path) {
+                        let page_box = transform_rect(user_box, state.ctm);
+                        if fills {
+                            self.add(page_box, state.clip);
+                        }
+                        if strokes {
+                            let [a, b, c, d, _, _] = state.ctm;
+                            let half = state.line_width.max(1.0) * (a * d - b * c).abs().sqrt() / 2.0;
+                            let stroked = [page_box[0] - half, page_box[1] - half, page_box[2] + half, page_box[3] + half];
+                            self.add(stroked, state.clip);
+                        }
+                        if clip_pending {
+                            state.clip = intersect_rect(state.clip, page_box).unwrap_or([0.0; 4]);
+                        }
+                    }
+                    clip_pending = false;
+                    path.clear();
+                }
+
+                "BT" => {
+                    text_matrix = IDENTITY_MATRIX;
+                    line_matrix = IDENTITY_MATRIX;
+                }
+                "Tf" => {
+                    let font = operands
+                        .first()
+                        .and_then(|name| name.as_name().ok())
+                        .and_then(|name| resource_object(self.doc, resources, b"Font", name));
+                    state.text.font = font.and_then(|(id, _)| id);
+                    if let (Some(id), Some((_, Object::Dictionary(dict)))) = (state.text.font, font) {
+                        if !self.fonts.contains_key(&id) {
+                            let metrics = font_metrics(self.doc, dict);
+                            self.fonts.insert(id, metrics);
+                        }
+                    }
+                    state.text.size = operands.get(1).and_then(|size| size.as_float().ok()).unwrap_or(0.0);
+                }
+                "Tc" => state.text.char_spacing = numbers.first().copied().unwrap_or(0.0),
+                "Tw" => state.text.word_spacing = numbers.first().copied().unwrap_or(0.0),
+                "Tz" => state.text.horizontal_scale = numbers.first().copied().unwrap_or(100.0) / 100.0,
+                "TL" => state.text.leading = numbers.first().copied().unwrap_or(0.0),
+                "Ts" => state.text.rise = numbers.first().copied().unwrap_or(0.0),
+                "Tr" => {
+                    let mode = numbers.first().copied().unwrap_or(0.0) as i64;
+                    state.text.invisible = mode == 3 || mode == 7;
+                }
+                "Td" | "TD" => {
+                    if let [tx, ty] = numbers[..] {
+                        if operation.operator == "TD" {
+                            state.text.leading = -ty;
+                        }
+                        line_matrix = concat_matrix([1.0, 0.0, 0.0, 1.0, tx, ty], line_matrix);
+                        text_matrix = line_matrix;
+                    }
+                }
+                "Tm" => {
+                    if let Ok(matrix) = <[f32; 6]>::try_from(numbers) {
+                        line_matrix = matrix;
+                        text_matrix = matrix;
+                    }
+                }
+                "T*" | "'" | "\"" => {
+                    if operation.operator == "\"" {
+                        if let [word_spacing, char_spacing, ..] = numbers[..] {
+                            state.text.word_spacing = word_spacing;
+                            state.text.char_spacing = char_spacing;
+                        }
+                    }
+                    line_matrix = concat_matrix([1.0, 0.0, 0.0, 1.0, 0.0, -state.text.leading], line_matrix);
+                    text_matrix = line_matrix;
+                    if let Some(Object::String(bytes, _)) = operands.last() {
+                        self.show_text(bytes, &state, &mut text_matrix);
+                    }
+                }
+                "Tj" => {
+                    if let Some(Object::String(bytes, _)) = operands.first() {
+                        self.show_text(bytes, &state, &mut text_matrix);
+                    }
+                }
+                "TJ" => {
+                    let items = operands.first().and_then(|items| items.as_array().ok());
+                    for item in items.into_iter().flatten() {
+                        match item {
+                            Object::String(bytes, _) => self.show_text(bytes, &state, &mut text_matrix),
+                            item => {
+                                let adjustment = item.as_float().unwrap_or(0.0);
+                                let shift = -adjustment / 1000.0 * state.text.size * state.text.horizontal_scale;
+                                text_matrix = concat_matrix([1.0, 0.0, 0.0, 1.0, shift, 0.0], text_matrix);
+                            }
+                        }
+                    }
+                }
+
+                "sh" => self.add(state.clip, state.clip),
+                "BI" => self.add(transform_rect([0.0, 0.0, 1.0, 1.0], state.ctm), state.clip),
+                "Do" => {
+                    let Some(stream) = operands
+                        .first()
+                        .and_then(|name| name.as_name().ok())
+                        .and_then(|name| resource_stream(self.doc, resources, b"XObject", name))
+                    else {
+                        continue;
+                    };
+                    if is_form(stream) {
+                        if depth >= MAX_FORM_DEPTH {
+                            self.add(state.clip, state.clip);
+                        } else {
+                            self.scan_form(stream, resources, state, depth + 1);
+                        }
+                    } else {
+                        let mask = stream.dict.get(b"ImageMask").and_then(Object::as_bool).unwrap_or(false);
+                        if !mask || !state.fill_white {
+                            self.add(transform_rect([0.0, 0.0, 1.0, 1.0], state.ctm), state.clip);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Scan a Form XObject drawn with the given state, clipped to its /BBox
+    fn scan_form(
+        &mut self,
+        stream: &'a Stream,
+        inherited_resources: Option<&'a lopdf::Dictionary>,
+        mut state: BoundsState,
+        depth: usize,
+    ) {
+        let matrix = stream
+            .dict
+            .get(b"Matrix")
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|items| {
+                let values: Vec<f32> = items.iter().filter_map(|v| v.as_float().ok()).collect();
+                <[f32; 6]>::try_from(values).ok()
+            })
+            .unwrap_or(IDENTITY_MATRIX);
+        state.ctm = concat_matrix(matrix, state.ctm);
+        if let Some(bbox) = stream.dict.get(b"BBox").ok().and_then(|b| rect_from_object(self.doc, b)) {
+            state.clip = intersect_rect(state.clip, transform_rect(bbox, state.ctm)).unwrap_or([0.0; 4]);
+        }
+        let resources = stream
+            .dict
+            .get(b"Resources")
+            .ok()
+            .and_then(|object| self.doc.dereference(object).ok())
+            .and_then(|(_, object)| object.as_dict().ok())
+            .or(inherited_resources);
+        let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+        self.scan(&content, resources, state, depth);
+    }
+
+    /// Add the box of a run of text and move the text matrix past it. Glyph
+    /// heights are approximated from the font size.
+    fn show_text(&mut self, bytes: &[u8], state: &BoundsState, text_matrix: &mut [f32; 6]) {
+        let text = &state.text;
+        let metrics = text.font.and_then(|id| self.fonts.get(&id));
+        let two_byte = metrics.is_some_and(|metrics| metrics.two_byte);
+        let codes: Vec<u32> = if two_byte {
+            bytes.chunks(2).map(|pair| pair.iter().fold(0, |code, &b| code << 8 | u32::from(b))).collect()
+        } else {
+            bytes.iter().map(|&b| u32::from(b)).collect()
+        };
+
+        let mut width = 0.0;
+        for code in codes {
+            let glyph = metrics.map_or(600.0, |metrics| metrics.width(code));
+            let word = if !two_byte && code == 32 { text.word_spacing } else { 0.0 };
+            width += (glyph / 1000.0 * text.size + text.char_spacing + word) * text.horizontal_scale;
+        }
+
+        if !text.invisible && !state.fill_white && text.size != 0.0 {
+            let descent = text.rise - 0.25 * text.size;
+            let ascent = text.rise + 0.95 * text.size;
+            let text_box = [width.min(0.0), descent.min(ascent), width.max(0.0), descent.max(ascent)];
+            let page_box = transform_rect(text_box, concat_matrix(*text_matrix, state.ctm));
+            self.add(page_box, state.clip);
+        }
+        *text_matrix = concat_matrix([1.0, 0.0, 0.0, 1.0, width, 0.0], *text_matrix);
+    }
+}
+
+const IDENTITY_MATRIX: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Bounding box of everything visibly painted on a page, in default user
+/// space, or `None` for a page that paints nothing. White marks are ignored,
+/// as in blank page detection.
+fn content_bounds(doc: &Document, page_id: ObjectId) -> Option<[f32; 4]> {
+    let geometry = page_geometry(doc, page_id);
+    let resources = inherited_attribute(doc, page_id, b"Resources").and_then(|object| object.as_dict().ok());
+    let state = BoundsState {
+        ctm: IDENTITY_MATRIX,
+        clip: geometry.bbox,
+        line_width: 1.0,
+        fill_white: false,
+        stroke_white: false,
+        text: TextState::default(),
+    };
+
+    let mut scanner = BoundsScanner {
+        doc,
+        fonts: HashMap::new(),
+        bounds: None,
+    };
+    scanner.scan(&page_content_bytes(doc, page_id), resources, state, 0);
+    for (stream_id, matrix) in printable_appearances(doc, page_id) {
+        if let Ok(stream) = doc.get_object(stream_id).and_then(Object::as_stream) {
+            scanner.scan_form(stream, None, BoundsState { ctm: matrix, ..state }, 1);
+        }
+    }
+    scanner.bounds
+}
+
+fn points_box(points: &[(f32, f32)]) -> Option<[f32; 4]> {
+    let (&(x, y), rest) = points.split_first()?;
+    Some(rest.iter().fold([x, y, x, y], |b, &(x, y)| [b[0].min(x), b[1].min(y), b[2].max(x), b[3].max(y)]))
+}
+
+/// Overlap of two rectangles, `None` when they don't overlap
+fn intersect_rect(a: [f32; 4], b: [f32; 4]) -> Option<[f32; 4]> {
+    let rect = [a[0].max(b[0]), a[1].max(b[1]), a[2].min(b[2]), a[3].min(b[3])];
+    (rect[2] > rect[0] && rect[3] > rect[1]).then_some(rect)
+}
+
+// ========== Page Selection ==========
+
+/// Parse a page selection such as "1-3,7,10-" into 1-based page numbers, in
+/// the order written.
+///
+/// Terms are separated by commas and may be:
+/// - a page number, or a negative index counted from the end (-1 is the last page)
+/// - `last`, `odd` or `even`
+/// - a page label as shown by PDF viewers, e.g. "iv" or "A-3"
+/// - a range of two pages joined by `-`; either end may be left open or be a
+///   negative index (so `1--2` is all but the last page), and a range written
+///   backwards selects its pages in reverse
+///
+/// Empty terms such as the middle of "1,,2" are an error. Numbers always refer
+/// to physical pages. Labels are matched exactly first,
+/// then ignoring case.
+pub fn parse_page_selection(
+    expression: &str,
+    num_pages: u32,
+    labels: &[Option<String>],
+) -> Result<Vec<u32>, String> {
+    if expression.trim().is_empty() {
+        return Err("Page selection is empty".to_string());
+    }
+    let mut pages = Vec::new();
+    for term in expression.split(',').map(str::trim) {
+        if term.is_empty() {
+            return Err(format!("Empty term in page selection '{}'", expression));
+        }
+        pages.extend(parse_selection_term(term, num_pages, labels)?);
+    }
+    if pages.is_empty() {
+        return Err("Page selection is empty".to_string());
+    }
+    Ok(pages)
+}
+
+/// Load a document and parse a page selection against its pages and labels
+pub fn parse_page_selection_internal(
+    file_path: &str,
+    expression: &str,
+    password: Option<&str>,
+) -> Result<Vec<u32>, Box<dyn std::error::Error>> {
+    if !Path::new(file_path).exists() {
+        return Err(format!("PDF file not found: {}", file_path).into());
+    }
+    let doc = load_document(file_path, password).map_err(|e| match e {
+        lopdf::Error::InvalidPassword => format!("Incorrect password for {}", file_path),
+        e => format!("Failed to parse PDF for page selection: {}", e),
+    })?;
+    if doc.is_encrypted() {
+        return Err(format!("PDF is password protected: {}", file_path).into());
+    }
+
+    let num_pages = doc.get_pages().len() as u32;
+    Ok(parse_page_selection(expression, num_pages, &page_labels(&doc))?)
+}
+
+fn parse_selection_term(term: &str, num_pages: u32, labels: &[Option<String>]) -> Result<Vec<u32>, String> {
+    match term.to_ascii_lowercase().as_str() {
+        "odd" => return Ok((1..=num_pages).step_by(2).collect()),
+        "even" => return Ok((2..=num_pages).step_by(2).collect()),
+        _ => {}
+    }
+    if let Some(page) = selection_page(term, num_pages, labels)? {
+        return Ok(vec![page]);
+    }
+
+    // Labels such as "A-3" contain dashes too, so try every dash that could
+    // separate the two ends of a range
+    let mut error = None;
+    for (index, _) in term.match_indices('-') {
+        let (start_text, end_text) = (term[..index].trim(), term[index + 1..].trim());
+        let start = if start_text.is_empty() {
+            Ok(Some(1))
+        } else {
+            selection_page(start_text, num_pages, labels)
+        };
+        let end = if end_text.is_empty() {
+            Ok(Some(num_pages))
+        } else {
+            selection_page(end_text, num_pages, labels)
+        };
+        match (start, end) {
+            (Ok(Some(start)), Ok(Some(end))) if start <= end => return Ok((start..=end).collect()),
+            (Ok(Some(start)), Ok(Some(end))) => return Ok((end..=start).rev().collect()),
+            (Err(e), Ok(Some(_))) | (Ok(Some(_)), Err(e)) | (Err(e), Err(_)) => error = Some(e),
+            (Ok(Some(_)), Ok(None)) => {
+                error.get_or_insert(format!("Unknown page or page label '{}' in '{}'", end_text, term));
+            }
+            (Ok(None), Ok(Some(_))) => {
+                error.get_or_insert(format!("Unknown page or page label '{}' in '{}'", start_text, term));
+            }
+            _ => {}
+        }
+    }
+    Err(error.unwrap_or_else(|| format!("Unknown page or page label '{}'", term)))
+}
+
+/// Resolve one page of a selection; `None` when the text is neither a page
+/// number nor a label
+fn selection_page(text: &str, num_pages: u32, labels: &[Option<String>]) -> Result<Option<u32>, String> {
+    if text.eq_ignore_ascii_case("last") {
+        return Ok(Some(num_pages));
+    }
+    if let Ok(number) = text.parse::<i64>() {
+        return match number {
+            0 => Err("Page numbers start at 1".to_string()),
+            1.. if number <= num_pages as i64 => Ok(Some(number as u32)),
+            1.. => Err(format!("Page {} is out of range (document has {} pages)", number, num_pages)),
+            _ if -number <= num_pages as i64 => Ok(Some((num_pages as i64 + 1 + number) as u32)),
+            _ => Err(format!(
+                "Page {} counts back past the first page (document has {} pages)",
+                number, num_pages
+            )),
+        };
+    }
+
+    let find = |matches: &dyn Fn(&str) -> bool| {
+        labels
+            .iter()
+            .position(|label| label.as_deref().is_some_and(matches))
+            .map(|index| index as u32 + 1)
+    };
+    Ok(find(&|label| label == text).or_else(|| find(&|label| label.eq_ignore_ascii_case(text))))
+}
+
+/// Label of every page from the catalog's /PageLabels number tree; all `None`
+/// when the document has no labels
+pub fn page_labels(doc: &Document) -> Vec<Option<String>> {
+    let num_pages = doc.get_pages().len();
+    let mut ranges = Vec::new();
+    if let Some(tree) = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"PageLabels").ok())
+        .and_then(|object| doc.dereference(object).ok())
+        .and_then(|(_, object)| object.as_dict().ok())
+    {
+        collect_label_ranges(doc, tree, &mut ranges, 0);
+    }
+    if ranges.is_empty() {
+        return vec![None; num_pages];
+    }
+    ranges.sort_by_key(|(start, _)| *start);
+
+    (0..num_pages)
+        .map(|index| {
+            let (start, style) = ranges.iter().rev().find(|(start, _)| *start <= index)?;
+            Some(format_page_label(doc, style, index - start))
+        })
+        .collect()
+}
+
+/// Gather (first page index, label dictionary) pairs from a number tree node
+fn collect_label_ranges<'a>(
+    doc: &'a Document,
+    node: &'a lopdf::Dictionary,
+    ranges: &mut Vec<(usize, &'a lopdf::Dictionary)>,
+    depth: usize,
+) {
+    if depth > 16 {
+        return;
+    }
+    if let Ok(nums) = node.get(b"Nums").and_then(Object::as_array) {
+        for pair in nums.chunks(2) {
+            if let [start, style] = pair {
+                let style = doc.dereference(style).ok().and_then(|(_, object)| object.as_dict().ok());
+                if let (Ok(start), Some(style)) = (start.as_i64(), style) {
+                    ranges.push((start.max(0) as usize, style));
+                }
+            }
+        }
+    }
+    if let Ok(kids) = node.get(b"Kids").and_then(Object::as_array) {
+        for kid in kids {
+            if let Ok((_, Object::Dictionary(kid))) = doc.dereference(kid) {
+                collect_label_ranges(doc, kid, ranges, depth + 1);
+            }
+        }
+    }
+}
+
+/// Label of the page `offset` pages into a labelling range
+fn format_page_label(doc: &Document, style: &lopdf::Dictionary, offset: usize) -> String {
+    let prefix = style
+        .get(b"P")
+        .ok()
+        .and_then(|object| doc.dereference(object).ok())
+        .and_then(|(_, object)| lopdf::decode_text_string(object).ok())
+        .unwrap_or_default();
+    let number = style
+        .get(b"St")
+        .and_then(Object::as_i64)
+        .unwrap_or(1)
+        .max(1) as usize
+        + offset;
+
+    let numeral = match style.get(b"S").and_then(Object::as_name) {
+        Ok(b"D") => number.to_string(),
+        Ok(b"R") => roman_numeral(number),
+        Ok(b"r") => roman_numeral(number).to_lowercase(),
+        Ok(b"A") => letter_numeral(number),
+        Ok(b"a") => letter_numeral(number).to_lowercase(),
+        _ => String::new(),
+    };
+    prefix + &numeral
+}
+
+fn roman_numeral(mut number: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut numeral = String::new();
+    for (value, letters) in NUMERALS {
+        while number >= value {
+            numeral.push_str(letters);
+            number -= value;
+        }
+    }
+    numeral
+}
+
+/// A to Z, then AA to ZZ, AAA to ZZZ and so on
+fn letter_numeral(number: usize) -> String {
+    let letter = (b'A' + ((number - 1) % 26) as u8) as char;
+    letter.to_string().repeat((number - 1) / 26 + 1)
+}
+
+// ========== Outline ==========
+
+/// Maximum nesting followed in outlines and destination lookups
+const MAX_OUTLINE_DEPTH: usize = 32;
+
+/// The document's outline (bookmarks) with the page span of every entry
+pub fn document_outline(doc: &Document) -> Vec<OutlineEntry> {
+    let Some(root) = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"Outlines").ok())
+        .and_then(|object| doc.dereference(object).ok())
+        .and_then(|(_, object)| object.as_dict().ok())
+    else {
+        return Vec::new();
+    };
+    let pages: HashMap<ObjectId, u32> = doc
+        .get_pages()
+        .into_iter()
+        .map(|(number, page_id)| (page_id, number))
+        .collect();
+
+    let mut visited = HashSet::new();
+    let mut entries = outline_children(doc, root, &pages, &mut visited, 0);
+
+    // A section runs until the next entry at the same or a higher level
+    let mut flat = Vec::new();
+    flatten_outline(&entries, 0, &mut flat);
+    let last_pages: Vec<Option<u32>> = flat
+        .iter()
+        .enumerate()
+        .map(|(index, (level, page))| {
+            let page = (*page)?;
+            let next = flat[index + 1..]
+                .iter()
+                .filter(|(next_level, _)| next_level <= level)
+                .find_map(|(_, next_page)| *next_page);
+            Some(next.map_or(pages.len() as u32, |next| next.saturating_sub(1).max(page)))
+        })
+        .collect();
+    set_last_pages(&mut entries, &mut last_pages.into_iter());
+    entries
+}
+
+fn outline_children(
+    doc: &Document,
+    parent: &lopdf::Dictionary,
+    pages: &HashMap<ObjectId, u32>,
+    visited: &mut HashSet<ObjectId>,
+    depth: usize,
+) -> Vec<OutlineEntry> {
+    let mut entries = Vec::new();
+    if depth > MAX_OUTLINE_DEPTH {
+        return entries;
+    }
+    let mut next = parent.get(b"First").and_then(Object::as_reference).ok();
+    while let Some(item_id) = next {
+        // Broken outlines can link back to earlier items
+        if !visited.insert(item_id) {
+            break;
+        }
+        let Ok(item) = doc.get_dictionary(item_id) else {
+            break;
+        };
+        let title = item
+            .get(b"Title")
+            .ok()
+            .and_then(|object| doc.dereference(object).ok())
+            .and_then(|(_, object)| lopdf::decode_text_string(object).ok())
+            .unwrap_or_default();
+        entries.push(OutlineEntry {
+            title: title.split_whitespace().collect::<Vec<_>>().join(" "),
+            page: outline_target(doc, item).and_then(|page_id| pages.get(&page_id).copied()),
+            last_page: None,
+            children: outline_children(doc, item, pages, visited, depth + 1),
+        });
+        next = item.get(b"Next").and_then(Object::as_reference).ok();
+    }
+    entries
+}
+
+/// Page an outline item points to, through /Dest or a GoTo action
+fn outline_target(doc: &Document, item: &lopdf::Dictionary) -> Option<ObjectId> {
+    let destination = match item.get(b"Dest") {
+        Ok(destination) => destination,
+        Err(_) => {
+            let (_, action) = doc.dereference(item.get(b"A").ok()?).ok()?;
+            let action = action.as_dict().ok()?;
+            if action.get(b"S").and_then(Object::as_name).ok()? != b"GoTo" {
+                return None;
+            }
+            action.get(b"D").ok()?
+        }
+    };
+    destination_page(doc, destination, 0)
+}
+
+/// Page of an explicit destination array, a named destination or a
+/// dictionary holding one under /D
+fn destination_page(doc: &Document, destination: &Object, depth: usize) -> Option<ObjectId> {
+    if depth > MAX_OUTLINE_DEPTH {
+        return None;
+    }
+    match doc.dereference(destination).ok()?.1 {
+        Object::Array(items) => items.first()?.as_reference().ok(),
+        Object::Dictionary(dict) => destination_page(doc, dict.get(b"D").ok()?, depth + 1),
+        Object::Name(name) | Object::String(name, _) => {
+            destination_page(doc, named_destination(doc, name)?, depth + 1)
+        }
+        _ => None,
+    }
+}
+
+/// Look a name up in the catalog's /Dests dictionary or /Names /Dests tree
+fn named_destination<'a>(doc: &'a Document, name: &[u8]) -> Option<&'a Object> {
+    let catalog = doc.catalog().ok()?;
+    let dict = |object: &'a Object| doc.dereference(object).ok().and_then(|(_, object)| object.as_dict().ok());
+
+    if let Some(destination) = catalog
+        .get(b"Dests")
+        .ok()
+        .and_then(dict)
+        .and_then(|dests| dests.get(name).ok())
+    {
+        return Some(destination);
+    }
+    let tree = catalog.get(b"Names").ok().and_then(dict)?.get(b"Dests").ok().and_then(dict)?;
+    name_tree_lookup(doc, tree, name, 0)
+}
+
+fn name_tree_lookup<'a>(
+    doc: &'a Document,
+    node: &'a lopdf::Dictionary,
+    name: &[u8],
+    depth: usize,
+) -> Option<&'a Object> {
+    if depth > MAX_OUTLINE_DEPTH {
+        return None;
+    }
+    if let Ok(names) = node.get(b"Names").and_then(Object::as_array) {
+        for pair in names.chunks(2) {
+            if let [Object::String(key, _), value] = pair {
+                if key == name {
+                    return Some(value);
+                }
+            }
+        }
+    }
+    node.get(b"Kids")
+        .and_then(Object::as_array)
+        .ok()?
+        .iter()
+        .filter_map(|kid| doc.dereference(kid).ok()?.1.as_dict().ok())
+        .find_map(|kid| name_tree_lookup(doc, kid, name, depth + 1))
+}
+
+/// (level, page) of every entry, parents before their children
+fn flatten_outline(entries: &[OutlineEntry], level: usize, flat: &mut Vec<(usize, Option<u32>)>) {
+    for entry in entries {
+        flat.push((level, entry.page));
+        flatten_outline(&entry.children, level + 1, flat);
+    }
+}
+
+/// Assign last pages in `flatten_outline` order; a section also covers its
+/// subsections
+fn set_last_pages(entries: &mut [OutlineEntry], last_pages: &mut impl Iterator<Item = Option<u32>>) {
+    for entry in entries {
+        entry.last_page = last_pages.next().flatten();
+        set_last_pages(&mut entry.children, last_pages);
+        let children_last = entry.children.iter().filter_map(|child| child.last_page).max();
+        entry.last_page = entry.last_page.max(children_last);
+    }
+}
+
+/// Pages of the outline sections named by `titles`, in the order given.
+///
+/// A title matches an entry with the same title (ignoring case and any
+/// leading section number such as "2.1."), or else the first entry whose
+/// title starts with it followed by a non-alphanumeric character, so
+/// "Chapter 3" finds "Chapter 3: Sorting" but not "Chapter 30".
+fn outline_pages(outline: &[OutlineEntry], titles: &[String]) -> Result<Vec<u32>, String> {
+    if outline.is_empty() {
+        return Err("The document has no outline (bookmarks)".to_string());
+    }
+    let mut entries = Vec::new();
+    flatten_outline_entries(outline, &mut entries);
+
+    let mut pages = Vec::new();
+    for title in titles {
+        let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+        let starts_with = |text: &str| {
+            text.len() > title.len()
+                && text.is_char_boundary(title.len())
+                && text[..title.len()].eq_ignore_ascii_case(&title)
+                && !text[title.len()..].starts_with(char::is_alphanumeric)
+        };
+        let entry = entries
+            .iter()
+            .find(|entry| entry.title.eq_ignore_ascii_case(&title))
+            .or_else(|| {
+                entries
+                    .iter()
+                    .find(|entry| strip_section_number(&entry.title).eq_ignore_ascii_case(&title))
+            })
+            .or_else(|| entries.iter().find(|entry| starts_with(&entry.title)))
+            .or_else(|| {
+                entries
+                    .iter()
+                    .find(|entry| starts_with(strip_section_number(&entry.title)))
+            })
+            .ok_or_else(|| format!("No outline entry matches '{}'", title))?;
+        let (Some(first), Some(last)) = (entry.page, entry.last_page) else {
+            return Err(format!("Outline entry '{}' does not point to a page", entry.title));
+        };
+        // Sections may overlap, e.g. a chapter and one of its sections
+        pages.extend((first..=last).filter(|page| !pages.contains(page)).collect::<Vec<_>>());
+    }
+    Ok(pages)
+}
+
+/// Title without a leading section number like "2.", "2.1" or "A"
+fn strip_section_number(title: &str) -> &str {
+    match title.split_once(' ') {
+        Some((number, rest))
+            if number.trim_end_matches('.').split('.').all(|part| {
+                !part.is_empty()
+                    && (part.chars().all(|c| c.is_ascii_digit())
+                        || (part.len() == 1 && part.chars().all(|c| c.is_ascii_uppercase())))
+            }) =>
+        {
+            rest.trim_start()
+        }
+        _ => title,
+    }
+}
+
+fn flatten_outline_entries<'a>(entries: &'a [OutlineEntry], flat: &mut Vec<&'a OutlineEntry>) {
+    for entry in entries {
+        flat.push(entry);
+        flatten_outline_entries(&entry.children, flat);
+    }
+}
+
+// ========== Text Search ==========
+
+/// Characters of context kept on each side of a match in excerpts
+const EXCERPT_CONTEXT: usize = 40;
+
+/// Search every page of a PDF for a keyword, or a regular expression with `regex`
+pub fn search_text_internal(
+    file_path: &str,
+    pattern: &str,
+    regex: bool,
+    case_sensitive: bool,
+    password: Option<&str>,
+) -> Result<Vec<PageTextMatch>, Box<dyn std::error::Error>> {
+    let matcher = search_regex(pattern, regex, case_sensitive)?;
+    if !Path::new(file_path).exists() {
+        return Err(format!("PDF file not found: {}", file_path).into());
+    }
+    let doc = load_document(file_path, password).map_err(|e| match e {
+        lopdf::Error::InvalidPassword => format!("Incorrect password for {}", file_path),
+        e => format!("Failed to parse PDF for text search: {}", e),
+    })?;
+    if doc.is_encrypted() {
+        return Err(format!("PDF is password protected: {}", file_path).into());
+    }
+    Ok(search_document_text(&doc, &matcher))
+}
+
+/// Build the matcher for a search; plain keywords match literally
+fn search_regex(pattern: &str, regex: bool, case_sensitive: bool) -> Result<regex::Regex, String> {
+    let pattern = pattern.trim();
+    if pattern.is_empty() {
+        return Err("Search text is empty".to_string());
+    }
+    let source = if regex {
+        pattern.to_string()
+    } else {
+        // Line breaks in the page text become single spaces
+        pattern
+            .split_whitespace()
+            .map(regex::escape)
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    regex::RegexBuilder::new(&source)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid search pattern: {}", e))
+}
+
+/// Match every page's text, with runs of whitespace (including line breaks)
+/// collapsed to single spaces
+fn search_document_text(doc: &Document, matcher: &regex::Regex) -> Vec<PageTextMatch> {
+    doc.get_pages()
+        .keys()
+        .map(|&number| {
+            let text = doc.extract_text(&[number]).unwrap_or_default();
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            let mut found = matcher.find_iter(&text).filter(|found| !found.is_empty());
+            let excerpt = found
+                .next()
+                .map(|first| match_excerpt(&text, first.start(), first.end()));
+            PageTextMatch {
+                number,
+                matches: excerpt.as_ref().map_or(0, |_| 1 + found.count() as u32),
+                excerpt,
+            }
+        })
+        .collect()
+}
+
+/// The match with up to `EXCERPT_CONTEXT` characters either side
+fn match_excerpt(text: &str, start: usize, end: usize) -> String {
+    let from = text[..start]
+        .char_indices()
+        .rev()
+        .nth(EXCERPT_CONTEXT - 1)
+        .map_or(0, |(index, _)| index);
+    let to = text[end..]
+        .char_indices()
+        .nth(EXCERPT_CONTEXT)
+        .map_or(text.len(), |(index, _)| end + index);
+    format!(
+        "{}{}{}",
+        if from > 0 { "…" } else { "" },
+        &text[from..to],
+        if to < text.len() { "…" } else { "" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A document with `count` empty A4 pages
+    fn blank_document(count: usize) -> Document {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let kids: Vec<Object> = (0..count)
+            .map(|_| {
+                doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+                })
+                .into()
+            })
+            .collect();
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => count as i64,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    /// A document whose page `n` is `100 + n` points wide, so pages can be
+    /// told apart after they are reordered
+    fn numbered_document(count: usize) -> Document {
+        let mut doc = blank_document(count);
+        for (number, page_id) in doc.get_pages() {
+            let width = 100 + number as i64;
+            doc.get_dictionary_mut(page_id)
+                .unwrap()
+                .set("MediaBox", vec![0.into(), 0.into(), width.into(), 842.into()]);
+        }
+        doc
+    }
+
+    /// Numbers of the pages of a file made from `numbered_document`
+    fn page_numbers(path: &str) -> Vec<u32> {
+        page_sizes(path).iter().map(|(width, _)| *width as u32 - 100).collect()
+    }
+
+    /// Scratch file for a test; tests run in parallel, so each needs its own name
+    fn temp_path(name: &str) -> String {
+        let file = format!("pdf_service_test_{}_{}", std::process::id(), name);
+        std::env::temp_dir().join(file).to_string_lossy().to_string()
+    }
+
+    /// Save a document as a scratch file and return its path
+    fn save_temp(mut doc: Document, name: &str) -> String {
+        let path = temp_path(name);
+        save_document(&mut doc, &path).unwrap();
+        path
+    }
+
+    /// Displayed size of each page of a PDF file
+    fn page_sizes(path: &str) -> Vec<(f32, f32)> {
+        let doc = Document::load(path).unwrap();
+        doc.get_pages().values().map(|&page_id| page_geometry(&doc, page_id).size()).collect()
+    }
+
+    /// Matrices that forms are drawn with on a page, in drawing order
+    fn placements(doc: &Document, page_id: ObjectId) -> Vec<[f32; 6]> {
+        Content::decode(&page_content_bytes(doc, page_id))
+            .unwrap()
+            .operations
+            .iter()
+            .filter(|operation| operation.operator == "cm")
+            .map(|operation| {
+                let values: Vec<f32> = operation.operands.iter().map(|value| value.as_float().unwrap()).collect();
+                values.try_into().unwrap()
+            })
+            .collect()
+    }
+
+    /// Labels of an 8 page document: roman front matter, then "1" to "3",
+    /// then an appendix "A-1", "A-2"
+    fn sample_labels() -> Vec<Option<String>> {
+        ["i", "ii", "iii", "1", "2", "3", "A-1", "A-2"]
+            .iter()
+            .map(|label| Some(label.to_string()))
+            .collect()
+    }
+
+    fn select(expression: &str) -> Result<Vec<u32>, String> {
+        parse_page_selection(expression, 8, &sample_labels())
+    }
+
+    #[test]
+    fn selection_numbers_are_physical_pages() {
+        // Page 4 is labelled "1", but numbers never match labels
+        assert_eq!(select("1").unwrap(), vec![1]);
+        assert_eq!(select("4, 2").unwrap(), vec![4, 2]);
+        assert_eq!(select("2-4").unwrap(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn selection_labels() {
+        assert_eq!(select("iii").unwrap(), vec![3]);
+        assert_eq!(select("II").unwrap(), vec![2]);
+        assert_eq!(select("A-2").unwrap(), vec![8]);
+        assert_eq!(select("ii-A-1").unwrap(), vec![2, 3, 4, 5, 6, 7]);
+        assert_eq!(select("A-1-A-2").unwrap(), vec![7, 8]);
+    }
+
+    #[test]
+    fn selection_keywords_and_open_ranges() {
+        assert_eq!(select("last").unwrap(), vec![8]);
+        assert_eq!(select("odd").unwrap(), vec![1, 3, 5, 7]);
+        assert_eq!(select("EVEN").unwrap(), vec![2, 4, 6, 8]);
+        assert_eq!(select("7-").unwrap(), vec![7, 8]);
+        assert_eq!(select("-2").unwrap(), vec![7]);
+        assert_eq!(select("3-1").unwrap(), vec![3, 2, 1]);
+        assert_eq!(select("last-6").unwrap(), vec![8, 7, 6]);
+    }
+
+    #[test]
+    fn selection_negative_range_ends() {
+        // An open start up to the last page, and page 1 up to the last page
+        assert_eq!(select("--1").unwrap(), (1..=8).collect::<Vec<_>>());
+        assert_eq!(select("1--1").unwrap(), (1..=8).collect::<Vec<_>>());
+        assert_eq!(select("1--2").unwrap(), (1..=7).collect::<Vec<_>>());
+        assert_eq!(select("-3--1").unwrap(), vec![6, 7, 8]);
+    }
+
+    #[test]
+    fn selection_errors() {
+        assert!(select("9").unwrap_err().contains("out of range"));
+        assert!(select("0").unwrap_err().contains("start at 1"));
+        assert!(select("-9").unwrap_err().contains("counts back past"));
+        assert!(select("2-9").unwrap_err().contains("out of range"));
+        assert!(select("B-1").unwrap_err().contains("Unknown page"));
+        assert!(select("1,,2").unwrap_err().contains("Empty term"));
+        assert!(select("1,").unwrap_err().contains("Empty term"));
+        assert!(select("  ").unwrap_err().contains("empty"));
+    }
+
+    #[test]
+    fn page_labels_from_number_tree() {
+        let mut doc = blank_document(8);
+        let appendix = doc.add_object(dictionary! { "S" => "D", "P" => Object::string_literal("A-") });
+        let tree = dictionary! {
+            "Kids" => vec![
+                dictionary! { "Nums" => vec![0.into(), dictionary! { "S" => "r" }.into()] }.into(),
+                dictionary! {
+                    "Nums" => vec![3.into(), dictionary! { "S" => "D" }.into(), 6.into(), appendix.into()],
+                }
+                .into(),
+            ],
+        };
+        doc.catalog_mut().unwrap().set("PageLabels", tree);
+
+        assert_eq!(page_labels(&doc), sample_labels());
+    }
+
+    #[test]
+    fn page_labels_absent() {
+        assert_eq!(page_labels(&blank_document(3)), vec![None, None, None]);
+    }
+
+    #[test]
+    fn page_label_numerals() {
+        let doc = blank_document(1);
+        let style = dictionary! { "S" => "R", "St" => 1994 };
+        assert_eq!(format_page_label(&doc, &style, 0), "MCMXCIV");
+        let style = dictionary! { "S" => "a" };
+        assert_eq!(format_page_label(&doc, &style, 0), "a");
+        assert_eq!(format_page_label(&doc, &style, 26), "aa");
+        assert_eq!(format_page_label(&doc, &style, 53), "bbb");
+        // A prefix with no numbering style
+        let style = dictionary! { "P" => Object::string_literal("Cover") };
+        assert_eq!(format_page_label(&doc, &style, 0), "Cover");
+    }
+
+    fn entry(title: &str, pages: Option<(u32, u32)>, children: Vec<OutlineEntry>) -> OutlineEntry {
+        OutlineEntry {
+            title: title.to_string(),
+            page: pages.map(|(first, _)| first),
+            last_page: pages.map(|(_, last)| last),
+            children,
+        }
+    }
+
+    fn sample_outline() -> Vec<OutlineEntry> {
+        vec![
+            entry("1. Introduction", Some((1, 2)), vec![]),
+            entry(
+                "Chapter 3: Sorting",
+                Some((3, 6)),
+                vec![
+                    entry("3.1 Quicksort", Some((4, 5)), vec![]),
+                    entry("3.2 Mergesort", Some((6, 6)), vec![]),
+                ],
+            ),
+            entry("Chapter 30", Some((7, 8)), vec![]),
+            entry("Index", None, vec![]),
+        ]
+    }
+
+    fn sections(titles: &[&str]) -> Result<Vec<u32>, String> {
+        let titles: Vec<String> = titles.iter().map(|title| title.to_string()).collect();
+        outline_pages(&sample_outline(), &titles)
+    }
+
+    #[test]
+    fn outline_exact_and_numbered_titles() {
+        assert_eq!(sections(&["chapter 30"]).unwrap(), vec![7, 8]);
+        assert_eq!(sections(&["  Chapter   30 "]).unwrap(), vec![7, 8]);
+        // Section numbers are ignored, nested entries are found too
+        assert_eq!(sections(&["Introduction"]).unwrap(), vec![1, 2]);
+        assert_eq!(sections(&["quicksort"]).unwrap(), vec![4, 5]);
+    }
+
+    #[test]
+    fn outline_prefix_stops_at_a_word_boundary() {
+        // "Chapter 3" is a prefix of both, but only "Chapter 3: Sorting" ends there
+        assert_eq!(sections(&["Chapter 3"]).unwrap(), vec![3, 4, 5, 6]);
+        assert_eq!(sections(&["Merge"]).unwrap_err(), "No outline entry matches 'Merge'");
+    }
+
+    #[test]
+    fn outline_overlapping_sections_are_listed_once() {
+        assert_eq!(sections(&["Quicksort", "Chapter 3"]).unwrap(), vec![4, 5, 3, 6]);
+    }
+
+    #[test]
+    fn outline_errors() {
+        assert!(sections(&["Chapter 4"]).unwrap_err().contains("No outline entry"));
+        assert!(sections(&["Index"]).unwrap_err().contains("does not point to a page"));
+        assert!(outline_pages(&[], &["Chapter 3".to_string()])
+            .unwrap_err()
+            .contains("no outline"));
+    }
+
+    #[test]
+    fn section_numbers() {
+        assert_eq!(strip_section_number("2.1 Trees"), "Trees");
+        assert_eq!(strip_section_number("2. Trees"), "Trees");
+        assert_eq!(strip_section_number("A Proofs"), "Proofs");
+        assert_eq!(strip_section_number("Chapter 3"), "Chapter 3");
+        assert_eq!(strip_section_number("AB Testing"), "AB Testing");
+    }
+
+    #[test]
+    fn excerpt_of_a_short_page_is_the_whole_text() {
+        let text = "Tutorial 5 answers";
+        assert_eq!(match_excerpt(text, 0, 10), text);
+    }
+
+    #[test]
+    fn excerpt_keeps_context_either_side() {
+        let text = format!("{}KEY{}", "a".repeat(50), "b".repeat(50));
+        assert_eq!(
+            match_excerpt(&text, 50, 53),
+            format!("…{}KEY{}…", "a".repeat(40), "b".repeat(40))
+        );
+        // Exactly the context length before the match: nothing is cut there
+        let text = format!("{}KEY", "a".repeat(40));
+        assert_eq!(match_excerpt(&text, 40, 43), text);
+    }
+
+    #[test]
+    fn excerpt_counts_characters_not_bytes() {
+        let text = format!("{}KEY{}", "é".repeat(50), "ü".repeat(45));
+        let start = "é".len() * 50;
+        assert_eq!(
+            match_excerpt(&text, start, start + 3),
+            format!("…{}KEY{}…", "é".repeat(40), "ü".repeat(40))
+        );
+    }
+
+    #[test]
+    fn keywords_match_literally_with_single_spaces() {
+        // Page text has its whitespace collapsed, so the keyword's is too
+        let matcher = search_regex("tutorial  5", false, false).unwrap();
+        assert!(matcher.is_match("Week 3 Tutorial 5 Questions"));
+        assert!(!matcher.is_match("Tutorial 4"));
+        let matcher = search_regex("C++ (intro)", false, true).unwrap();
+        assert!(matcher.is_match("Notes on C++ (intro)"));
+        assert!(!matcher.is_match("Notes on c++ (intro)"));
+        assert!(search_regex(" ", false, false).unwrap_err().contains("empty"));
+        assert!(search_regex("(", true, false).unwrap_err().contains("Invalid search pattern"));
+    }
+
+    #[test]
+    fn grayscale_carries_colour_space_across_page_streams() {
+        let mut doc = blank_document(1);
+        let page_id = doc.page_iter().next().unwrap();
+        let first = doc.add_object(Stream::new(dictionary! {}, b"/CS0 cs".to_vec()));
+        let second = doc.add_object(Stream::new(dictionary! {}, b"1 0 0 sc 0 0 10 10 re f".to_vec()));
+        let page = doc.get_object_mut(page_id).and_then(Object::as_dict_mut).unwrap();
+        page.set("Contents", vec![first.into(), second.into()]);
+        page.set(
+            "Resources",
+            dictionary! { "ColorSpace" => dictionary! { "CS0" => vec![Object::from("CalRGB"), dictionary! {}.into()] } },
+        );
+
+        let resources = doc.get_dictionary(page_id).and_then(|page| page.get(b"Resources")).unwrap();
+        let spaces = color_spaces(&doc, resources.as_dict().ok());
+        let content = page_content_bytes(&doc, page_id);
+        let gray = grayscale_content(&content, &spaces, ColorKind::Gray, false).unwrap();
+        let operations = Content::decode(&gray).unwrap().operations;
+        assert_eq!(operations[0].operands, vec![Object::Name(b"DeviceGray".to_vec())]);
+        assert_eq!(operations[1].operator, "sc");
+        assert_eq!(operations[1].operands, vec![Object::Real(0.299)]);
+    }
+
+    #[test]
+    fn grayscale_keeps_inline_images() {
+        let image = b"BI /W 2 /H 1 /CS /RGB /BPC 8 ID \xffEI\x00BI\x00\x00 EI";
+        let mut content = b"1 0 0 rg (BI) Tj /BI gs\n".to_vec();
+        content.extend_from_slice(image);
+        content.extend_from_slice(b"\n0 0 1 RG");
+
+        let gray = grayscale_content(&content, &ColorSpaces::new(), ColorKind::Gray, false).unwrap();
+        let start = gray.windows(image.len()).position(|window| window == image).unwrap();
+        let before = Content::decode(&gray[..start]).unwrap().operations;
+        let after = Content::decode(&gray[start + image.len()..]).unwrap().operations;
+        assert_eq!((before[0].operator.as_str(), &before[0].operands), ("g", &vec![Object::Real(0.299)]));
+        assert_eq!(before.len(), 3);
+        assert_eq!((after[0].operator.as_str(), &after[0].operands), ("G", &vec![Object::Real(0.114)]));
+    }
+
+    #[test]
+    fn grayscale_forms_inherit_colour_space() {
+        let spaces = ColorSpaces::new();
+        let gray = grayscale_content(b"0 1 0 sc 0 0 0 1 SC 0.5 sc", &spaces, ColorKind::Inherited, false).unwrap();
+        let levels: Vec<_> = Content::decode(&gray)
+            .unwrap()
+            .operations
+            .iter()
+            .map(|operation| (operation.operator.clone(), operation.operands[0].as_float().unwrap()))
+            .collect();
+        let expected = [("sc", 0.587), ("SC", 0.0), ("sc", 0.5)];
+        assert_eq!(levels, expected.map(|(operator, level)| (operator.to_string(), level)));
+        assert!(grayscale_content(b"0 1 0 sc", &spaces, ColorKind::Gray, false).is_none());
+    }
+
+    #[test]
+    fn stamp_rejects_text_the_font_cannot_print() {
+        let input = save_temp(blank_document(2), "stamp_in.pdf");
+        let output = temp_path("stamp_out.pdf");
+        let (input, output) = (input.as_str(), output.as_str());
+        let stamp = |template: &str, watermark: Option<&str>| StampSettings {
+            template: template.to_string(),
+            position: StampPosition::default(),
+            font_size: 9.0,
+            opacity: 0.3,
+            watermark: watermark.map(str::to_string),
+        };
+
+        let error = stamp_pdf_internal(input, output, &stamp("Ωμέγα {n}/{N}", None)).unwrap_err();
+        assert!(error.to_string().ends_with(": Ωμέγα"), "{}", error);
+        let error = stamp_pdf_internal(input, output, &stamp("", Some("草稿 DRAFT"))).unwrap_err();
+        assert!(error.to_string().ends_with(": 草稿"), "{}", error);
+        // Windows-1252 covers accents and typographic punctuation
+        stamp_pdf_internal(input, output, &stamp("Café – “draft” {n}/{N}", Some("ÉBAUCHE"))).unwrap();
+        assert_eq!(Document::load(output).unwrap().get_pages().len(), 2);
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    fn booklet_order(num_pages: u32, sheets_per_signature: u32) -> Vec<Vec<Option<u32>>> {
+        generate_booklet_layout_internal(num_pages, sheets_per_signature).page_order
+    }
+
+    #[test]
+    fn booklet_page_order() {
+        // Each sheet is [front-left, front-right, back-left, back-right]
+        assert_eq!(
+            booklet_order(8, 0),
+            vec![
+                vec![Some(8), Some(1), Some(2), Some(7)],
+                vec![Some(6), Some(3), Some(4), Some(5)],
+            ]
+        );
+        // Pages past the end of the document are left blank
+        assert_eq!(booklet_order(1, 0), vec![vec![None, Some(1), None, None]]);
+        assert_eq!(
+            booklet_order(5, 0),
+            vec![
+                vec![None, Some(1), Some(2), None],
+                vec![None, Some(3), Some(4), Some(5)],
+            ]
+        );
+    }
+
+    #[test]
+    fn booklet_places_every_page_once() {
+        for num_pages in 1..=40 {
+            let layout = generate_booklet_layout_internal(num_pages, 0);
+            assert_eq!(layout.total_sheets, num_pages.div_ceil(4));
+            assert_eq!(layout.page_order.len() as u32, layout.total_sheets);
+            let mut pages: Vec<u32> = layout.page_order.iter().flatten().flatten().copied().collect();
+            pages.sort_unstable();
+            assert_eq!(pages, (1..=num_pages).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn booklet_sides_hold_two_pages_on_landscape_paper() {
+        let input = save_temp(blank_document(5), "booklet_in.pdf");
+        let output = temp_path("booklet_out.pdf");
+        create_booklet_pdf_internal(&input, &output, None).unwrap();
+
+        // Two sheets, printed on both sides
+        assert_eq!(page_sizes(&output), vec![(842.0, 595.0); 4]);
+        let doc = Document::load(&output).unwrap();
+        let forms: Vec<usize> = doc.page_iter().map(|page_id| placements(&doc, page_id).len()).collect();
+        assert_eq!(forms, vec![1, 1, 1, 2]);
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    fn nup_presets() {
+        assert_eq!(nup_grid(2), Some((2, 1)));
+        assert_eq!(nup_grid(6), Some((3, 2)));
+        assert_eq!(nup_grid(16), Some((4, 4)));
+        assert_eq!(nup_grid(3), None);
+        assert_eq!(nup_grid(0), None);
+    }
+
+    #[test]
+    fn nup_fills_cells_in_reading_order() {
+        let input = save_temp(blank_document(5), "nup4_in.pdf");
+        let output = temp_path("nup4_out.pdf");
+        create_nup_pdf_internal(&input, &output, 4, Orientation::Auto).unwrap();
+
+        // Portrait pages stay on portrait paper, each at half size
+        assert_eq!(page_sizes(&output), vec![(595.0, 842.0); 2]);
+        let doc = Document::load(&output).unwrap();
+        let sheets: Vec<ObjectId> = doc.page_iter().collect();
+        assert_eq!(
+            placements(&doc, sheets[0]),
+            vec![
+                [0.5, 0.0, 0.0, 0.5, 0.0, 421.0],
+                [0.5, 0.0, 0.0, 0.5, 297.5, 421.0],
+                [0.5, 0.0, 0.0, 0.5, 0.0, 0.0],
+                [0.5, 0.0, 0.0, 0.5, 297.5, 0.0],
+            ]
+        );
+        assert_eq!(placements(&doc, sheets[1]), vec![[0.5, 0.0, 0.0, 0.5, 0.0, 421.0]]);
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    fn nup_two_portrait_pages_sit_side_by_side_on_landscape_paper() {
+        let input = save_temp(blank_document(2), "nup2_in.pdf");
+        let output = temp_path("nup2_out.pdf");
+        create_nup_pdf_internal(&input, &output, 2, Orientation::Auto).unwrap();
+
+        assert_eq!(page_sizes(&output), vec![(842.0, 595.0)]);
+        let doc = Document::load(&output).unwrap();
+        let matrices = placements(&doc, doc.page_iter().next().unwrap());
+        assert_eq!(matrices.len(), 2);
+        // Upright, the left page entirely in the left half
+        assert_eq!(matrices[0][1], 0.0);
+        assert!(matrices[0][4] >= 0.0 && matrices[0][4] + 595.0 * matrices[0][0] <= 421.0 + 1e-3);
+        assert!(matrices[1][4] >= 421.0 - 1e-3);
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    fn nup_rejects_unsupported_layouts() {
+        let input = save_temp(blank_document(1), "nup_bad_in.pdf");
+        let output = temp_path("nup_bad_out.pdf");
+        let error = create_nup_pdf_internal(&input, &output, 3, Orientation::Auto).unwrap_err();
+        assert_eq!(error.to_string(), "Unsupported pages per sheet: 3");
+        let error = create_nup_grid_pdf_internal(&input, &output, 0, 2, Orientation::Auto).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid n-up grid: 0x2");
+        assert!(!Path::new(&output).exists());
+
+        let _ = std::fs::remove_file(input);
+    }
+
+    #[test]
+    fn page_range_resolution() {
+        assert_eq!(resolve_page_range(&PageRange::All, 3, &[]).unwrap(), vec![1, 2, 3]);
+        assert_eq!(
+            resolve_page_range(&PageRange::Range { start: 3, end: 5 }, 6, &[]).unwrap(),
+            vec![3, 4, 5]
+        );
+        assert_eq!(
+            resolve_page_range(&PageRange::Selection { pages: vec![5, 1, 3] }, 6, &[]).unwrap(),
+            vec![5, 1, 3]
+        );
+    }
+
+    #[test]
+    fn page_range_errors() {
+        let resolve = |range: PageRange| resolve_page_range(&range, 6, &[]).unwrap_err();
+        assert_eq!(resolve(PageRange::Range { start: 0, end: 2 }), "Invalid page range 0-2");
+        assert_eq!(resolve(PageRange::Range { start: 4, end: 2 }), "Invalid page range 4-2");
+        assert_eq!(
+            resolve(PageRange::Range { start: 5, end: 7 }),
+            "Page range 5-7 exceeds document length (6 pages)"
+        );
+        assert_eq!(
+            resolve(PageRange::Selection { pages: vec![2, 9] }),
+            "Page 9 is out of range (document has 6 pages)"
+        );
+        assert_eq!(resolve(PageRange::Selection { pages: vec![] }), "Page selection is empty");
+    }
+
+    #[test]
+    fn extract_keeps_selected_pages_in_selection_order() {
+        let input = save_temp(numbered_document(6), "extract_in.pdf");
+        let output = temp_path("extract_out.pdf");
+
+        extract_page_range(&input, &output, &PageRange::Range { start: 3, end: 5 }).unwrap();
+        assert_eq!(page_numbers(&output), vec![3, 4, 5]);
+        extract_page_range(&input, &output, &PageRange::Selection { pages: vec![6, 2, 4] }).unwrap();
+        assert_eq!(page_numbers(&output), vec![6, 2, 4]);
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    fn page_formats() {
+        assert_eq!(classify_page_format(595.0, 842.0), PageFormat::A4);
+        assert_eq!(classify_page_format(842.0, 595.0), PageFormat::A4);
+        assert_eq!(classify_page_format(612.0, 792.0), PageFormat::Letter);
+        assert_eq!(classify_page_format(1190.5, 841.9), PageFormat::A3);
+        assert_eq!(classify_page_format(800.0, 600.0), PageFormat::Slide4x3);
+        assert_eq!(classify_page_format(1280.0, 720.0), PageFormat::Slide16x9);
+        // Slides are only recognized in landscape
+        assert_eq!(classify_page_format(720.0, 1280.0), PageFormat::Other);
+        assert_eq!(classify_page_format(500.0, 500.0), PageFormat::Other);
+    }
+
+    #[test]
+    fn info_reports_geometry_of_every_page() {
+        let mut doc = blank_document(3);
+        let pages: Vec<ObjectId> = doc.page_iter().collect();
+        // A Letter page shown through a CropBox and turned a quarter
+        let letter = doc.get_dictionary_mut(pages[1]).unwrap();
+        letter.set("MediaBox", vec![0.into(), 0.into(), 700.into(), 900.into()]);
+        letter.set("CropBox", vec![50.into(), 60.into(), 662.into(), 852.into()]);
+        letter.set("Rotate", 90);
+        doc.get_dictionary_mut(pages[2])
+            .unwrap()
+            .set("MediaBox", vec![0.into(), 0.into(), 1280.into(), 720.into()]);
+        let path = save_temp(doc, "info.pdf");
+
+        let info = get_pdf_info_internal(&path, None).unwrap();
+        assert_eq!(info.num_pages, 3);
+        assert_eq!(info.page_size, (595.0, 842.0));
+        assert!(info.mixed_page_sizes);
+        assert!(info.has_non_a4_pages);
+        assert!(!info.encrypted);
+
+        let letter = &info.pages[1];
+        assert_eq!(letter.number, 2);
+        assert_eq!(letter.media_box, [0.0, 0.0, 700.0, 900.0]);
+        assert_eq!(letter.crop_box, [50.0, 60.0, 662.0, 852.0]);
+        assert_eq!(letter.rotate, 90);
+        assert_eq!((letter.width, letter.height), (792.0, 612.0));
+        assert_eq!(letter.orientation, Orientation::Landscape);
+        assert_eq!(letter.format, PageFormat::Letter);
+
+        let formats: Vec<PageFormat> = info.pages.iter().map(|page| page.format).collect();
+        assert_eq!(formats, vec![PageFormat::A4, PageFormat::Letter, PageFormat::Slide16x9]);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn info_of_uniform_a4_document() {
+        let path = save_temp(blank_document(2), "info_a4.pdf");
+        let info = get_pdf_info_internal(&path, None).unwrap();
+        assert!(!info.mixed_page_sizes);
+        assert!(!info.has_non_a4_pages);
+        assert!(info.pages.iter().all(|page| page.orientation == Orientation::Portrait));
+        let _ = std::fs::remove_file(path);
+    }
+
+    fn fit_options(paper_size: PaperSize, scale_mode: ScaleMode, orientation: Orientation) -> FitOptions {
+        FitOptions {
+            paper_size,
+            scale_mode,
+            orientation,
+            short_edge_flip: false,
+        }
+    }
+
+    #[test]
+    fn placement_scale_modes() {
+        let cell = Rect::new(10.0, 20.0, 200.0, 200.0);
+        let place = |mode| placement_matrix((100.0, 50.0), cell, Turn::None, mode);
+        // Fit: as large as fits, centered vertically
+        assert_eq!(place(ScaleMode::Fit), [2.0, 0.0, 0.0, 2.0, 10.0, 70.0]);
+        // Fill: covers the cell, overflowing it on the sides
+        assert_eq!(place(ScaleMode::Fill), [4.0, 0.0, 0.0, 4.0, -90.0, 20.0]);
+        // Actual size: unscaled, centered
+        assert_eq!(place(ScaleMode::ActualSize), [1.0, 0.0, 0.0, 1.0, 60.0, 95.0]);
+    }
+
+    #[test]
+    fn fit_scales_letter_pages_onto_a4() {
+        let mut doc = blank_document(2);
+        for page_id in doc.page_iter().collect::<Vec<_>>() {
+            doc.get_dictionary_mut(page_id)
+                .unwrap()
+                .set("MediaBox", vec![0.into(), 0.into(), 612.into(), 792.into()]);
+        }
+        let input = save_temp(doc, "fit_letter_in.pdf");
+        let output = temp_path("fit_letter_out.pdf");
+        let options = fit_options(PaperSize::A4, ScaleMode::Fit, Orientation::Auto);
+        fit_to_paper_pdf_internal(&input, &output, &options).unwrap();
+
+        assert_eq!(page_sizes(&output), vec![(595.0, 842.0); 2]);
+        let doc = Document::load(&output).unwrap();
+        let matrix = placements(&doc, doc.page_iter().next().unwrap())[0];
+        let scale = 595.0 / 612.0;
+        assert!((matrix[0] - scale).abs() < 1e-4 && (matrix[3] - scale).abs() < 1e-4);
+        assert!(matrix[4].abs() < 1e-3);
+        assert!((matrix[5] - (842.0 - 792.0 * scale) / 2.0).abs() < 1e-2);
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    fn fit_leaves_matching_pages_untouched() {
+        let input = save_temp(blank_document(2), "fit_a4_in.pdf");
+        let output = temp_path("fit_a4_out.pdf");
+        let options = fit_options(PaperSize::A4, ScaleMode::Fit, Orientation::Auto);
+        fit_to_paper_pdf_internal(&input, &output, &options).unwrap();
+        assert_eq!(std::fs::read(&input).unwrap(), std::fs::read(&output).unwrap());
+
+        // A3 paper needs every page scaled up
+        let options = fit_options(PaperSize::A3, ScaleMode::Fit, Orientation::Auto);
+        fit_to_paper_pdf_internal(&input, &output, &options).unwrap();
+        assert_eq!(page_sizes(&output), vec![(842.0, 1191.0); 2]);
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    fn nup_layout(cols: u32, rows: u32, allow_transpose: bool, orientation: Orientation) -> (f32, f32, u32, u32) {
+        let layout = choose_nup_layout((595.0, 842.0), cols, rows, allow_transpose, orientation);
+        (layout.sheet_width, layout.sheet_height, layout.cols, layout.rows)
+    }
+
+    #[test]
+    fn nup_layout_follows_orientation() {
+        // Auto picks whatever shows portrait pages largest
+        assert_eq!(nup_layout(2, 1, true, Orientation::Auto), (842.0, 595.0, 2, 1));
+        assert_eq!(nup_layout(2, 2, true, Orientation::Auto), (595.0, 842.0, 2, 2));
+        // A fixed orientation keeps the sheet; pages are turned per cell later
+        assert_eq!(nup_layout(2, 1, true, Orientation::Portrait), (595.0, 842.0, 2, 1));
+        assert_eq!(nup_layout(2, 2, true, Orientation::Landscape), (842.0, 595.0, 2, 2));
+        // Transposing the grid wins when it shows pages larger
+        assert_eq!(nup_layout(1, 2, true, Orientation::Landscape), (842.0, 595.0, 2, 1));
+        assert_eq!(nup_layout(1, 2, false, Orientation::Landscape), (842.0, 595.0, 1, 2));
+    }
+
+    /// Fit one page of `width` x `height` onto A4 and return its placement
+    fn fitted_matrices(width: i64, height: i64, orientation: Orientation, short_edge_flip: bool) -> Vec<[f32; 6]> {
+        let mut doc = blank_document(2);
+        for page_id in doc.page_iter().collect::<Vec<_>>() {
+            doc.get_dictionary_mut(page_id)
+                .unwrap()
+                .set("MediaBox", vec![0.into(), 0.into(), width.into(), height.into()]);
+        }
+        let name = format!("orient_{}x{}_{:?}_{}", width, height, orientation, short_edge_flip);
+        let input = save_temp(doc, &format!("{}_in.pdf", name));
+        let output = temp_path(&format!("{}_out.pdf", name));
+        let options = FitOptions {
+            short_edge_flip,
+            ..fit_options(PaperSize::A4, ScaleMode::Fit, orientation)
+        };
+        fit_to_paper_pdf_internal(&input, &output, &options).unwrap();
+
+        // The paper itself always stays portrait
+        assert_eq!(page_sizes(&output), vec![(595.0, 842.0); 2]);
+        let doc = Document::load(&output).unwrap();
+        let matrices = doc.page_iter().map(|page_id| placements(&doc, page_id)[0]).collect();
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+        matrices
+    }
+
+    #[test]
+    fn orientation_turns_content_on_the_paper() {
+        let turned = [0.0, 1.0, -1.0, 0.0, 595.0, 0.0];
+        // Auto turns landscape pages to fill the paper
+        assert_eq!(fitted_matrices(842, 595, Orientation::Auto, false), vec![turned; 2]);
+        // Portrait keeps them upright, at a smaller scale
+        let upright = fitted_matrices(842, 595, Orientation::Portrait, false);
+        assert_eq!(upright[0][1], 0.0);
+        assert!((upright[0][0] - 595.0 / 842.0).abs() < 1e-4);
+        // Landscape turns portrait pages too
+        let forced = fitted_matrices(595, 842, Orientation::Landscape, false);
+        assert!(forced[0][0] == 0.0 && forced[0][1] > 0.0 && forced[0][2] < 0.0);
+    }
+
+    #[test]
+    fn short_edge_flip_turns_back_sides_the_other_way() {
+        assert_eq!(
+            fitted_matrices(842, 595, Orientation::Auto, true),
+            vec![[0.0, 1.0, -1.0, 0.0, 595.0, 0.0], [0.0, -1.0, 1.0, 0.0, 0.0, 842.0]]
+        );
+    }
+
+    #[test]
+    fn exif_matrix_moves_the_stored_top_left_corner() {
+        use image::metadata::Orientation as Exif;
+        // Where the first stored pixel (top-left of the unit square) is shown
+        let corner = |orientation| {
+            let [_, _, c, d, e, f] = exif_matrix(orientation);
+            (c + e, d + f)
+        };
+        assert_eq!(corner(Exif::NoTransforms), (0.0, 1.0));
+        assert_eq!(corner(Exif::Rotate90), (1.0, 1.0));
+        assert_eq!(corner(Exif::Rotate180), (1.0, 0.0));
+        assert_eq!(corner(Exif::Rotate270), (0.0, 0.0));
+        assert_eq!(corner(Exif::FlipHorizontal), (1.0, 1.0));
+        assert_eq!(corner(Exif::FlipVertical), (0.0, 0.0));
+        assert_eq!(corner(Exif::Rotate90FlipH), (0.0, 1.0));
+        assert_eq!(corner(Exif::Rotate270FlipH), (1.0, 0.0));
+        // Every orientation keeps the image inside the unit square
+        for orientation in [Exif::Rotate90, Exif::Rotate270FlipH] {
+            let [a, b, c, d, e, f] = exif_matrix(orientation);
+            for (x, y) in [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)] {
+                let point = (a * x + c * y + e, b * x + d * y + f);
+                assert!((0.0..=1.0).contains(&point.0) && (0.0..=1.0).contains(&point.1));
+            }
+        }
+    }
+
+    #[test]
+    fn images_become_pages_in_their_own_orientation() {
+        let wide = temp_path("wide.png");
+        let tall = temp_path("tall.png");
+        image::RgbImage::new(40, 20).save(&wide).unwrap();
+        image::GrayImage::new(20, 40).save(&tall).unwrap();
+        let output = temp_path("images_out.pdf");
+        let options = ImageToPdfOptions {
+            paper_size: PaperSize::A4,
+            margin_mm: 10.0,
+            orientation: Orientation::Auto,
+        };
+        images_to_pdf_internal(&[wide.clone(), tall.clone()], &output, &options).unwrap();
+
+        assert_eq!(page_sizes(&output), vec![(842.0, 595.0), (595.0, 842.0)]);
+        let doc = Document::load(&output).unwrap();
+        let matrix = placements(&doc, doc.page_iter().next().unwrap())[0];
+        // The wide image spans the page between the margins
+        let margin = 10.0 * 72.0 / 25.4;
+        assert!((matrix[0] - (842.0 - 2.0 * margin)).abs() < 1e-2);
+        assert!((matrix[4] - margin).abs() < 1e-3);
+
+        for path in [wide, tall, output] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn images_reject_other_formats_and_oversized_margins() {
+        let input = save_temp(blank_document(1), "not_an_image.pdf");
+        let output = temp_path("not_an_image_out.pdf");
+        let mut options = ImageToPdfOptions {
+            paper_size: PaperSize::A4,
+            margin_mm: 0.0,
+            orientation: Orientation::Auto,
+        };
+        let error = images_to_pdf_internal(std::slice::from_ref(&input), &output, &options).unwrap_err();
+        assert!(error.to_string().starts_with("Unsupported image format"));
+
+        options.margin_mm = 105.0;
+        let error = images_to_pdf_internal(std::slice::from_ref(&input), &output, &options).unwrap_err();
+        assert_eq!(error.to_string(), "Margin of 105 mm leaves no room on the paper");
+
+        let _ = std::fs::remove_file(input);
+    }
+
+    #[test]
+    fn tabs_expand_to_the_next_stop() {
+        assert_eq!(expand_tabs("\tx", 4), "    x");
+        assert_eq!(expand_tabs("ab\tc", 4), "ab  c");
+        assert_eq!(expand_tabs("abcd\te", 4), "abcd    e");
+        assert_eq!(expand_tabs("a\u{7}b\r", 4), "ab");
+    }
+
+    #[test]
+    fn win_ansi_encoding() {
+        assert_eq!(encode_win_ansi("Café – 5€"), b"Caf\xE9 \x96 5\x80");
+        assert_eq!(encode_win_ansi("✓ ok"), b"? ok");
+        assert_eq!(unencodable_chars("naïve “quotes”"), 0);
+        assert_eq!(unencodable_chars("→ λ 日本"), 4);
+    }
+
+    /// Render `text` as a 10pt listing on A4 without line numbers; a page
+    /// then holds 62 lines of 87 characters
+    fn listing(text: &str, wrap_lines: bool) -> TextListing {
+        let input = temp_path(&format!("listing_{}_{}.txt", text.len(), wrap_lines));
+        let output = temp_path(&format!("listing_{}_{}.pdf", text.len(), wrap_lines));
+        std::fs::write(&input, text).unwrap();
+        let options = TextToPdfOptions {
+            font_size: 10.0,
+            wrap_lines,
+            line_numbers: false,
+            ..Default::default()
+        };
+        let result = text_to_pdf_internal(&input, &output, &options).unwrap();
+        assert_eq!(Document::load(&output).unwrap().get_pages().len() as u32, result.num_pages);
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+        result
+    }
+
+    #[test]
+    fn listing_pages_and_wrapping() {
+        assert_eq!(listing("", true).num_pages, 1);
+        assert_eq!(listing(&"line\n".repeat(62), true).num_pages, 1);
+        assert_eq!(listing(&"line\n".repeat(63), true).num_pages, 2);
+
+        // A line three page widths long takes three lines when wrapped
+        let text = format!("{}{}\n", "line\n".repeat(60), "x".repeat(87 * 3));
+        assert_eq!(listing(&text, true).num_pages, 2);
+        assert_eq!(listing(&text, false).num_pages, 1);
+    }
+
+    #[test]
+    fn listing_counts_replaced_characters() {
+        assert_eq!(listing("plain ASCII\ncafé\n", true).replaced_chars, 0);
+        assert_eq!(listing("a → b\n✓ done\n", true).replaced_chars, 2);
+    }
+
+    #[test]
+    fn listing_rejects_binary_files_and_bad_font_sizes() {
+        let input = temp_path("binary.txt");
+        let output = temp_path("binary.pdf");
+        std::fs::write(&input, b"PK\x03\x04\x00\x00").unwrap();
+        let error = text_to_pdf_internal(&input, &output, &TextToPdfOptions::default()).unwrap_err();
+        assert!(error.to_string().starts_with("File looks binary"));
+
+        std::fs::write(&input, "text").unwrap();
+        let options = TextToPdfOptions {
+            font_size: 30.0,
+            ..Default::default()
+        };
+        let error = text_to_pdf_internal(&input, &output, &options).unwrap_err();
+        assert_eq!(error.to_string(), "Font size 30 is out of range (4-24)");
+
+        let _ = std::fs::remove_file(input);
+    }
+
+    #[test]
+    fn merge_keeps_document_order() {
+        let first = save_temp(numbered_document(3), "merge_first.pdf");
+        let second = save_temp(numbered_document(2), "merge_second.pdf");
+        let output = temp_path("merge_out.pdf");
+        let inputs = vec![second.clone(), first.clone()];
+
+        merge_pdfs_internal(&inputs, &output, false).unwrap();
+        assert_eq!(page_numbers(&output), vec![1, 2, 1, 2, 3]);
+
+        let _ = std::fs::remove_file(first);
+        let _ = std::fs::remove_file(second);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    fn merge_pads_odd_documents_with_a_matching_blank_page() {
+        let first = save_temp(numbered_document(3), "pad_first.pdf");
+        let second = save_temp(numbered_document(2), "pad_second.pdf");
+        let output = temp_path("pad_out.pdf");
+        let inputs = vec![first.clone(), second.clone()];
+
+        merge_pdfs_internal(&inputs, &output, true).unwrap();
+        // The blank page takes the size of the page before it
+        assert_eq!(page_numbers(&output), vec![1, 2, 3, 3, 1, 2]);
+        let doc = Document::load(&output).unwrap();
+        let blank = doc.get_pages()[&4];
+        assert!(page_content_bytes(&doc, blank).is_empty());
+
+        let _ = std::fs::remove_file(first);
+        let _ = std::fs::remove_file(second);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    fn merge_errors() {
+        let output = temp_path("merge_err_out.pdf");
+        assert_eq!(merge_pdfs_internal(&[], &output, false).unwrap_err().to_string(), "No PDFs to merge");
+        let missing = temp_path("merge_missing.pdf");
+        let error = merge_pdfs_internal(std::slice::from_ref(&missing), &output, false).unwrap_err();
+        assert_eq!(error.to_string(), format!("Input PDF not found for merging: {}", missing));
+    }
+
+    /// Strings shown with `Tj` on a page, with the position they start at
+    fn page_texts(doc: &Document, page_id: ObjectId) -> Vec<(String, [f32; 2])> {
+        let operations = Content::decode(&page_content_bytes(doc, page_id)).unwrap().operations;
+        let mut position = [0.0; 2];
+        let mut texts = Vec::new();
+        for operation in operations {
+            match operation.operator.as_str() {
+                "Td" => position = [0, 1].map(|i| operation.operands[i].as_float().unwrap()),
+                "Tj" => {
+                    let bytes = operation.operands[0].as_str().unwrap();
+                    texts.push((bytes.iter().map(|&b| b as char).collect(), position));
+                }
+                _ => {}
+            }
+        }
+        texts
+    }
+
+    #[test]
+    fn stamp_numbers_pages_in_the_chosen_corner() {
+        let input = save_temp(blank_document(3), "stamp_pages_in.pdf");
+        let output = temp_path("stamp_pages_out.pdf");
+        let stamp = StampSettings {
+            template: "Page {n} of {N}".to_string(),
+            position: StampPosition::BottomRight,
+            font_size: 10.0,
+            opacity: 0.3,
+            watermark: None,
+        };
+        stamp_pdf_internal(&input, &output, &stamp).unwrap();
+
+        let doc = Document::load(&output).unwrap();
+        let texts: Vec<_> = doc.page_iter().map(|page_id| page_texts(&doc, page_id)).collect();
+        assert_eq!(texts[1].len(), 1);
+        let (text, [x, y]) = &texts[1][0];
+        assert_eq!(text, "Page 2 of 3");
+        assert_eq!(*y, STAMP_MARGIN);
+        assert!((x + helvetica_width(text, 10.0) - (595.0 - STAMP_MARGIN)).abs() < 1e-3);
+        assert_eq!(texts[2][0].0, "Page 3 of 3");
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    fn stamp_without_text_copies_the_file() {
+        let input = save_temp(blank_document(1), "stamp_empty_in.pdf");
+        let output = temp_path("stamp_empty_out.pdf");
+        let stamp = StampSettings {
+            template: " \n".to_string(),
+            position: StampPosition::default(),
+            font_size: 9.0,
+            opacity: 0.3,
+            watermark: Some(String::new()),
+        };
+        stamp_pdf_internal(&input, &output, &stamp).unwrap();
+        assert_eq!(std::fs::read(&input).unwrap(), std::fs::read(&output).unwrap());
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    /// Save a copy of `doc` encrypted with RC4, requiring `user_password` to open
+    fn save_encrypted(mut doc: Document, user_password: &str, permissions: lopdf::Permissions, name: &str) -> String {
+        doc.trailer.set(
+            "ID",
+            vec![
+                Object::string_literal(b"0123456789abcdef".to_vec()),
+                Object::string_literal(b"0123456789abcdef".to_vec()),
+            ],
+        );
+        let version = lopdf::EncryptionVersion::V2 {
+            document: &doc,
+            owner_password: "owner",
+            user_password,
+            key_length: 128,
+            permissions,
+        };
+        let state = lopdf::EncryptionState::try_from(version).unwrap();
+        doc.encrypt(&state).unwrap();
+        let path = temp_path(name);
+        doc.save(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn permission_flags() {
+        let all = permissions_from_flags(-4);
+        assert!(all.print && all.print_high_quality && all.modify && all.copy);
+        assert!(all.annotate && all.fill_forms && all.assemble);
+
+        // Bit 3 alone allows low quality printing only
+        let print_only = permissions_from_flags(0b100);
+        assert!(print_only.print && !print_only.print_high_quality);
+        assert!(!print_only.copy && !print_only.modify);
+        assert!(!permissions_from_flags(-4 & !0b100).print);
+    }
+
+    #[test]
+    fn password_protected_info_needs_the_password() {
+        let path = save_encrypted(blank_document(2), "secret", lopdf::Permissions::all(), "enc_user.pdf");
+        assert!(may_be_encrypted(&path));
+
+        let locked = get_pdf_info_internal(&path, None).unwrap();
+        assert!(locked.encrypted && locked.password_required);
+        assert_eq!(locked.num_pages, 0);
+        assert!(locked.permissions.unwrap().print);
+
+        let error = get_pdf_info_internal(&path, Some("wrong")).unwrap_err();
+        assert_eq!(error.to_string(), format!("Incorrect password for {}", path));
+
+        let unlocked = get_pdf_info_internal(&path, Some("secret")).unwrap();
+        assert!(unlocked.encrypted && !unlocked.password_required);
+        assert_eq!(unlocked.num_pages, 2);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn owner_password_only_restricts_permissions() {
+        let permissions = lopdf::Permissions::all() - lopdf::Permissions::PRINTABLE;
+        let path = save_encrypted(blank_document(1), "", permissions, "enc_owner.pdf");
+
+        let info = get_pdf_info_internal(&path, None).unwrap();
+        assert!(info.encrypted && !info.password_required);
+        assert_eq!(info.num_pages, 1);
+        let permissions = info.permissions.unwrap();
+        assert!(!permissions.print && permissions.copy);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn decrypt_writes_a_plain_copy() {
+        let path = save_encrypted(blank_document(3), "secret", lopdf::Permissions::all(), "enc_decrypt.pdf");
+        let output = temp_path("enc_decrypt_out.pdf");
+
+        let error = decrypt_pdf_internal(&path, &output, None).unwrap_err();
+        assert_eq!(error.to_string(), format!("PDF is password protected: {}", path));
+
+        decrypt_pdf_internal(&path, &output, Some("secret")).unwrap();
+        assert!(!may_be_encrypted(&output));
+        let info = get_pdf_info_internal(&output, None).unwrap();
+        assert!(!info.encrypted);
+        assert_eq!(info.num_pages, 3);
+
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    fn plain_files_are_not_reported_encrypted() {
+        let path = save_temp(blank_document(1), "plain.pdf");
+        assert!(!may_be_encrypted(&path));
+        let info = get_pdf_info_internal(&path, None).unwrap();
+        assert!(!info.encrypted && info.permissions.is_none());
+        let _ = std::fs::remove_file(path);
+    }
+
+    /// A document with one page per content stream, each with Helvetica as /F1
+    fn document_with_contents(contents: &[&str]) -> Document {
+        let mut doc = blank_document(contents.len());
+        let font_id = standard_font(&mut doc, "Helvetica");
+        let page_ids: Vec<ObjectId> = doc.page_iter().collect();
+        for (page_id, content) in page_ids.into_iter().zip(contents) {
+            let stream_id = doc.add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()));
+            let page = doc.get_dictionary_mut(page_id).unwrap();
+            page.set("Contents", stream_id);
+            page.set("Resources", dictionary! { "Font" => dictionary! { "F1" => font_id } });
+        }
+        doc
+    }
+
+    #[test]
+    fn blank_page_reasons() {
+        let doc = document_with_contents(&[
+            "",
+            "BT /F1 12 Tf 72 720 Td (Chapter 1) Tj ET",
+            "1 g 0 0 595 842 re f",
+            "BT 3 Tr /F1 12 Tf 72 720 Td (hidden) Tj ET",
+            "BT /F1 12 Tf 200 400 Td (This page intentionally left blank) Tj ET",
+            "BT /F1 10 Tf 290 30 Td (- 6 -) Tj ET",
+            "0 0 1 rg 100 100 200 200 re f",
+            "0 0 1 rg 100 100 200 200 re f BT /F1 10 Tf 290 30 Td (8) Tj ET",
+            "   \n ",
+        ]);
+        let reasons: Vec<Option<BlankReason>> = doc
+            .get_pages()
+            .into_iter()
+            .map(|(number, page_id)| blank_reason(&doc, number, page_id))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                Some(BlankReason::Empty),
+                None,
+                Some(BlankReason::Invisible),
+                Some(BlankReason::Invisible),
+                Some(BlankReason::Placeholder),
+                Some(BlankReason::Placeholder),
+                None,
+                // A page number beside a figure is kept
+                None,
+                Some(BlankReason::Empty),
+            ]
+        );
+    }
+
+    #[test]
+    fn placeholder_texts() {
+        assert!(matches!(placeholder_text("This page has been intentionally left blank."), Some(Placeholder::Notice)));
+        assert!(matches!(placeholder_text("Page 3 of 10"), Some(Placeholder::PageNumber)));
+        assert!(placeholder_text("Blank verse: an introduction").is_none());
+        assert!(placeholder_text("Page").is_none());
+    }
+
+    #[test]
+    fn blank_pages_are_detected_and_removed() {
+        let input = save_temp(
+            document_with_contents(&["BT /F1 12 Tf 72 720 Td (One) Tj ET", "", "BT /F1 12 Tf 72 720 Td (Three) Tj ET"]),
+            "blank_in.pdf",
+        );
+        let output = temp_path("blank_out.pdf");
+
+        let blanks = detect_blank_pages_internal(&input, None).unwrap();
+        assert_eq!(blanks.len(), 1);
+        assert_eq!(blanks[0].number, 2);
+        remove_blank_pages_internal(&input, &output).unwrap();
+        assert_eq!(Document::load(&output).unwrap().get_pages().len(), 2);
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    fn gray_levels() {
+        assert_eq!(to_rgb(ColorKind::Gray, &[0.25]), Some([0.25; 3]));
+        assert_eq!(to_rgb(ColorKind::Cmyk, &[1.0, 0.0, 0.0, 0.5]), Some([0.0, 0.5, 0.5]));
+        assert_eq!(to_rgb(ColorKind::Rgb, &[1.0, 0.0]), None);
+
+        assert_eq!(gray_level([1.0, 0.0, 0.0], false), 0.299);
+        assert_eq!(gray_level([1.0, 1.0, 0.0], false), 0.886);
+        assert_eq!(gray_level([0.5, 0.5, 0.5], false), 0.5);
+        // Boosting darkens colours, never neutral grays
+        assert_eq!(gray_level([1.0, 1.0, 0.0], true), 0.443);
+        assert_eq!(gray_level([0.5, 0.5, 0.5], true), 0.5);
+    }
+
+    /// Add an uncompressed 8-bit RGB image named /Im0 to the first page
+    fn add_rgb_image(doc: &mut Document, pixels: &[u8]) {
+        let image_id = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => (pixels.len() / 3) as i64,
+                "Height" => 1,
+                "ColorSpace" => "DeviceRGB",
+                "BitsPerComponent" => 8,
+            },
+            pixels.to_vec(),
+        ));
+        let page_id = doc.page_iter().next().unwrap();
+        let page = doc.get_dictionary_mut(page_id).unwrap();
+        let mut resources = page.get(b"Resources").and_then(Object::as_dict).cloned().unwrap_or_default();
+        resources.set("XObject", dictionary! { "Im0" => image_id });
+        page.set("Resources", resources);
+    }
+
+    #[test]
+    fn grayscale_leaves_no_colour_behind() {
+        let mut doc = document_with_contents(&[
+            "1 0 0 rg 0 0 100 100 re f 0 0 1 RG 0 0 m 100 100 l S q 50 0 0 50 0 0 cm /Im0 Do Q",
+            "0 0.5 0 0.2 k BT /F1 12 Tf 72 720 Td (Green) Tj ET",
+        ]);
+        add_rgb_image(&mut doc, &[255, 0, 0, 0, 0, 255]);
+        let input = save_temp(doc, "gray_in.pdf");
+        let output = temp_path("gray_out.pdf");
+        assert_eq!(detect_color_pages_internal(&input, None).unwrap().color_pages, vec![1, 2]);
+
+        grayscale_pdf_internal(&input, &output, false).unwrap();
+        let analysis = detect_color_pages_internal(&output, None).unwrap();
+        assert_eq!(analysis.num_pages, 2);
+        assert!(analysis.color_pages.is_empty());
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    fn color_pages(doc: &Document) -> Vec<u32> {
+        let mut cache = ColorCache::new();
+        doc.get_pages()
+            .into_iter()
+            .filter(|(_, page_id)| page_uses_color(doc, *page_id, &mut cache))
+            .map(|(number, _)| number)
+            .collect()
+    }
+
+    #[test]
+    fn colour_operators() {
+        let doc = document_with_contents(&[
+            "0.2 g 0 0 100 100 re f BT /F1 12 Tf 72 720 Td (Gray) Tj ET",
+            // Neutral RGB and CMYK values print the same on a mono queue
+            "0.5 0.5 0.5 rg 0 0 0 0.7 K 0 0 100 100 re B",
+            "1 0 0 rg BT /F1 12 Tf 72 720 Td (Red) Tj ET",
+            "0 0 1 RG 0 0 m 100 100 l S",
+            "/DeviceRGB cs 0 0.6 0 sc 0 0 100 100 re f",
+        ]);
+        assert_eq!(color_pages(&doc), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn colour_images() {
+        let mut doc = document_with_contents(&["q 50 0 0 50 0 0 cm /Im0 Do Q"]);
+        add_rgb_image(&mut doc, &[10, 10, 10, 200, 205, 198]);
+        assert!(color_pages(&doc).is_empty(), "a gray photo stored as RGB is not colour");
+
+        add_rgb_image(&mut doc, &[10, 10, 10, 200, 40, 40]);
+        assert_eq!(color_pages(&doc), vec![1]);
+    }
+
+    #[test]
+    fn colour_inside_forms() {
+        let mut doc = document_with_contents(&["/Fm0 Do", "/Fm0 Do", "/Fm0 Do"]);
+        let form = |content: &[u8]| {
+            Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Form",
+                    "BBox" => vec![0.into(), 0.into(), 100.into(), 100.into()],
+                },
+                content.to_vec(),
+            )
+        };
+        let green = doc.add_object(form(b"0 1 0 rg 0 0 10 10 re f"));
+        let gray = doc.add_object(form(b"0.3 g 0 0 10 10 re f"));
+        // The first two pages share one form, so the second uses the cached result
+        let page_ids: Vec<ObjectId> = doc.page_iter().collect();
+        for (page_id, form_id) in page_ids.into_iter().zip([green, green, gray]) {
+            doc.get_dictionary_mut(page_id)
+                .unwrap()
+                .set("Resources", dictionary! { "XObject" => dictionary! { "Fm0" => form_id } });
+        }
+        assert_eq!(color_pages(&doc), vec![1, 2]);
+    }
+
+    /// Seven slides: a build of three frames, a frame that only changes
+    /// colour on the next page, a finished slide and a blank page
+    fn slide_builds() -> Document {
+        const A: &str = "BT /F1 20 Tf 72 700 Td (First point) Tj ET ";
+        const B: &str = "BT /F1 20 Tf 72 660 Td (Second point) Tj ET ";
+        const C: &str = "BT /F1 20 Tf 72 620 Td (Third point) Tj ET ";
+        const D: &str = "BT /F1 20 Tf 72 700 Td (Summary) Tj ET ";
+        let highlighted = format!("1 0 0 rg {}", D);
+        document_with_contents(&[
+            A,
+            &format!("{}{}", A, B),
+            &format!("{}{}{}", A, B, C),
+            D,
+            &highlighted,
+            "BT /F1 20 Tf 72 700 Td (Questions?) Tj ET",
+            "",
+        ])
+    }
+
+    #[test]
+    fn overlay_frames_are_found_in_builds() {
+        let doc = slide_builds();
+        let pages: Vec<(u32, ObjectId)> = doc.get_pages().into_iter().collect();
+        assert_eq!(overlay_pages(&doc, &pages), vec![1, 2, 4]);
+
+        // A frame is only dropped when the next page has the same geometry
+        let mut doc = slide_builds();
+        let page_ids: Vec<ObjectId> = doc.page_iter().collect();
+        doc.get_dictionary_mut(page_ids[1])
+            .unwrap()
+            .set("MediaBox", vec![0.into(), 0.into(), 842.into(), 595.into()]);
+        let pages: Vec<(u32, ObjectId)> = doc.get_pages().into_iter().collect();
+        assert_eq!(overlay_pages(&doc, &pages), vec![4]);
+    }
+
+    #[test]
+    fn overlay_removal_and_kept_page_counts() {
+        let input = save_temp(slide_builds(), "overlays_in.pdf");
+        let output = temp_path("overlays_out.pdf");
+
+        let analysis = detect_overlay_pages_internal(&input, None).unwrap();
+        assert_eq!((analysis.num_pages, analysis.pages_saved), (7, 3));
+        remove_overlay_pages_internal(&input, &output).unwrap();
+        assert_eq!(Document::load(&output).unwrap().get_pages().len(), 4);
+
+        let all: Vec<u32> = (1..=7).collect();
+        assert_eq!(count_kept_pages(&input, None, &all, false, false).unwrap(), 7);
+        assert_eq!(count_kept_pages(&input, None, &all, true, false).unwrap(), 4);
+        assert_eq!(count_kept_pages(&input, None, &all, true, true).unwrap(), 3);
+        // Frames are found among the selected pages only: without page 2,
+        // page 1 is followed by page 3, which still repeats it
+        assert_eq!(count_kept_pages(&input, None, &[1, 3, 6], true, false).unwrap(), 2);
+        assert_eq!(count_kept_pages(&input, None, &[3, 1], true, false).unwrap(), 2);
+        let error = count_kept_pages(&input, None, &[7], false, true).unwrap_err();
+        assert_eq!(error.to_string(), "Every page of the PDF is blank");
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    fn content_bounds_of_painted_marks() {
+        let doc = document_with_contents(&[
+            "100 200 50 60 re f",
+            "q 2 0 0 2 10 10 cm 0 0 10 10 re f Q",
+            // White backgrounds don't count as content
+            "1 g 0 0 595 842 re f 0 g 100 100 10 10 re f",
+            // Marks are clipped
+            "0 0 50 50 re W n 0 0 100 100 re f",
+            "1 g 0 0 595 842 re f",
+        ]);
+        let bounds: Vec<Option<[f32; 4]>> = doc.page_iter().map(|page_id| content_bounds(&doc, page_id)).collect();
+        assert_eq!(
+            bounds,
+            vec![
+                Some([100.0, 200.0, 150.0, 260.0]),
+                Some([10.0, 10.0, 30.0, 30.0]),
+                Some([100.0, 100.0, 110.0, 110.0]),
+                Some([0.0, 0.0, 50.0, 50.0]),
+                None,
+            ]
+        );
+    }
+
+    /// Trim a document whose pages each have one mark, returning the crop boxes
+    fn trimmed_boxes(contents: &[&str], trim: &TrimSettings, name: &str) -> Vec<[f32; 4]> {
+        let input = save_temp(document_with_contents(contents), &format!("{}_in.pdf", name));
+        let output = temp_path(&format!("{}_out.pdf", name));
+        trim_margins_pdf_internal(&input, &output, trim).unwrap();
+        let doc = Document::load(&output).unwrap();
+        let boxes = doc.page_iter().map(|page_id| page_geometry(&doc, page_id).bbox).collect();
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+        boxes
+    }
+
+    #[test]
+    fn trim_shares_one_crop_box_between_pages_of_a_size() {
+        let contents = ["100 100 100 100 re f", "300 400 100 100 re f", ""];
+        let shared = TrimSettings {
+            padding_mm: 0.0,
+            per_page: false,
+        };
+        let union = [100.0, 100.0, 400.0, 500.0];
+        assert_eq!(
+            trimmed_boxes(&contents, &shared, "trim_shared"),
+            vec![union, union, [0.0, 0.0, 595.0, 842.0]]
+        );
+
+        let per_page = TrimSettings {
+            padding_mm: 0.0,
+            per_page: true,
+        };
+        assert_eq!(
+            trimmed_boxes(&contents, &per_page, "trim_per_page")[..2],
+            [[100.0, 100.0, 200.0, 200.0], [300.0, 400.0, 400.0, 500.0]]
+        );
+    }
+
+    #[test]
+    fn trim_padding_stays_on_the_page() {
+        let padding = TrimSettings {
+            padding_mm: 25.4,
+            per_page: true,
+        };
+        // 72pt of padding, cut off at the page edge on the left
+        assert_eq!(
+            trimmed_boxes(&["20 100 100 100 re f"], &padding, "trim_padding"),
+            vec![[0.0, 28.0, 192.0, 272.0]]
+        );
+    }
+
+    /// Make a handout of `count` 16:9 slides on A4
+    fn handout(count: usize, layout: HandoutLayout, name: &str) -> Document {
+        let mut doc = blank_document(count);
+        for page_id in doc.page_iter().collect::<Vec<_>>() {
+            doc.get_dictionary_mut(page_id)
+                .unwrap()
+                .set("MediaBox", vec![0.into(), 0.into(), 1280.into(), 720.into()]);
+        }
+        let input = save_temp(doc, &format!("{}_in.pdf", name));
+        let output = temp_path(&format!("{}_out.pdf", name));
+        let settings = HandoutSettings {
+            layout,
+            frame: true,
+            numbering: true,
+        };
+        create_handout_pdf_internal(&input, &output, &settings, &PaperSize::A4).unwrap();
+        assert!(page_sizes(&output).iter().all(|size| *size == (595.0, 842.0)));
+        let doc = Document::load(&output).unwrap();
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+        doc
+    }
+
+    #[test]
+    fn handout_three_slides_with_notes() {
+        let doc = handout(4, HandoutLayout::ThreeWithNotes, "handout3");
+        let pages: Vec<ObjectId> = doc.page_iter().collect();
+        assert_eq!(pages.len(), 2);
+
+        let slides = placements(&doc, pages[0]);
+        assert_eq!(slides.len(), 3);
+        // Down the left column, top to bottom, leaving the right half for notes
+        assert!(slides.iter().all(|matrix| matrix[4] == HANDOUT_MARGIN));
+        assert!(slides.windows(2).all(|pair| pair[0][5] > pair[1][5]));
+        assert!(slides.iter().all(|matrix| HANDOUT_MARGIN + 1280.0 * matrix[0] < 595.0 / 2.0));
+
+        let labels: Vec<String> = page_texts(&doc, pages[1]).into_iter().map(|(text, _)| text).collect();
+        assert_eq!(labels, vec!["Slide 4"]);
+    }
+
+    #[test]
+    fn handout_two_stacked_slides() {
+        let doc = handout(3, HandoutLayout::TwoStacked, "handout2");
+        let pages: Vec<ObjectId> = doc.page_iter().collect();
+        assert_eq!(pages.len(), 2);
+
+        let slides = placements(&doc, pages[0]);
+        assert_eq!(slides.len(), 2);
+        for matrix in &slides {
+            // Centered across the page, within its top two thirds
+            let width = 1280.0 * matrix[0];
+            assert!((matrix[4] + width / 2.0 - 595.0 / 2.0).abs() < 1e-3);
+            assert!(matrix[5] > HANDOUT_MARGIN + (842.0 - 2.0 * HANDOUT_MARGIN) / 3.0);
+        }
+        assert_eq!(placements(&doc, pages[1]).len(), 1);
+    }
+
+    fn poster_settings(cols: u32, rows: u32, overlap_mm: f32) -> PosterSettings {
+        PosterSettings {
+            cols,
+            rows,
+            overlap_mm,
+            crop_marks: true,
+            labels: true,
+        }
+    }
+
+    #[test]
+    fn poster_tiles_overlap_their_neighbours() {
+        let input = save_temp(blank_document(2), "poster_in.pdf");
+        let output = temp_path("poster_out.pdf");
+        create_poster_pdf_internal(&input, &output, &poster_settings(2, 2, 10.0), &PaperSize::A4).unwrap();
+
+        // A portrait page is enlarged across portrait sheets, four per page
+        assert_eq!(page_sizes(&output), vec![(595.0, 842.0); 8]);
+        let doc = Document::load(&output).unwrap();
+        let sheets: Vec<ObjectId> = doc.page_iter().collect();
+        let matrices: Vec<[f32; 6]> = sheets[..4].iter().map(|&page_id| placements(&doc, page_id)[0]).collect();
+        assert!(matrices.iter().all(|matrix| matrix[0] == matrices[0][0] && matrix[0] > 1.7));
+
+        // Each tile moves on by its width (or height) less the overlap
+        let overlap = 10.0 * 72.0 / 25.4;
+        let tile_width = 595.0 - 2.0 * POSTER_MARGIN;
+        let tile_height = 842.0 - 2.0 * POSTER_MARGIN;
+        assert!((matrices[0][4] - matrices[1][4] - (tile_width - overlap)).abs() < 1e-2);
+        assert!((matrices[2][5] - matrices[0][5] - (tile_height - overlap)).abs() < 1e-2);
+
+        let label = |index: usize| page_texts(&doc, sheets[index])[0].0.clone();
+        assert_eq!(label(1), "page 1, row 1, col 2");
+        assert_eq!(label(6), "page 2, row 2, col 1");
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    fn poster_errors() {
+        let input = save_temp(blank_document(1), "poster_err_in.pdf");
+        let output = temp_path("poster_err_out.pdf");
+        let make = |poster: PosterSettings| {
+            create_poster_pdf_internal(&input, &output, &poster, &PaperSize::A4).unwrap_err().to_string()
+        };
+        assert_eq!(make(poster_settings(0, 2, 10.0)), "Invalid poster grid: 0x2");
+        assert_eq!(make(poster_settings(2, 11, 10.0)), "Invalid poster grid: 2x11");
+        assert_eq!(make(poster_settings(2, 2, 100.0)), "Overlap of 100 mm is too large for the paper");
+        let _ = std::fs::remove_file(input);
+    }
+
+    #[test]
+    fn booklet_signatures() {
+        let layout = generate_booklet_layout_internal(17, 2);
+        assert_eq!(layout.total_sheets, 5);
+        assert_eq!(layout.signature_sheets, vec![2, 2, 1]);
+        // Each signature is folded on its own, from its own first page
+        assert_eq!(
+            layout.page_order,
+            vec![
+                vec![Some(8), Some(1), Some(2), Some(7)],
+                vec![Some(6), Some(3), Some(4), Some(5)],
+                vec![Some(16), Some(9), Some(10), Some(15)],
+                vec![Some(14), Some(11), Some(12), Some(13)],
+                vec![None, Some(17), None, None],
+            ]
+        );
+        // More sheets per signature than the document needs makes one signature
+        assert_eq!(generate_booklet_layout_internal(8, 5).signature_sheets, vec![2]);
+    }
+
+    /// Horizontal placement of each page of an 8 page booklet, side by side
+    fn booklet_offsets(creep_mm: f32, name: &str) -> Vec<Vec<f32>> {
+        let input = save_temp(blank_document(8), &format!("{}_in.pdf", name));
+        let output = temp_path(&format!("{}_out.pdf", name));
+        let settings = BookletSettings {
+            sheets_per_signature: 0,
+            creep_mm,
+        };
+        create_booklet_pdf_internal(&input, &output, Some(&settings)).unwrap();
+        let doc = Document::load(&output).unwrap();
+        let offsets = doc
+            .page_iter()
+            .map(|page_id| placements(&doc, page_id).iter().map(|matrix| matrix[4]).collect())
+            .collect();
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+        offsets
+    }
+
+    #[test]
+    fn booklet_creep_moves_inner_pages_away_from_the_fold() {
+        let plain = booklet_offsets(0.0, "creep_none");
+        let crept = booklet_offsets(2.0, "creep_2mm");
+        let shift = 2.0 * 72.0 / 25.4;
+        // Outer sheet: both sides unchanged
+        assert_eq!(crept[..2], plain[..2]);
+        // Inner sheet: left pages move left, right pages move right
+        for side in 2..4 {
+            assert!((crept[side][0] - (plain[side][0] - shift)).abs() < 1e-3);
+            assert!((crept[side][1] - (plain[side][1] + shift)).abs() < 1e-3);
+        }
+    }
+
+    fn duplex_pass(count: usize, side: SheetSide, reverse: bool) -> Vec<u32> {
+        let name = format!("duplex_{}_{:?}_{}", count, side, reverse);
+        let input = save_temp(numbered_document(count), &format!("{}_in.pdf", name));
+        let output = temp_path(&format!("{}_out.pdf", name));
+        manual_duplex_side_internal(&input, &output, side, reverse).unwrap();
+        let numbers = page_numbers(&output);
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+        numbers
+    }
+
+    #[test]
+    fn manual_duplex_passes() {
+        assert_eq!(duplex_pass(5, SheetSide::Front, false), vec![1, 3, 5]);
+        assert_eq!(duplex_pass(5, SheetSide::Front, true), vec![5, 3, 1]);
+        assert_eq!(duplex_pass(4, SheetSide::Back, false), vec![2, 4]);
+        // An odd count gets a blank back, sized like the last page
+        assert_eq!(duplex_pass(5, SheetSide::Back, false), vec![2, 4, 5]);
+        assert_eq!(duplex_pass(1, SheetSide::Back, false), vec![1]);
+    }
+
+    #[test]
+    fn manual_duplex_blank_back_is_empty() {
+        let input = save_temp(document_with_contents(&["0 0 10 10 re f"; 3]), "duplex_blank_in.pdf");
+        let output = temp_path("duplex_blank_out.pdf");
+        manual_duplex_side_internal(&input, &output, SheetSide::Back, false).unwrap();
+        let doc = Document::load(&output).unwrap();
+        let pages = doc.get_pages();
+        assert_eq!(pages.len(), 2);
+        assert!(blank_reason(&doc, 1, pages[&1]).is_none());
+        assert_eq!(blank_reason(&doc, 2, pages[&2]), Some(BlankReason::Empty));
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    fn arranged(count: usize, copies: u32, collate: bool, reverse: bool, duplex: bool) -> Vec<u32> {
+        let name = format!("copies_{}_{}_{}_{}_{}", count, copies, collate, reverse, duplex);
+        let input = save_temp(numbered_document(count), &format!("{}_in.pdf", name));
+        let output = temp_path(&format!("{}_out.pdf", name));
+        let options = CopyOptions {
+            copies,
+            collate,
+            reverse,
+            duplex,
+        };
+        arrange_copies_internal(&input, &output, &options).unwrap();
+        let numbers = page_numbers(&output);
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+        numbers
+    }
+
+    #[test]
+    fn copies_collated_and_uncollated() {
+        assert_eq!(arranged(3, 2, true, false, false), vec![1, 2, 3, 1, 2, 3]);
+        assert_eq!(arranged(3, 2, false, false, false), vec![1, 1, 2, 2, 3, 3]);
+        assert_eq!(arranged(3, 1, true, true, false), vec![3, 2, 1]);
+        assert_eq!(arranged(2, 0, true, false, false), vec![1, 2]);
+    }
+
+    #[test]
+    fn duplex_copies_keep_sheets_together() {
+        // The odd page gets a blank back (sized like page 3) so copies don't share a sheet
+        assert_eq!(arranged(3, 2, true, false, true), vec![1, 2, 3, 3, 1, 2, 3, 3]);
+        // Uncollated copies repeat each sheet, front and back
+        assert_eq!(arranged(4, 2, false, false, true), vec![1, 2, 1, 2, 3, 4, 3, 4]);
+        // Reversing swaps whole sheets, not pages
+        assert_eq!(arranged(4, 1, true, true, true), vec![3, 4, 1, 2]);
+    }
 }
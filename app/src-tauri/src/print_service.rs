@@ -1,4 +1,5 @@
-use crate::ssh_service::submit_print_job_ssh;
+use crate::pdf_service::SheetSide;
+use crate::ssh_service::{resolve_queue, submit_print_job_ssh};
 use crate::storage_service;
 use crate::types::*;
 use chrono::Utc;
@@ -53,8 +54,107 @@ pub fn print_create_job(
         // Continue anyway, the original file will be used
     }
 
-    let job = PrintJob {
-        id: job_id.clone(),
+    let job = new_job(job_id, name, file_path, printer, settings, Vec::new());
+    ApiResponse::success(insert_job(job))
+}
+
+/// Create a print job that prints several PDFs, in order, as one document
+#[tauri::command]
+pub fn print_create_merged_job(
+    name: String,
+    file_paths: Vec<String>,
+    printer: String,
+    settings: PrintSettings,
+    pad_to_even: bool,
+) -> ApiResponse<PrintJob> {
+    let job_id = Uuid::new_v4().to_string();
+
+    let merged_path = std::env::temp_dir().join(format!("merged_{}.pdf", job_id));
+    let merged_path = merged_path.to_string_lossy().to_string();
+    if let Err(e) = crate::pdf_service::merge_pdfs_internal(&file_paths, &merged_path, pad_to_even) {
+        return ApiResponse::error(format!("Failed to merge PDFs: {}", e));
+    }
+
+    // The merged file only exists in the temp dir, so keep the job pointing at its backup
+    let file_path = match storage_service::backup_pdf_file(&job_id, &merged_path) {
+        Ok(backup_path) => {
+            let _ = std::fs::remove_file(&merged_path);
+            backup_path.to_string_lossy().to_string()
+        }
+        Err(e) => {
+            eprintln!("[Print] Warning: Failed to backup merged PDF: {}", e);
+            merged_path
+        }
+    };
+
+    let job = new_job(job_id, name, file_path, printer, settings, file_paths);
+    ApiResponse::success(insert_job(job))
+}
+
+/// Split a job into two linked jobs: pages that use colour go to
+/// `color_printer`, the rest to `mono_printer`. The page range is applied
+/// first; a side without pages gets no job.
+#[tauri::command]
+pub fn print_create_split_jobs(
+    name: String,
+    file_path: String,
+    color_printer: String,
+    mono_printer: String,
+    settings: PrintSettings,
+    password: Option<String>,
+) -> ApiResponse<Vec<PrintJob>> {
+    let analysis = match crate::pdf_service::detect_color_pages_internal(&file_path, password.as_deref()) {
+        Ok(analysis) => analysis,
+        Err(e) => return ApiResponse::error(format!("Failed to detect colour pages: {}", e)),
+    };
+    let selected = match crate::pdf_service::resolve_file_page_range(&file_path, &settings.page_range, password.as_deref()) {
+        Ok(pages) => pages,
+        Err(e) => return ApiResponse::error(e.to_string()),
+    };
+    let (color_pages, mono_pages): (Vec<u32>, Vec<u32>) = selected
+        .into_iter()
+        .partition(|page| analysis.color_pages.contains(page));
+
+    let parts = [
+        (color_pages, color_printer, "color", ColorMode::Color),
+        (mono_pages, mono_printer, "mono", settings.color_mode),
+    ];
+    let mut jobs: Vec<PrintJob> = parts
+        .into_iter()
+        .filter(|(pages, ..)| !pages.is_empty())
+        .map(|(pages, printer, label, color_mode)| {
+            let job_id = Uuid::new_v4().to_string();
+            if let Err(e) = storage_service::backup_pdf_file(&job_id, &file_path) {
+                eprintln!("[Print] Warning: Failed to backup PDF: {}", e);
+            }
+            let settings = PrintSettings {
+                page_range: PageRange::Selection { pages },
+                color_mode,
+                ..settings.clone()
+            };
+            let job_name = format!("{} ({})", name, label);
+            new_job(job_id, job_name, file_path.clone(), printer, settings, Vec::new())
+        })
+        .collect();
+
+    if let [color_job, mono_job] = jobs.as_mut_slice() {
+        color_job.linked_job_id = Some(mono_job.id.clone());
+        mono_job.linked_job_id = Some(color_job.id.clone());
+    }
+    ApiResponse::success(jobs.into_iter().map(insert_job).collect())
+}
+
+fn new_job(
+    id: String,
+    name: String,
+    file_path: String,
+    printer: String,
+    settings: PrintSettings,
+    source_files: Vec<String>,
+) -> PrintJob {
+    let estimate = estimate_job_for_file(&file_path, &printer, &settings);
+    PrintJob {
+        id,
         name,
         file_path,
         printer,
@@ -64,17 +164,23 @@ pub fn print_create_job(
         updated_at: Utc::now(),
         error: None,
         lpq_job_id: None,
-    };
+        source_files,
+        linked_job_id: None,
+        estimate,
+    }
+}
 
+/// Add a job to the global state and persist history
+fn insert_job(job: PrintJob) -> PrintJob {
     let mut jobs = PRINT_JOBS.lock().unwrap();
-    jobs.insert(job_id.clone(), job.clone());
+    jobs.insert(job.id.clone(), job.clone());
     mark_dirty();
 
     // Try to save immediately (non-blocking)
     drop(jobs);
     let _ = save_if_dirty();
 
-    ApiResponse::success(job)
+    job
 }
 
 /// Get all print jobs
@@ -132,6 +238,8 @@ pub fn print_cancel_job(job_id: String, ssh_config: SSHConfig) -> ApiResponse<St
                 if !result.success {
                     return ApiResponse::error(format!("Failed to cancel job: {:?}", result.error));
                 }
+            } else if matches!(job.status, PrintJobStatus::AwaitingReinsert) {
+                remove_manual_duplex_files(&job_id, &ssh_config);
             }
 
             job.status = PrintJobStatus::Cancelled;
@@ -166,10 +274,14 @@ pub fn print_delete_job(job_id: String) -> ApiResponse<String> {
 
 /// Submit a print job via SSH
 #[tauri::command]
-pub fn print_submit_job(job_id: String, ssh_config: SSHConfig) -> ApiResponse<String> {
+pub fn print_submit_job(
+    job_id: String,
+    ssh_config: SSHConfig,
+    password: Option<String>,
+) -> ApiResponse<String> {
     let mut jobs = PRINT_JOBS.lock().unwrap();
 
-    let (file_path, printer_name, _job_name, settings) = {
+    let (file_path, printer_name, job_name, mut settings) = {
         match jobs.get_mut(&job_id) {
             Some(job) => {
                 job.status = PrintJobStatus::Uploading;
@@ -185,71 +297,56 @@ pub fn print_submit_job(job_id: String, ssh_config: SSHConfig) -> ApiResponse<St
 
     // Verify input file exists
     if !std::path::Path::new(&file_path).exists() {
-        let mut jobs = PRINT_JOBS.lock().unwrap();
-        if let Some(job) = jobs.get_mut(&job_id) {
-            job.status = PrintJobStatus::Failed;
-            job.error = Some(format!("Source PDF file not found: {}", file_path));
-            job.updated_at = Utc::now();
-        }
+        mark_job_failed(&job_id, format!("Source PDF file not found: {}", file_path));
         return ApiResponse::error(format!("PDF file not found: {}", file_path));
     }
 
     eprintln!("[Print] Processing job {} with file: {}", job_id, file_path);
 
-    // Use original file directly - scaling will be done on server
-    let base_file_path = file_path.clone();
+    if let Some(stamp) = settings.stamp.as_mut() {
+        stamp.template = fill_stamp_template(&stamp.template, &ssh_config.username, &job_name);
+        stamp.watermark = stamp
+            .watermark
+            .as_deref()
+            .map(|text| fill_stamp_template(text, &ssh_config.username, &job_name));
+    }
 
-    // Apply n-up layout or booklet if needed
-    let processed_file_path = if settings.pages_per_sheet > 1 {
-        // Use NUS SoC recommended pdfjam for n-up layout
-        let temp_dir = std::env::temp_dir();
-        let output_path = temp_dir.join(format!("nup_{}.pdf", job_id));
-        let output_str = output_path.to_string_lossy().to_string();
+    if settings.color_mode == ColorMode::Auto {
+        settings.color_mode = if printer_supports_color(&printer_name) {
+            ColorMode::Color
+        } else {
+            ColorMode::Grayscale
+        };
+    }
 
-        eprintln!("[Print] Creating {}-up layout: {} -> {}", settings.pages_per_sheet, base_file_path, output_str);
-        match crate::pdf_service::create_nup_pdf_internal(&base_file_path, &output_str, settings.pages_per_sheet) {
-            Ok(_) => {
-                eprintln!("[Print] N-up layout succeeded");
-                output_str
-            }
-            Err(e) => {
-                eprintln!("[Print] N-up layout failed: {}", e);
-                let mut jobs = PRINT_JOBS.lock().unwrap();
-                if let Some(job) = jobs.get_mut(&job_id) {
-                    job.status = PrintJobStatus::Failed;
-                    job.error = Some(format!("PDF n-up layout failed: {}", e));
-                    job.updated_at = Utc::now();
-                }
-                return ApiResponse::error(format!("Failed to create n-up layout: {}", e));
-            }
-        }
-    } else if settings.booklet {
-        // Use booklet layout
-        let temp_dir = std::env::temp_dir();
-        let output_path = temp_dir.join(format!("booklet_{}.pdf", job_id));
-        let output_str = output_path.to_string_lossy().to_string();
+    let mut stages = StageFiles::new(&job_id);
+    let processed = match process_job_pdf(&file_path, &settings, password.as_deref(), &mut stages) {
+        Ok(processed) => processed,
+        Err(e) => return ApiResponse::error(e),
+    };
+    let processed_file_path = processed.path;
 
-        eprintln!("[Print] Creating booklet layout: {} -> {}", base_file_path, output_str);
-        match crate::pdf_service::create_booklet_pdf_internal(&base_file_path, &output_str) {
-            Ok(_) => {
-                eprintln!("[Print] Booklet layout succeeded");
-                output_str
-            }
-            Err(e) => {
-                eprintln!("[Print] Booklet layout failed: {}", e);
-                let mut jobs = PRINT_JOBS.lock().unwrap();
-                if let Some(job) = jobs.get_mut(&job_id) {
-                    job.status = PrintJobStatus::Failed;
-                    job.error = Some(format!("Booklet creation failed: {}", e));
-                    job.updated_at = Utc::now();
-                }
-                return ApiResponse::error(format!("Failed to create booklet: {}", e));
-            }
+    // Replace the creation-time estimate with figures for the processed file:
+    // Auto colour is resolved now, and encrypted files can be read
+    if let (Some(pages), Some(printer)) = (processed.pages, find_printer(&printer_name)) {
+        let estimate = estimate_pages(pages, &settings, &printer);
+        if let Some(job) = PRINT_JOBS.lock().unwrap().get_mut(&job_id) {
+            job.estimate = Some(estimate);
         }
-    } else {
-        eprintln!("[Print] Using original file directly");
-        base_file_path
-    };
+        mark_dirty();
+        let _ = save_if_dirty();
+    }
+
+    if uses_manual_duplex(&settings) {
+        return submit_manual_duplex_fronts(
+            &job_id,
+            &ssh_config,
+            &printer_name,
+            &processed_file_path,
+            &settings,
+            stages,
+        );
+    }
 
     // Generate remote file path using job_id (UUID, always safe)
     let remote_path = format!("/tmp/{}.pdf", job_id);
@@ -258,6 +355,8 @@ pub fn print_submit_job(job_id: String, ssh_config: SSHConfig) -> ApiResponse<St
         processed_file_path,
         remote_path.clone(),
     );
+    // The uploaded copy is all that is needed from here on
+    drop(stages);
 
     let mut jobs = PRINT_JOBS.lock().unwrap();
     let job = jobs.get_mut(&job_id).unwrap();
@@ -302,6 +401,366 @@ pub fn print_submit_job(job_id: String, ssh_config: SSHConfig) -> ApiResponse<St
     }
 }
 
+/// Whether copies and reverse order are laid out in the uploaded file instead
+/// of being left to lpr. Only manual duplex needs this: its two passes must
+/// pair up sheet by sheet, which lpr options cannot express.
+fn arranged_in_file(settings: &PrintSettings) -> bool {
+    uses_manual_duplex(settings) && (settings.copies > 1 || settings.reverse_order)
+}
+
+/// Whether a job is printed duplex in two passes on the simplex queue
+fn uses_manual_duplex(settings: &PrintSettings) -> bool {
+    settings.manual_duplex.is_some() && !matches!(settings.duplex, DuplexMode::Simplex)
+}
+
+/// Remote copy of a manual duplex job's back sides, printed by `print_continue_manual_duplex`
+fn manual_duplex_backs_path(job_id: &str) -> String {
+    format!("/tmp/{}_backs.pdf", job_id)
+}
+
+/// Delete both passes of a manual duplex job from the print host. Best effort:
+/// a failure is logged rather than hiding the job's own outcome.
+fn remove_manual_duplex_files(job_id: &str, ssh_config: &SSHConfig) {
+    let command = format!("rm -f /tmp/{}.pdf {}", job_id, manual_duplex_backs_path(job_id));
+    let result = crate::ssh_service::ssh_execute_command(ssh_config.clone(), command);
+    if !result.success {
+        eprintln!(
+            "[Print] Failed to remove manual duplex files for {}: {:?}",
+            job_id, result.error
+        );
+    }
+}
+
+/// Settings for one pass of manual duplex: simplex, with copies and order
+/// already laid out in the file (see `arranged_in_file`)
+fn manual_duplex_pass_settings(settings: &PrintSettings) -> PrintSettings {
+    PrintSettings {
+        copies: 1,
+        reverse_order: false,
+        duplex: DuplexMode::Simplex,
+        ..settings.clone()
+    }
+}
+
+/// Upload both passes of a manual duplex job, print the fronts and hold the
+/// job until the user has reinserted the stack
+fn submit_manual_duplex_fronts(
+    job_id: &str,
+    ssh_config: &SSHConfig,
+    printer_name: &str,
+    processed_file_path: &str,
+    settings: &PrintSettings,
+    mut stages: StageFiles,
+) -> ApiResponse<String> {
+    let reverse_fronts = settings.manual_duplex.as_ref().is_some_and(|m| m.reverse_fronts);
+    let passes = [
+        ("fronts", SheetSide::Front, reverse_fronts, format!("/tmp/{}.pdf", job_id)),
+        ("backs", SheetSide::Back, false, manual_duplex_backs_path(job_id)),
+    ];
+
+    for (stage, side, reverse, remote_path) in &passes {
+        let local_path = match stages.run(stage, processed_file_path, |input, output| {
+            crate::pdf_service::manual_duplex_side_internal(input, output, *side, *reverse)
+        }) {
+            Ok(path) => path,
+            Err(e) => return ApiResponse::error(e),
+        };
+        let upload_result = crate::ssh_service::ssh_upload_file(ssh_config.clone(), local_path, remote_path.clone());
+        if !upload_result.success {
+            let error_msg = upload_result.error.unwrap_or_else(|| "Unknown error".to_string());
+            mark_job_failed(job_id, error_msg.clone());
+            remove_manual_duplex_files(job_id, ssh_config);
+            return ApiResponse::error(error_msg);
+        }
+    }
+    drop(stages);
+
+    let pass_settings = manual_duplex_pass_settings(settings);
+    match submit_print_job_ssh(ssh_config, printer_name, &passes[0].3, &pass_settings) {
+        Ok(output) => {
+            let mut jobs = PRINT_JOBS.lock().unwrap();
+            if let Some(job) = jobs.get_mut(job_id) {
+                job.status = PrintJobStatus::AwaitingReinsert;
+                job.lpq_job_id = parse_lpr_job_id(&output);
+                job.updated_at = Utc::now();
+            }
+            mark_dirty();
+            drop(jobs);
+            let _ = save_if_dirty();
+            ApiResponse::success(format!(
+                "Front sides submitted: {}. Put the printed stack back in the tray, then continue the job",
+                output
+            ))
+        }
+        Err(e) => {
+            mark_job_failed(job_id, e.to_string());
+            remove_manual_duplex_files(job_id, ssh_config);
+            ApiResponse::error(format!("Failed to submit print job: {}", e))
+        }
+    }
+}
+
+/// Print the back sides of a manual duplex job once its printed fronts are
+/// back in the tray
+#[tauri::command]
+pub fn print_continue_manual_duplex(job_id: String, ssh_config: SSHConfig) -> ApiResponse<String> {
+    let (printer_name, settings) = {
+        let jobs = PRINT_JOBS.lock().unwrap();
+        match jobs.get(&job_id) {
+            Some(job) if matches!(job.status, PrintJobStatus::AwaitingReinsert) => {
+                (job.printer.clone(), job.settings.clone())
+            }
+            Some(_) => return ApiResponse::error("Job is not waiting for its back sides".to_string()),
+            None => return ApiResponse::error("Job not found".to_string()),
+        }
+    };
+
+    let pass_settings = manual_duplex_pass_settings(&settings);
+    match submit_print_job_ssh(&ssh_config, &printer_name, &manual_duplex_backs_path(&job_id), &pass_settings) {
+        Ok(output) => {
+            let mut jobs = PRINT_JOBS.lock().unwrap();
+            if let Some(job) = jobs.get_mut(&job_id) {
+                job.status = PrintJobStatus::Printing;
+                if let Some(lpq_id) = parse_lpr_job_id(&output) {
+                    job.lpq_job_id = Some(lpq_id);
+                }
+                job.updated_at = Utc::now();
+            }
+            mark_dirty();
+            drop(jobs);
+            let _ = save_if_dirty();
+            // lpr has spooled its own copy of the backs
+            remove_manual_duplex_files(&job_id, &ssh_config);
+            ApiResponse::success(format!("Back sides submitted: {}", output))
+        }
+        Err(e) => {
+            mark_job_failed(&job_id, e.to_string());
+            remove_manual_duplex_files(&job_id, &ssh_config);
+            ApiResponse::error(format!("Failed to submit print job: {}", e))
+        }
+    }
+}
+
+/// Output of `process_job_pdf`
+struct ProcessedPdf {
+    /// File to upload
+    path: String,
+    /// Pages of one copy left after selection and blank page and overlay
+    /// removal; None if the count could not be read
+    pages: Option<u32>,
+}
+
+/// Run the local PDF pipeline for a job and return the file to upload.
+/// Encrypted files are decrypted first; then come page selection, removal of
+/// slide overlays and blank pages, grayscale conversion, margin trimming,
+/// handout, poster, n-up or booklet imposition, scaling onto the paper,
+/// stamping and finally, for manual duplex, laying out copies and reverse order.
+fn process_job_pdf(
+    file_path: &str,
+    settings: &PrintSettings,
+    password: Option<&str>,
+    stages: &mut StageFiles,
+) -> Result<ProcessedPdf, String> {
+    let mut current = file_path.to_string();
+
+    // The password is only held for this call, never stored with the job;
+    // the decrypted copy lives in `stages` and is removed with it
+    if crate::pdf_service::may_be_encrypted(&current) {
+        current = stages.run("decrypt", &current, |input, output| {
+            crate::pdf_service::decrypt_pdf_internal(input, output, password)
+        })?;
+    }
+
+    if !matches!(settings.page_range, PageRange::All) {
+        current = stages.run("range", &current, |input, output| {
+            crate::pdf_service::extract_page_range(input, output, &settings.page_range)
+        })?;
+    }
+
+    if settings.remove_overlays {
+        current = stages.run("overlays", &current, |input, output| {
+            crate::pdf_service::remove_overlay_pages_internal(input, output)
+        })?;
+    }
+
+    if settings.remove_blank_pages {
+        current = stages.run("blank", &current, |input, output| {
+            crate::pdf_service::remove_blank_pages_internal(input, output)
+        })?;
+    }
+
+    // Later stages only impose, scale and repeat these pages
+    let pages = lopdf::Document::load_metadata(&current)
+        .map(|metadata| metadata.page_count)
+        .ok();
+
+    if settings.color_mode == ColorMode::Grayscale {
+        current = stages.run("gray", &current, |input, output| {
+            crate::pdf_service::grayscale_pdf_internal(input, output, settings.boost_contrast)
+        })?;
+    }
+
+    if let Some(trim) = &settings.trim {
+        current = stages.run("trim", &current, |input, output| {
+            crate::pdf_service::trim_margins_pdf_internal(input, output, trim)
+        })?;
+    }
+
+    let imposed = settings.handout.is_some()
+        || settings.poster.is_some()
+        || settings.pages_per_sheet > 1
+        || settings.booklet;
+    if let Some(handout) = &settings.handout {
+        current = stages.run("handout", &current, |input, output| {
+            crate::pdf_service::create_handout_pdf_internal(input, output, handout, &settings.paper_size)
+        })?;
+    } else if let Some(poster) = &settings.poster {
+        current = stages.run("poster", &current, |input, output| {
+            crate::pdf_service::create_poster_pdf_internal(input, output, poster, &settings.paper_size)
+        })?;
+    } else if settings.pages_per_sheet > 1 {
+        current = stages.run("nup", &current, |input, output| {
+            crate::pdf_service::create_nup_pdf_internal(
+                input,
+                output,
+                settings.pages_per_sheet,
+                settings.orientation,
+            )
+        })?;
+    } else if settings.booklet {
+        current = stages.run("booklet", &current, |input, output| {
+            crate::pdf_service::create_booklet_pdf_internal(input, output, settings.booklet_settings.as_ref())
+        })?;
+    }
+
+    // Scale onto the paper locally, so the uploaded file is exactly what prints
+    let fit_options = crate::pdf_service::FitOptions {
+        paper_size: settings.paper_size.clone(),
+        scale_mode: settings.scale_mode,
+        // Imposed sheets already have their orientation
+        orientation: if imposed {
+            Orientation::Auto
+        } else {
+            settings.orientation
+        },
+        // Booklet sheets are folded along the short edge
+        short_edge_flip: settings.booklet
+            && settings.pages_per_sheet <= 1
+            && settings.handout.is_none()
+            && settings.poster.is_none(),
+    };
+    current = stages.run("fit", &current, |input, output| {
+        crate::pdf_service::fit_to_paper_pdf_internal(input, output, &fit_options)
+    })?;
+
+    // Stamp after imposition so header/footer text sits on the printed sheet at its set size
+    if let Some(stamp) = &settings.stamp {
+        current = stages.run("stamp", &current, |input, output| {
+            crate::pdf_service::stamp_pdf_internal(input, output, stamp)
+        })?;
+    }
+
+    if arranged_in_file(settings) {
+        let copy_options = crate::pdf_service::CopyOptions {
+            copies: settings.copies,
+            collate: settings.collate,
+            reverse: settings.reverse_order,
+            duplex: !matches!(settings.duplex, DuplexMode::Simplex),
+        };
+        current = stages.run("copies", &current, |input, output| {
+            crate::pdf_service::arrange_copies_internal(input, output, &copy_options)
+        })?;
+    }
+
+    Ok(ProcessedPdf { path: current, pages })
+}
+
+/// Fill the job-level placeholders of a stamp template; `{n}` and `{N}` are
+/// left for the stamping stage
+fn fill_stamp_template(template: &str, username: &str, job_name: &str) -> String {
+    template
+        .replace("{username}", username)
+        .replace("{date}", &chrono::Local::now().format("%Y-%m-%d").to_string())
+        .replace("{name}", job_name)
+}
+
+/// Whether a queue prints in colour; unknown queues are assumed to do
+fn printer_supports_color(queue_name: &str) -> bool {
+    find_printer(queue_name).is_none_or(|printer| printer.supports_color)
+}
+
+fn find_printer(queue_name: &str) -> Option<Printer> {
+    print_get_printers()
+        .data
+        .unwrap_or_default()
+        .into_iter()
+        .find(|printer| printer.queue_name == queue_name)
+}
+
+/// Temp files written by a job's PDF stages. They are removed when this is
+/// dropped, since they may hold the decrypted content of a protected file.
+struct StageFiles {
+    job_id: String,
+    paths: Vec<String>,
+}
+
+impl StageFiles {
+    fn new(job_id: &str) -> Self {
+        StageFiles {
+            job_id: job_id.to_string(),
+            paths: Vec::new(),
+        }
+    }
+
+    /// Run one PDF processing stage into a job-specific temp file.
+    /// On failure the job is marked as failed and the error is returned.
+    fn run<F>(&mut self, stage: &str, input: &str, process: F) -> Result<String, String>
+    where
+        F: FnOnce(&str, &str) -> Result<(), Box<dyn std::error::Error>>,
+    {
+        let output_path = std::env::temp_dir().join(format!("{}_{}.pdf", stage, self.job_id));
+        let output_str = output_path.to_string_lossy().to_string();
+        // Tracked before running, so partial output is removed too
+        self.paths.push(output_str.clone());
+
+        eprintln!("[Print] Stage '{}': {} -> {}", stage, input, output_str);
+        match process(input, &output_str) {
+            Ok(_) => {
+                eprintln!("[Print] Stage '{}' succeeded", stage);
+                Ok(output_str)
+            }
+            Err(e) => {
+                eprintln!("[Print] Stage '{}' failed: {}", stage, e);
+                let error = format!("PDF {} processing failed: {}", stage, e);
+                mark_job_failed(&self.job_id, error.clone());
+                Err(error)
+            }
+        }
+    }
+}
+
+impl Drop for StageFiles {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            if let Err(e) = std::fs::remove_file(path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    eprintln!("[Print] Failed to remove temp file {}: {}", path, e);
+                }
+            }
+        }
+    }
+}
+
+/// Mark a job as failed with an error message
+fn mark_job_failed(job_id: &str, error: String) {
+    let mut jobs = PRINT_JOBS.lock().unwrap();
+    if let Some(job) = jobs.get_mut(job_id) {
+        job.status = PrintJobStatus::Failed;
+        job.error = Some(error);
+        job.updated_at = Utc::now();
+    }
+}
+
 /// Parse the lpq job ID from lpr output
 /// Format: "request id is psts-123 (1 file(s))"
 fn parse_lpr_job_id(output: &str) -> Option<String> {
@@ -375,6 +834,135 @@ pub fn print_get_printers() -> ApiResponse<Vec<Printer>> {
     ApiResponse::success(printers)
 }
 
+// ========== Job Accounting ==========
+
+/// Quota for one mono A4 side; A3 sides count double
+const MONO_SIDE_COST: f64 = 1.0;
+/// Quota for one colour A4 side
+const COLOR_SIDE_COST: f64 = 5.0;
+
+/// Work out the paper and quota a job will use
+#[tauri::command]
+pub fn print_estimate_job(
+    file_path: String,
+    settings: PrintSettings,
+    printer: Printer,
+    password: Option<String>,
+) -> ApiResponse<JobEstimate> {
+    match estimate_file_internal(&file_path, &settings, &printer, password.as_deref()) {
+        Ok(estimate) => ApiResponse::success(estimate),
+        Err(e) => ApiResponse::error(e),
+    }
+}
+
+/// Estimate a job for a PDF on disk. Every copy starts on a new sheet.
+/// Pages are counted the way `process_job_pdf` leaves them: keyword ranges
+/// are resolved against the document text, and pages dropped by blank page
+/// or overlay removal don't count.
+pub fn estimate_file_internal(
+    file_path: &str,
+    settings: &PrintSettings,
+    printer: &Printer,
+    password: Option<&str>,
+) -> Result<JobEstimate, String> {
+    let info = crate::pdf_service::get_pdf_info_internal(file_path, password).map_err(|e| e.to_string())?;
+    if info.password_required {
+        return Err("The PDF is encrypted; supply the password to estimate it".to_string());
+    }
+    let selected = match settings.page_range {
+        PageRange::Keyword { .. } => {
+            crate::pdf_service::resolve_file_page_range(file_path, &settings.page_range, password)
+                .map_err(|e| e.to_string())?
+        }
+        _ => crate::pdf_service::resolve_page_range(&settings.page_range, info.num_pages, &info.outline)?,
+    };
+    let pages = if settings.remove_overlays || settings.remove_blank_pages {
+        crate::pdf_service::count_kept_pages(
+            file_path,
+            password,
+            &selected,
+            settings.remove_overlays,
+            settings.remove_blank_pages,
+        )
+        .map_err(|e| e.to_string())?
+    } else {
+        selected.len() as u32
+    };
+    Ok(estimate_pages(pages, settings, printer))
+}
+
+/// Sheets, sides and quota for `pages` pages of one copy
+fn estimate_pages(pages: u32, settings: &PrintSettings, printer: &Printer) -> JobEstimate {
+    // Output sides of one copy
+    let sides = if let Some(handout) = &settings.handout {
+        pages.div_ceil(handout.layout.slides_per_page())
+    } else if let Some(poster) = &settings.poster {
+        pages * poster.cols * poster.rows
+    } else if settings.pages_per_sheet > 1 {
+        pages.div_ceil(settings.pages_per_sheet)
+    } else if settings.booklet {
+        // Four pages to a sheet, each side holding two
+        2 * pages.div_ceil(4)
+    } else {
+        pages
+    };
+
+    // Manual duplex prints both sides on the simplex queue
+    let manual_duplex = uses_manual_duplex(settings);
+    let queue = if manual_duplex {
+        resolve_queue(&printer.queue_name, &DuplexMode::Simplex)
+    } else {
+        resolve_queue(&printer.queue_name, &settings.duplex)
+    };
+    let duplex = manual_duplex
+        || (printer.supports_duplex
+            && !queue.ends_with("-sx")
+            && !matches!(settings.duplex, DuplexMode::Simplex));
+    // Auto only uses colour where the document does, but is priced as if it
+    // did throughout; the quota is an upper bound then
+    let color = printer.supports_color
+        && match settings.color_mode {
+            ColorMode::Auto | ColorMode::Color => true,
+            ColorMode::Grayscale => false,
+        };
+
+    let copies = settings.copies.max(1);
+    let (front, back) = if duplex {
+        (sides.div_ceil(2), sides / 2)
+    } else {
+        (sides, 0)
+    };
+    let side_cost = if color { COLOR_SIDE_COST } else { MONO_SIDE_COST };
+    let paper_factor = match settings.paper_size {
+        PaperSize::A4 => 1.0,
+        PaperSize::A3 => 2.0,
+    };
+
+    JobEstimate {
+        printed_pages: pages * copies,
+        sheets: front * copies,
+        front_impressions: front * copies,
+        back_impressions: back * copies,
+        queue,
+        duplex,
+        color,
+        quota_cost: ((front + back) * copies) as f64 * side_cost * paper_factor,
+    }
+}
+
+/// Estimate for a job's file on a known queue; None when the file cannot be
+/// read (e.g. it needs a password) or the queue is unknown
+fn estimate_job_for_file(file_path: &str, queue_name: &str, settings: &PrintSettings) -> Option<JobEstimate> {
+    let printer = find_printer(queue_name)?;
+    match estimate_file_internal(file_path, settings, &printer, None) {
+        Ok(estimate) => Some(estimate),
+        Err(e) => {
+            eprintln!("[Print] Could not estimate job: {}", e);
+            None
+        }
+    }
+}
+
 /// Check printer status via SSH
 #[tauri::command]
 pub fn print_check_printer_status(
@@ -466,6 +1054,16 @@ pub fn print_get_backup_path(job_id: String) -> ApiResponse<String> {
     }
 }
 
+/// Where to write the PDF converted from an image or text file, which is
+/// then printed like any other PDF
+#[tauri::command]
+pub fn print_get_converted_path(source_path: String) -> ApiResponse<String> {
+    match storage_service::converted_pdf_path(&source_path) {
+        Ok(path) => ApiResponse::success(path.to_string_lossy().to_string()),
+        Err(e) => ApiResponse::error(e),
+    }
+}
+
 /// Clean up old history entries (default: 30 days)
 #[tauri::command]
 pub fn print_cleanup_history(days: Option<i64>) -> ApiResponse<Vec<String>> {
@@ -490,3 +1088,163 @@ pub fn print_get_storage_info() -> ApiResponse<StorageInfo> {
         Err(e) => ApiResponse::error(format!("Failed to get storage info: {}", e)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One copy, simplex A4 in colour, with no page processing
+    fn settings() -> PrintSettings {
+        PrintSettings {
+            copies: 1,
+            collate: true,
+            reverse_order: false,
+            duplex: DuplexMode::Simplex,
+            orientation: Orientation::Auto,
+            page_range: PageRange::All,
+            pages_per_sheet: 1,
+            booklet: false,
+            booklet_settings: None,
+            paper_size: PaperSize::A4,
+            scale_mode: ScaleMode::Fit,
+            stamp: None,
+            remove_overlays: false,
+            remove_blank_pages: false,
+            handout: None,
+            poster: None,
+            manual_duplex: None,
+            trim: None,
+            color_mode: ColorMode::Color,
+            boost_contrast: false,
+        }
+    }
+
+    /// Mono duplex queue
+    fn mono_printer() -> Printer {
+        find_printer("psts").unwrap()
+    }
+
+    /// Colour duplex queue with A3 paper
+    fn color_printer() -> Printer {
+        find_printer("psc008").unwrap()
+    }
+
+    #[test]
+    fn estimate_simplex_and_duplex() {
+        let simplex = estimate_pages(10, &settings(), &mono_printer());
+        assert_eq!(simplex.queue, "psts-sx");
+        assert!(!simplex.duplex && !simplex.color);
+        assert_eq!((simplex.sheets, simplex.front_impressions, simplex.back_impressions), (10, 10, 0));
+        assert_eq!(simplex.quota_cost, 10.0);
+
+        let duplex_settings = PrintSettings {
+            duplex: DuplexMode::DuplexLongEdge,
+            copies: 3,
+            ..settings()
+        };
+        let duplex = estimate_pages(5, &duplex_settings, &mono_printer());
+        assert_eq!(duplex.queue, "psts");
+        assert!(duplex.duplex);
+        // Each copy starts on a fresh sheet, so the odd page leaves a blank back
+        assert_eq!(duplex.printed_pages, 15);
+        assert_eq!((duplex.sheets, duplex.front_impressions, duplex.back_impressions), (9, 9, 6));
+        assert_eq!(duplex.quota_cost, 15.0);
+    }
+
+    #[test]
+    fn estimate_counts_output_sides_of_each_layout() {
+        let sides = |settings: PrintSettings, pages: u32| estimate_pages(pages, &settings, &mono_printer()).front_impressions;
+        assert_eq!(sides(PrintSettings { pages_per_sheet: 4, ..settings() }, 10), 3);
+        assert_eq!(sides(PrintSettings { booklet: true, ..settings() }, 10), 6);
+        let handout = HandoutSettings {
+            layout: HandoutLayout::ThreeWithNotes,
+            frame: true,
+            numbering: true,
+        };
+        assert_eq!(sides(PrintSettings { handout: Some(handout), ..settings() }, 7), 3);
+        let poster = PosterSettings {
+            cols: 2,
+            rows: 3,
+            overlap_mm: 10.0,
+            crop_marks: true,
+            labels: true,
+        };
+        assert_eq!(sides(PrintSettings { poster: Some(poster), ..settings() }, 2), 12);
+    }
+
+    #[test]
+    fn estimate_colour_and_paper_costs() {
+        let color = estimate_pages(2, &settings(), &color_printer());
+        assert!(color.color);
+        assert_eq!(color.quota_cost, 2.0 * COLOR_SIDE_COST);
+
+        let a3 = PrintSettings {
+            paper_size: PaperSize::A3,
+            ..settings()
+        };
+        assert_eq!(estimate_pages(2, &a3, &color_printer()).quota_cost, 4.0 * COLOR_SIDE_COST);
+
+        let gray = PrintSettings {
+            color_mode: ColorMode::Grayscale,
+            ..settings()
+        };
+        assert_eq!(estimate_pages(2, &gray, &color_printer()).quota_cost, 2.0 * MONO_SIDE_COST);
+
+        // Auto is priced as colour wherever the queue can print it
+        let auto = PrintSettings {
+            color_mode: ColorMode::Auto,
+            ..settings()
+        };
+        assert!(estimate_pages(2, &auto, &color_printer()).color);
+        assert!(!estimate_pages(2, &auto, &mono_printer()).color);
+    }
+
+    #[test]
+    fn estimate_manual_duplex_uses_the_simplex_queue() {
+        let manual = PrintSettings {
+            duplex: DuplexMode::DuplexLongEdge,
+            manual_duplex: Some(ManualDuplexSettings { reverse_fronts: false }),
+            ..settings()
+        };
+        let estimate = estimate_pages(5, &manual, &mono_printer());
+        assert_eq!(estimate.queue, "psts-sx");
+        assert!(estimate.duplex);
+        assert_eq!((estimate.sheets, estimate.back_impressions), (3, 2));
+    }
+
+    #[test]
+    fn stamp_template_fills_job_placeholders() {
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        assert_eq!(
+            fill_stamp_template("{username} · {name} · {date} · {n}/{N}", "jdoe", "Tutorial 5"),
+            format!("jdoe · Tutorial 5 · {} · {{n}}/{{N}}", today)
+        );
+        assert_eq!(fill_stamp_template("no placeholders", "jdoe", "x"), "no placeholders");
+    }
+
+    #[test]
+    fn manual_duplex_arranges_copies_in_the_file() {
+        let manual = PrintSettings {
+            duplex: DuplexMode::DuplexShortEdge,
+            manual_duplex: Some(ManualDuplexSettings { reverse_fronts: true }),
+            ..settings()
+        };
+        assert!(uses_manual_duplex(&manual));
+        assert!(!arranged_in_file(&manual));
+        assert!(arranged_in_file(&PrintSettings { copies: 2, ..manual.clone() }));
+        assert!(arranged_in_file(&PrintSettings { reverse_order: true, ..manual.clone() }));
+
+        // Manual duplex is ignored for simplex jobs
+        let simplex = PrintSettings {
+            duplex: DuplexMode::Simplex,
+            copies: 2,
+            ..manual.clone()
+        };
+        assert!(!uses_manual_duplex(&simplex) && !arranged_in_file(&simplex));
+
+        let pass = manual_duplex_pass_settings(&PrintSettings { copies: 3, reverse_order: true, ..manual });
+        assert_eq!((pass.copies, pass.reverse_order), (1, false));
+        assert!(matches!(pass.duplex, DuplexMode::Simplex));
+        assert!(pass.manual_duplex.is_some());
+    }
+}
@@ -301,19 +301,13 @@ fn test_ssh_connection_internal(config: &SSHConfig) -> Result<String, Box<dyn st
     Ok(output.trim().to_string())
 }
 
-/// Submit a print job via SSH lpr command (uses persistent connection)
-/// Scales PDF on server-side using gs before printing
-pub fn submit_print_job_ssh(
-    _config: &SSHConfig,
-    printer: &str,
-    remote_file_path: &str,
-    settings: &PrintSettings,
-) -> Result<String, Box<dyn std::error::Error>> {
+/// Queue that prints `printer` with the given duplex mode
+pub fn resolve_queue(printer: &str, duplex: &DuplexMode) -> String {
     // NUS SoC Rule: Duplex is controlled by queue name, not lpr options
     // - Duplex (double-sided): use queues without -sx suffix (e.g., psts, pstsb)
     // - Simplex (single-sided): use queues with -sx suffix (e.g., psts-sx, pstsb-sx)
 
-    let actual_printer = match settings.duplex {
+    match duplex {
         DuplexMode::Simplex => {
             // Single-sided: ensure queue has -sx suffix
             if printer.ends_with("-sx") {
@@ -335,54 +329,38 @@ pub fn submit_print_job_ssh(
                 printer.to_string()
             }
         }
-    };
-
-    // Paper size dimensions in points
-    let (width_pts, height_pts) = match settings.paper_size {
-        PaperSize::A4 => (595, 842),
-        PaperSize::A3 => (842, 1191),
-    };
-
-    // Create scaled PDF path
-    let scaled_path = remote_file_path.replace(".pdf", "_scaled.pdf");
-
-    // Step 1: Scale PDF on server using ghostscript (available on NUS servers)
-    // -dPDFFitPage: scale content to fit page
-    // -dFIXEDMEDIA: force output page size
-    let scale_command = format!(
-        "gs -sDEVICE=pdfwrite -dPDFFitPage -dFIXEDMEDIA \
-         -dDEVICEWIDTHPOINTS={} -dDEVICEHEIGHTPOINTS={} \
-         -dCompatibilityLevel=1.4 -dNOPAUSE -dBATCH -dQUIET \
-         -sOutputFile=\"{}\" \"{}\" 2>/dev/null || cp \"{}\" \"{}\"",
-        width_pts, height_pts, scaled_path, remote_file_path, remote_file_path, scaled_path
-    );
-
-    eprintln!("[SSH] Scaling PDF on server: {} -> {}", remote_file_path, scaled_path);
-    let scale_result = execute_with_persistent_session(&scale_command);
-    if let Err(e) = &scale_result {
-        eprintln!("[SSH] PDF scaling warning: {}", e);
-        // Continue with original file if scaling fails
     }
+}
 
-    // Step 2: Build lpr command
-    let print_file = &scaled_path;
+/// Submit a print job via SSH lpr command (uses persistent connection)
+pub fn submit_print_job_ssh(
+    _config: &SSHConfig,
+    printer: &str,
+    remote_file_path: &str,
+    settings: &PrintSettings,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let actual_printer = resolve_queue(printer, &settings.duplex);
+
+    // Build lpr command (the file is already scaled to the paper locally)
     let mut lpr_command = format!("lpr -P {}", actual_printer);
 
     // Add copies (using -# notation as per SoC docs)
     if settings.copies > 1 {
         lpr_command.push_str(&format!(" '-#' {}", settings.copies));
+        if settings.collate {
+            lpr_command.push_str(" -o Collate=True");
+        }
+    }
+
+    if settings.reverse_order {
+        lpr_command.push_str(" -o outputorder=reverse");
     }
 
     // Add the file
-    lpr_command.push_str(&format!(" \"{}\"", print_file));
+    lpr_command.push_str(&format!(" \"{}\"", remote_file_path));
 
     eprintln!("[SSH] Submitting print job: {}", lpr_command);
-    let result = execute_with_persistent_session(&lpr_command);
-
-    // Step 3: Cleanup scaled file
-    let _ = execute_with_persistent_session(&format!("rm -f \"{}\"", scaled_path));
-
-    result
+    execute_with_persistent_session(&lpr_command)
 }
 
 // ========== Persistent Connection Implementation ==========
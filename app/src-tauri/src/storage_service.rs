@@ -24,6 +24,11 @@ pub fn get_backups_dir() -> Option<PathBuf> {
     get_app_data_dir().map(|dir| dir.join("backups"))
 }
 
+/// Get the directory for PDFs converted from images and text files
+pub fn get_converted_dir() -> Option<PathBuf> {
+    get_app_data_dir().map(|dir| dir.join("converted"))
+}
+
 /// Get the history file path
 pub fn get_history_file_path() -> Option<PathBuf> {
     get_history_dir().map(|dir| dir.join(HISTORY_FILE))
@@ -135,6 +140,52 @@ pub fn delete_pdf_backup(job_id: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Path for the PDF converted from `source_path`, named after it
+/// (notes.txt -> notes.txt.pdf) in a folder of its own, so files with the
+/// same name don't overwrite each other
+pub fn converted_pdf_path(source_path: &str) -> Result<PathBuf, String> {
+    let converted_dir = get_converted_dir()
+        .ok_or_else(|| "Failed to get converted files directory path".to_string())?;
+    let file_name = std::path::Path::new(source_path)
+        .file_name()
+        .ok_or_else(|| format!("Not a file path: {}", source_path))?;
+
+    let dir = converted_dir.join(uuid::Uuid::new_v4().to_string());
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create converted files directory: {}", e))?;
+
+    let mut pdf_name = file_name.to_os_string();
+    pdf_name.push(".pdf");
+    Ok(dir.join(pdf_name))
+}
+
+/// Remove converted PDFs older than `days`. Jobs print from their backup
+/// copy, so only unfinished drafts still refer to them.
+pub fn cleanup_converted_files(days: u64) -> Result<usize, String> {
+    let converted_dir = get_converted_dir()
+        .ok_or_else(|| "Failed to get converted files directory path".to_string())?;
+    if !converted_dir.exists() {
+        return Ok(0);
+    }
+    let max_age = std::time::Duration::from_secs(days * 24 * 60 * 60);
+
+    let entries = fs::read_dir(&converted_dir)
+        .map_err(|e| format!("Failed to read converted files directory: {}", e))?;
+    let mut removed = 0;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > max_age);
+        if expired && fs::remove_dir_all(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
 /// Get the backup file path for a job
 pub fn get_backup_file_path(job_id: &str) -> Option<PathBuf> {
     let backups_dir = get_backups_dir()?;
@@ -202,7 +253,11 @@ pub fn cleanup_old_history(jobs: &mut HashMap<String, PrintJob>, days: i64) -> V
         // Keep jobs that are still in progress
         let keep = matches!(
             job.status,
-            PrintJobStatus::Pending | PrintJobStatus::Uploading | PrintJobStatus::Queued | PrintJobStatus::Printing
+            PrintJobStatus::Pending
+                | PrintJobStatus::Uploading
+                | PrintJobStatus::Queued
+                | PrintJobStatus::Printing
+                | PrintJobStatus::AwaitingReinsert
         ) || job.created_at > cutoff;
 
         if !keep {
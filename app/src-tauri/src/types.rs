@@ -31,17 +31,85 @@ pub struct PrintJob {
     pub error: Option<String>,
     /// The job ID assigned by lpq (e.g., "psts-123")
     pub lpq_job_id: Option<String>,
+    /// Files merged into `file_path`, in print order (empty for single-file jobs)
+    #[serde(default)]
+    pub source_files: Vec<String>,
+    /// The other half of a job split into colour and mono pages
+    #[serde(default)]
+    pub linked_job_id: Option<String>,
+    /// Paper and quota the job uses, replaced with the figures for the
+    /// processed file on submit. Auto colour is priced as full colour, so the
+    /// quota is an upper bound for it.
+    #[serde(default)]
+    pub estimate: Option<JobEstimate>,
+}
+
+/// What a job prints: paper, sides and quota
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobEstimate {
+    /// Document pages printed, over all copies
+    pub printed_pages: u32,
+    /// Physical sheets of paper, over all copies
+    pub sheets: u32,
+    /// Sides printed on the front and on the back of the sheets
+    pub front_impressions: u32,
+    pub back_impressions: u32,
+    /// Queue the job goes to once the duplex setting is applied
+    pub queue: String,
+    pub duplex: bool,
+    pub color: bool,
+    /// Quota charged, in units of one single-sided mono A4 side
+    pub quota_cost: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrintSettings {
     pub copies: u32,
+    /// Print copies one complete set at a time rather than each sheet N times
+    #[serde(default = "default_true")]
+    pub collate: bool,
+    /// Print the last sheet first, e.g. for printers that stack face up
+    #[serde(default)]
+    pub reverse_order: bool,
     pub duplex: DuplexMode,
     pub orientation: Orientation,
     pub page_range: PageRange,
     pub pages_per_sheet: u32,
     pub booklet: bool,
+    /// Signatures and creep compensation for booklets (one signature when absent)
+    #[serde(default)]
+    pub booklet_settings: Option<BookletSettings>,
     pub paper_size: PaperSize,
+    /// How pages are scaled onto the paper before upload
+    #[serde(default)]
+    pub scale_mode: ScaleMode,
+    /// Header/footer text and watermark stamped onto every printed page
+    #[serde(default)]
+    pub stamp: Option<StampSettings>,
+    /// Keep only the final frame of slide build sequences (Beamer overlays)
+    #[serde(default)]
+    pub remove_overlays: bool,
+    /// Drop blank pages before imposition
+    #[serde(default)]
+    pub remove_blank_pages: bool,
+    /// Print slides as a handout instead of n-up or booklet
+    #[serde(default)]
+    pub handout: Option<HandoutSettings>,
+    /// Enlarge each page across several sheets instead of n-up or booklet
+    #[serde(default)]
+    pub poster: Option<PosterSettings>,
+    /// Print duplex on a simplex queue in two passes: fronts, then backs after
+    /// the user has reinserted the stack
+    #[serde(default)]
+    pub manual_duplex: Option<ManualDuplexSettings>,
+    /// Crop pages to their content before imposition
+    #[serde(default)]
+    pub trim: Option<TrimSettings>,
+    #[serde(default)]
+    pub color_mode: ColorMode,
+    /// Darken coloured text when converting to grayscale
+    #[serde(default)]
+    pub boost_contrast: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,10 +119,12 @@ pub enum DuplexMode {
     DuplexShortEdge,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Orientation {
     Portrait,
     Landscape,
+    /// Pick per page from its aspect ratio
+    Auto,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -63,6 +133,18 @@ pub enum PageRange {
     All,
     Range { start: u32, end: u32 },
     Selection { pages: Vec<u32> },
+    /// The sections under outline entries (bookmarks) with these titles, e.g.
+    /// "Chapter 3"; a title may also name the start of an entry's title
+    Outline { entries: Vec<String> },
+    /// Pages whose text contains `pattern`, e.g. "Tutorial 5"
+    Keyword {
+        pattern: String,
+        /// Treat `pattern` as a regular expression
+        #[serde(default)]
+        regex: bool,
+        #[serde(default)]
+        case_sensitive: bool,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,12 +153,171 @@ pub enum PaperSize {
     A3,
 }
 
+impl PaperSize {
+    /// Portrait paper dimensions in points (width, height)
+    pub fn dimensions(&self) -> (f64, f64) {
+        match self {
+            PaperSize::A4 => (595.0, 842.0),
+            PaperSize::A3 => (842.0, 1191.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScaleMode {
+    /// Scale to fit entirely on the paper
+    #[default]
+    Fit,
+    /// Scale to cover the whole paper, cropping overflow
+    Fill,
+    /// Keep the original size, centered
+    ActualSize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookletSettings {
+    /// Sheets folded together into each signature; 0 puts every sheet in one
+    #[serde(default)]
+    pub sheets_per_signature: u32,
+    /// Shift of the innermost sheet's pages away from the fold, in mm.
+    /// Sheets further out shift proportionally less; negative values shift
+    /// towards the fold.
+    #[serde(default)]
+    pub creep_mm: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandoutSettings {
+    pub layout: HandoutLayout,
+    /// Thin border around each slide
+    #[serde(default = "default_true")]
+    pub frame: bool,
+    /// "Slide n" under each slide
+    #[serde(default = "default_true")]
+    pub numbering: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HandoutLayout {
+    /// Three slides down the left, ruled note lines on the right
+    ThreeWithNotes,
+    /// Two slides stacked, free space below for notes
+    TwoStacked,
+}
+
+impl HandoutLayout {
+    pub fn slides_per_page(&self) -> u32 {
+        match self {
+            HandoutLayout::ThreeWithNotes => 3,
+            HandoutLayout::TwoStacked => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManualDuplexSettings {
+    /// Print the fronts last page first, for printers that stack output face
+    /// up; the backs always print first page first
+    #[serde(default)]
+    pub reverse_fronts: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PosterSettings {
+    /// Sheets across
+    pub cols: u32,
+    /// Sheets down
+    pub rows: u32,
+    /// Content repeated on neighbouring sheets, for trimming and gluing
+    #[serde(default = "default_poster_overlap")]
+    pub overlap_mm: f32,
+    #[serde(default = "default_true")]
+    pub crop_marks: bool,
+    /// "row 1, col 2" in the margin of each sheet
+    #[serde(default = "default_true")]
+    pub labels: bool,
+}
+
+fn default_poster_overlap() -> f32 {
+    10.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrimSettings {
+    /// Space kept around the content, in mm
+    #[serde(default = "default_trim_padding")]
+    pub padding_mm: f32,
+    /// Crop every page to its own content; by default pages of the same size
+    /// share one crop box so they keep a common scale
+    #[serde(default)]
+    pub per_page: bool,
+}
+
+fn default_trim_padding() -> f32 {
+    5.0
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorMode {
+    /// Grayscale on queues without colour support, unchanged otherwise
+    Auto,
+    /// The file is sent unchanged; mono queues render it in gray themselves
+    #[default]
+    Color,
+    Grayscale,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StampSettings {
+    /// Header/footer text; `{username}`, `{date}`, `{name}`, `{n}` and `{N}` are
+    /// replaced with the SSH user, today's date, the job name, the page number
+    /// and the page count
+    #[serde(default)]
+    pub template: String,
+    #[serde(default)]
+    pub position: StampPosition,
+    #[serde(default = "default_stamp_font_size")]
+    pub font_size: f32,
+    /// Watermark opacity, 0.0 (invisible) to 1.0 (opaque)
+    #[serde(default = "default_stamp_opacity")]
+    pub opacity: f32,
+    /// Large diagonal text across each page, e.g. "DRAFT"
+    #[serde(default)]
+    pub watermark: Option<String>,
+}
+
+fn default_stamp_font_size() -> f32 {
+    9.0
+}
+
+fn default_stamp_opacity() -> f32 {
+    0.3
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StampPosition {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomCenter,
+    BottomRight,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PrintJobStatus {
     Pending,
     Uploading,
     Queued,
     Printing,
+    /// Fronts of a manual duplex job printed; waiting for the stack to be
+    /// reinserted before the backs are sent
+    AwaitingReinsert,
     Completed,
     Failed,
     Cancelled,
@@ -149,8 +390,131 @@ impl<T> ApiResponse<T> {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PDFInfo {
     pub num_pages: u32,
+    /// Displayed size of the first page in points (width, height)
     pub page_size: (f64, f64),
     pub file_size: u64,
+    /// Geometry of every page, in document order
+    pub pages: Vec<PageInfo>,
+    /// True when pages do not all share the same displayed size
+    pub mixed_page_sizes: bool,
+    /// True when any page is not A4 (in either orientation)
+    pub has_non_a4_pages: bool,
+    pub encrypted: bool,
+    /// True when the file cannot be opened without a password; page details
+    /// are empty until it is supplied
+    pub password_required: bool,
+    /// Permissions granted by the encryption dictionary (None when not encrypted)
+    pub permissions: Option<PdfPermissions>,
+    /// Bookmarks, top level first (empty when the document has none)
+    #[serde(default)]
+    pub outline: Vec<OutlineEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutlineEntry {
+    pub title: String,
+    /// 1-based page the entry points to (None when it has no target in this document)
+    pub page: Option<u32>,
+    /// Last page of the entry's section: the page before the next entry at the
+    /// same or a higher level, or the last page of the document
+    pub last_page: Option<u32>,
+    pub children: Vec<OutlineEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlankPage {
+    /// 1-based page number
+    pub number: u32,
+    pub reason: BlankReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlankReason {
+    /// No content at all
+    Empty,
+    /// Content that draws nothing visible, e.g. only white fills
+    Invisible,
+    /// Only placeholder text such as "This page intentionally left blank" or a page number
+    Placeholder,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageTextMatch {
+    /// 1-based page number
+    pub number: u32,
+    /// Occurrences on the page; 0 when it does not match
+    pub matches: u32,
+    /// Text around the first occurrence
+    pub excerpt: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverlayAnalysis {
+    pub num_pages: u32,
+    /// 1-based numbers of build frames whose content the next page repeats
+    pub overlay_pages: Vec<u32>,
+    /// Pages no longer printed when the overlays are removed
+    pub pages_saved: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextListing {
+    pub num_pages: u32,
+    /// Characters the listing font has no glyph for, printed as '?'
+    pub replaced_chars: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColorAnalysis {
+    pub num_pages: u32,
+    /// 1-based numbers of the pages that use colour
+    pub color_pages: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfPermissions {
+    pub print: bool,
+    pub print_high_quality: bool,
+    pub modify: bool,
+    pub copy: bool,
+    pub annotate: bool,
+    pub fill_forms: bool,
+    pub assemble: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageInfo {
+    /// 1-based page number
+    pub number: u32,
+    /// MediaBox as [llx, lly, urx, ury] in points
+    pub media_box: [f64; 4],
+    /// CropBox clipped to the MediaBox (equal to it when absent)
+    pub crop_box: [f64; 4],
+    /// Clockwise /Rotate value: 0, 90, 180 or 270
+    pub rotate: u32,
+    /// Displayed width and height in points, after CropBox and /Rotate
+    pub width: f64,
+    pub height: f64,
+    pub orientation: Orientation,
+    pub format: PageFormat,
+    /// Page label shown by PDF viewers, e.g. "iv" or "A-3" (None when the
+    /// document defines no labels)
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// Paper or slide format a page size was recognized as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PageFormat {
+    A3,
+    A4,
+    A5,
+    Letter,
+    Legal,
+    Slide4x3,
+    Slide16x9,
+    Slide16x10,
+    Other,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -158,6 +522,9 @@ pub struct BookletLayout {
     pub total_sheets: u32,
     pub pages_per_sheet: u32,
     pub page_order: Vec<Vec<Option<u32>>>,
+    /// Sheets in each signature, in print order; `page_order` lists the
+    /// sheets of every signature one after another
+    pub signature_sheets: Vec<u32>,
 }
 
 // ========== Storage Info ==========
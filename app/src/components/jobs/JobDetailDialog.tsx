@@ -21,6 +21,7 @@ import {
   Layers,
   FileStack,
   Maximize2,
+  FlipHorizontal,
 } from 'lucide-react'
 import type { PrintJob, PrintJobStatus } from '@/types/printer'
 import 'react-pdf/dist/Page/AnnotationLayer.css'
@@ -32,6 +33,8 @@ interface JobDetailDialogProps {
   job: PrintJob | null
   open: boolean
   onOpenChange: (open: boolean) => void
+  /** Print the back sides of a manual duplex job waiting for its stack to be reinserted */
+  onContinueManualDuplex?: (jobId: string) => void
 }
 
 const statusConfig: Record<
@@ -42,12 +45,13 @@ const statusConfig: Record<
   Uploading: { color: 'bg-accent', label: 'Uploading' },
   Queued: { color: 'bg-warning text-warning-foreground', label: 'Queued' },
   Printing: { color: 'bg-primary', label: 'Printing' },
+  AwaitingReinsert: { color: 'bg-warning text-warning-foreground', label: 'Reinsert Paper' },
   Completed: { color: 'bg-success', label: 'Completed' },
   Failed: { color: 'bg-destructive', label: 'Failed' },
   Cancelled: { color: 'bg-muted-foreground', label: 'Cancelled' },
 }
 
-export function JobDetailDialog({ job, open, onOpenChange }: JobDetailDialogProps) {
+export function JobDetailDialog({ job, open, onOpenChange, onContinueManualDuplex }: JobDetailDialogProps) {
   const [pdfUrl, setPdfUrl] = useState<string | null>(null)
   const [loading, setLoading] = useState(false)
   const [error, setError] = useState<string | null>(null)
@@ -241,6 +245,19 @@ export function JobDetailDialog({ job, open, onOpenChange }: JobDetailDialogProp
                 />
               </div>
 
+              {/* Manual duplex: fronts are printed, waiting for the stack */}
+              {job.status === 'AwaitingReinsert' && onContinueManualDuplex && (
+                <div className="mt-4 p-3 bg-warning/10 border border-warning/20 rounded-lg space-y-3">
+                  <p className="text-xs text-muted-foreground">
+                    Front sides are printed. Put the stack back in the tray, then print the back sides.
+                  </p>
+                  <Button size="sm" className="w-full" onClick={() => onContinueManualDuplex(job.id)}>
+                    <FlipHorizontal className="w-4 h-4 mr-2" />
+                    Print Back Sides
+                  </Button>
+                </div>
+              )}
+
               {/* Error message if any */}
               {job.error && (
                 <div className="mt-4 p-3 bg-destructive/10 border border-destructive/20 rounded-lg">
@@ -307,6 +324,10 @@ function formatPageRange(pageRange: PrintJob['settings']['page_range']): string
         return pages.join(', ')
       }
       return `${pages.slice(0, 2).join(', ')}... (${pages.length})`
+    case 'Outline':
+      return pageRange.entries.join(', ')
+    case 'Keyword':
+      return `Pages containing "${pageRange.pattern}"`
     default:
       return 'All'
   }
@@ -0,0 +1,81 @@
+import { getConvertedPath, imagesToPDF, textToPDF } from './printer-api'
+import type { PaperSize } from '@/types/printer'
+
+/** Plain text and source code, printed as a listing with line numbers */
+export const TEXT_EXTENSIONS = [
+  'txt', 'md', 'log', 'csv',
+  'c', 'h', 'cpp', 'hpp', 'cc', 'java', 'py', 'js', 'ts', 'rs', 'go', 'sh', 'sql',
+]
+
+/** Photos and scans, one per page */
+export const IMAGE_EXTENSIONS = ['png', 'jpg', 'jpeg']
+
+/** Everything the app can print: PDFs as they are, other files once converted */
+export const PRINTABLE_EXTENSIONS = ['pdf', ...TEXT_EXTENSIONS, ...IMAGE_EXTENSIONS]
+
+function extension(path: string): string {
+  const name = path.split(/[\\/]/).pop() || ''
+  const dot = name.lastIndexOf('.')
+  return dot > 0 ? name.slice(dot + 1).toLowerCase() : ''
+}
+
+export function isPrintableFile(path: string): boolean {
+  return PRINTABLE_EXTENSIONS.includes(extension(path))
+}
+
+export function isImageFile(path: string): boolean {
+  return IMAGE_EXTENSIONS.includes(extension(path))
+}
+
+export interface ConvertedFile {
+  /** PDF to preview and print */
+  path: string
+  /** Problems worth telling the user about, e.g. characters the font lacks */
+  warning?: string
+}
+
+/**
+ * Turn printable files into one PDF: a PDF is returned unchanged, several
+ * images become one page each. Throws with the backend's message when
+ * conversion fails.
+ */
+export async function convertToPDF(paths: string[], paperSize: PaperSize = 'A4'): Promise<ConvertedFile> {
+  const [path] = paths
+  if (!path) {
+    throw new Error('No file to convert')
+  }
+  const images = paths.every(isImageFile)
+  if (paths.length > 1 && !images) {
+    throw new Error('Only images can be combined into one document')
+  }
+  if (!images && !TEXT_EXTENSIONS.includes(extension(path))) {
+    return { path }
+  }
+
+  const output = await getConvertedPath(path)
+  if (!output.success || !output.data) {
+    throw new Error(output.error || 'Failed to prepare the converted file')
+  }
+
+  if (images) {
+    // Orientation follows each image, after its EXIF rotation
+    const result = await imagesToPDF(paths, output.data, paperSize)
+    if (!result.success) {
+      throw new Error(result.error || 'Failed to convert the images')
+    }
+    return { path: output.data }
+  }
+
+  const listing = await textToPDF(path, output.data, paperSize)
+  if (!listing.success || !listing.data) {
+    throw new Error(listing.error || 'Failed to convert the text file')
+  }
+
+  const replaced = listing.data.replaced_chars
+  return {
+    path: output.data,
+    warning: replaced > 0
+      ? `${replaced} character${replaced === 1 ? '' : 's'} the listing font cannot show will print as "?"`
+      : undefined,
+  }
+}
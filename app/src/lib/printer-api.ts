@@ -6,9 +6,24 @@ import type {
   Printer,
   PDFInfo,
   BookletLayout,
+  BookletSettings,
+  JobEstimate,
   ApiResponse,
   PrintJobStatus,
   StorageInfo,
+  PaperSize,
+  ScaleMode,
+  Orientation,
+  StampSettings,
+  BlankPage,
+  ColorAnalysis,
+  OverlayAnalysis,
+  TrimSettings,
+  HandoutSettings,
+  PosterSettings,
+  PageTextMatch,
+  PageRange,
+  TextListing,
 } from '@/types/printer'
 
 // ========== SSH Operations ==========
@@ -58,32 +73,250 @@ export async function checkPrinterQueue(
 }
 
 // ========== PDF Operations ==========
-export async function getPDFInfo(filePath: string): Promise<ApiResponse<PDFInfo>> {
-  return await safeInvoke('pdf_get_info', { filePath })
+export async function getPDFInfo(
+  filePath: string,
+  password?: string
+): Promise<ApiResponse<PDFInfo>> {
+  return await safeInvoke('pdf_get_info', { filePath, password })
+}
+
+export async function detectBlankPages(
+  filePath: string,
+  password?: string
+): Promise<ApiResponse<BlankPage[]>> {
+  return await safeInvoke('pdf_detect_blank_pages', { filePath, password })
+}
+
+export async function detectOverlays(
+  filePath: string,
+  password?: string
+): Promise<ApiResponse<OverlayAnalysis>> {
+  return await safeInvoke('pdf_detect_overlays', { filePath, password })
+}
+
+export async function detectColorPages(
+  filePath: string,
+  password?: string
+): Promise<ApiResponse<ColorAnalysis>> {
+  return await safeInvoke('pdf_detect_color_pages', { filePath, password })
 }
 
 export async function generateBookletLayout(
-  numPages: number
+  numPages: number,
+  sheetsPerSignature?: number
 ): Promise<ApiResponse<BookletLayout>> {
-  return await safeInvoke('pdf_generate_booklet_layout', { numPages })
+  return await safeInvoke('pdf_generate_booklet_layout', { numPages, sheetsPerSignature })
 }
 
 export async function createBookletPDF(
   inputPath: string,
-  outputPath: string
+  outputPath: string,
+  settings?: BookletSettings
 ): Promise<ApiResponse<string>> {
-  return await safeInvoke('pdf_create_booklet', { inputPath, outputPath })
+  return await safeInvoke('pdf_create_booklet', { inputPath, outputPath, settings })
 }
 
 export async function createNupPDF(
   inputPath: string,
   outputPath: string,
-  pagesPerSheet: number
+  pagesPerSheet: number,
+  orientation?: Orientation
 ): Promise<ApiResponse<string>> {
   return await safeInvoke('pdf_create_nup', {
     inputPath,
     outputPath,
     pagesPerSheet,
+    orientation,
+  })
+}
+
+export async function createNupGridPDF(
+  inputPath: string,
+  outputPath: string,
+  cols: number,
+  rows: number,
+  orientation?: Orientation
+): Promise<ApiResponse<string>> {
+  return await safeInvoke('pdf_create_nup_grid', {
+    inputPath,
+    outputPath,
+    cols,
+    rows,
+    orientation,
+  })
+}
+
+export async function fitPDFToPaper(
+  inputPath: string,
+  outputPath: string,
+  paperSize: PaperSize,
+  scaleMode: ScaleMode,
+  orientation?: Orientation
+): Promise<ApiResponse<string>> {
+  return await safeInvoke('pdf_fit_to_paper', {
+    inputPath,
+    outputPath,
+    paperSize,
+    scaleMode,
+    orientation,
+  })
+}
+
+export async function imagesToPDF(
+  imagePaths: string[],
+  outputPath: string,
+  paperSize: PaperSize,
+  marginMm?: number,
+  orientation?: Orientation
+): Promise<ApiResponse<string>> {
+  return await safeInvoke('pdf_images_to_pdf', {
+    imagePaths,
+    outputPath,
+    paperSize,
+    marginMm,
+    orientation,
+  })
+}
+
+export async function textToPDF(
+  inputPath: string,
+  outputPath: string,
+  paperSize: PaperSize,
+  options?: {
+    fontSize?: number
+    tabWidth?: number
+    wrapLines?: boolean
+    lineNumbers?: boolean
+  }
+): Promise<ApiResponse<TextListing>> {
+  return await safeInvoke('pdf_text_to_pdf', {
+    inputPath,
+    outputPath,
+    paperSize,
+    ...options,
+  })
+}
+
+export async function arrangePDFCopies(
+  inputPath: string,
+  outputPath: string,
+  copies: number,
+  collate: boolean,
+  reverse: boolean,
+  duplex: boolean
+): Promise<ApiResponse<string>> {
+  return await safeInvoke('pdf_arrange_copies', {
+    inputPath,
+    outputPath,
+    copies,
+    collate,
+    reverse,
+    duplex,
+  })
+}
+
+/** Parse a page selection such as "1-3,7,10-", "odd", "-1" or "iv-x" into page numbers */
+export async function parsePageSelection(
+  filePath: string,
+  expression: string,
+  password?: string
+): Promise<ApiResponse<number[]>> {
+  return await safeInvoke('pdf_parse_page_selection', { filePath, expression, password })
+}
+
+/** Pages a page range selects, resolved exactly as when the job is printed */
+export async function resolvePageRange(
+  filePath: string,
+  pageRange: PageRange,
+  password?: string
+): Promise<ApiResponse<number[]>> {
+  return await safeInvoke('pdf_resolve_page_range', { filePath, pageRange, password })
+}
+
+/** Search each page's text for a keyword, or a regular expression when `regex` is set */
+export async function searchPDFText(
+  filePath: string,
+  pattern: string,
+  regex = false,
+  caseSensitive = false,
+  password?: string
+): Promise<ApiResponse<PageTextMatch[]>> {
+  return await safeInvoke('pdf_search_text', { filePath, pattern, regex, caseSensitive, password })
+}
+
+export async function decryptPDF(
+  inputPath: string,
+  outputPath: string,
+  password?: string
+): Promise<ApiResponse<string>> {
+  return await safeInvoke('pdf_decrypt', {
+    inputPath,
+    outputPath,
+    password,
+  })
+}
+
+export async function stampPDF(
+  inputPath: string,
+  outputPath: string,
+  stamp: StampSettings
+): Promise<ApiResponse<string>> {
+  return await safeInvoke('pdf_stamp', {
+    inputPath,
+    outputPath,
+    stamp,
+  })
+}
+
+export async function createHandoutPDF(
+  inputPath: string,
+  outputPath: string,
+  handout: HandoutSettings,
+  paperSize: PaperSize
+): Promise<ApiResponse<string>> {
+  return await safeInvoke('pdf_create_handout', {
+    inputPath,
+    outputPath,
+    handout,
+    paperSize,
+  })
+}
+
+export async function createPosterPDF(
+  inputPath: string,
+  outputPath: string,
+  poster: PosterSettings,
+  paperSize: PaperSize
+): Promise<ApiResponse<string>> {
+  return await safeInvoke('pdf_create_poster', {
+    inputPath,
+    outputPath,
+    poster,
+    paperSize,
+  })
+}
+
+export async function trimPDFMargins(
+  inputPath: string,
+  outputPath: string,
+  trim: TrimSettings
+): Promise<ApiResponse<string>> {
+  return await safeInvoke('pdf_trim_margins', {
+    inputPath,
+    outputPath,
+    trim,
+  })
+}
+
+export async function convertPDFToGrayscale(
+  inputPath: string,
+  outputPath: string,
+  boostContrast = false
+): Promise<ApiResponse<string>> {
+  return await safeInvoke('pdf_convert_grayscale', {
+    inputPath,
+    outputPath,
+    boostContrast,
   })
 }
 
@@ -102,6 +335,41 @@ export async function createPrintJob(
   })
 }
 
+export async function createMergedPrintJob(
+  name: string,
+  filePaths: string[],
+  printer: string,
+  settings: PrintSettings,
+  padToEven: boolean = false
+): Promise<ApiResponse<PrintJob>> {
+  return await safeInvoke('print_create_merged_job', {
+    name,
+    filePaths,
+    printer,
+    settings,
+    padToEven,
+  })
+}
+
+/** Split a job into linked colour and mono jobs; a side without pages is omitted */
+export async function createSplitPrintJobs(
+  name: string,
+  filePath: string,
+  colorPrinter: string,
+  monoPrinter: string,
+  settings: PrintSettings,
+  password?: string
+): Promise<ApiResponse<PrintJob[]>> {
+  return await safeInvoke('print_create_split_jobs', {
+    name,
+    filePath,
+    colorPrinter,
+    monoPrinter,
+    settings,
+    password,
+  })
+}
+
 export async function getAllPrintJobs(): Promise<ApiResponse<PrintJob[]>> {
   return await safeInvoke('print_get_all_jobs')
 }
@@ -130,10 +398,19 @@ export async function deletePrintJob(jobId: string): Promise<ApiResponse<string>
 }
 
 export async function submitPrintJob(
+  jobId: string,
+  sshConfig: SSHConfig,
+  password?: string
+): Promise<ApiResponse<string>> {
+  return await safeInvoke('print_submit_job', { jobId, sshConfig, password })
+}
+
+/** Print the back sides of a manual duplex job after the stack is reinserted */
+export async function continueManualDuplex(
   jobId: string,
   sshConfig: SSHConfig
 ): Promise<ApiResponse<string>> {
-  return await safeInvoke('print_submit_job', { jobId, sshConfig })
+  return await safeInvoke('print_continue_manual_duplex', { jobId, sshConfig })
 }
 
 export async function getPrinters(): Promise<ApiResponse<Printer[]>> {
@@ -157,6 +434,16 @@ export async function checkActiveJobs(
   return await safeInvoke('print_check_active_jobs', { sshConfig })
 }
 
+/** Sheets, sides and quota a job will use, before it is created */
+export async function estimatePrintJob(
+  filePath: string,
+  settings: PrintSettings,
+  printer: Printer,
+  password?: string
+): Promise<ApiResponse<JobEstimate>> {
+  return await safeInvoke('print_estimate_job', { filePath, settings, printer, password })
+}
+
 // ========== Storage Operations ==========
 
 /**
@@ -173,6 +460,13 @@ export async function getBackupPath(jobId: string): Promise<ApiResponse<string>>
   return await safeInvoke('print_get_backup_path', { jobId })
 }
 
+/**
+ * Get a path for the PDF converted from an image or text file
+ */
+export async function getConvertedPath(sourcePath: string): Promise<ApiResponse<string>> {
+  return await safeInvoke('print_get_converted_path', { sourcePath })
+}
+
 /**
  * Clean up old history entries
  * @param days Number of days to keep (default: 30)
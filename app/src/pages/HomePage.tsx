@@ -3,6 +3,14 @@ import { useNavigate } from 'react-router-dom'
 import { usePrinterStore } from '@/store/printer-store'
 import { getAllPrintJobs, getPDFInfo } from '@/lib/printer-api'
 import { safeDialogOpen } from '@/lib/tauri-utils'
+import {
+  convertToPDF,
+  isImageFile,
+  isPrintableFile,
+  IMAGE_EXTENSIONS,
+  PRINTABLE_EXTENSIONS,
+  TEXT_EXTENSIONS,
+} from '@/lib/convert-to-pdf'
 import { toast } from 'sonner'
 import { FileText, AlertCircle, Clock, Printer, Edit3, X } from 'lucide-react'
 import { Button } from '@/components/ui/button'
@@ -24,6 +32,7 @@ const statusColors: Record<PrintJobStatus, string> = {
   Uploading: 'text-accent',
   Queued: 'text-warning',
   Printing: 'text-primary',
+  AwaitingReinsert: 'text-warning',
   Completed: 'text-success',
   Failed: 'text-destructive',
   Cancelled: 'text-muted-foreground',
@@ -52,9 +61,29 @@ export default function ModernHomePageV2() {
     }
   }
 
-  const handleFileSelect = useCallback(async (filePath: string) => {
+  const handleFileSelect = useCallback(async (selectedPaths: string[]) => {
     setLoading(true)
     try {
+      // Text files and images are converted to a PDF first
+      let filePath: string
+      try {
+        const converted = await convertToPDF(selectedPaths)
+        filePath = converted.path
+        if (converted.warning) {
+          toast.warning(converted.warning)
+        }
+      } catch (error) {
+        const errorMsg = error instanceof Error ? error.message : String(error)
+        setErrorDialog({
+          open: true,
+          title: 'Failed to Convert File',
+          message: 'The file could not be converted to a PDF for printing.',
+          technicalDetails: errorMsg,
+        })
+        toast.error('Failed to convert file')
+        return
+      }
+
       const info = await getPDFInfo(filePath)
       if (info.success && info.data) {
         setCurrentFile(null, filePath)
@@ -97,12 +126,18 @@ export default function ModernHomePageV2() {
   }, [setCurrentFile, navigate])
 
   const handleBrowseFile = useCallback(async () => {
-    const file = await safeDialogOpen({
-      multiple: false,
-      filters: [{ name: 'PDF', extensions: ['pdf'] }],
+    const files = await safeDialogOpen({
+      // Several images are printed together, one per page
+      multiple: true,
+      filters: [
+        { name: 'Printable Files', extensions: PRINTABLE_EXTENSIONS },
+        { name: 'PDF', extensions: ['pdf'] },
+        { name: 'Text and Source Code', extensions: TEXT_EXTENSIONS },
+        { name: 'Images', extensions: IMAGE_EXTENSIONS },
+      ],
     })
-    if (file) {
-      handleFileSelect(file as string)
+    if (files) {
+      handleFileSelect(Array.isArray(files) ? files : [files])
     }
   }, [handleFileSelect])
 
@@ -150,11 +185,14 @@ export default function ModernHomePageV2() {
             setIsDragging(false)
           } else if (payload.type === 'drop') {
             const paths: string[] = payload.paths || []
-            const pdfPath = paths.find((p) => p.toLowerCase().endsWith('.pdf'))
-            if (pdfPath) {
-              handleFileSelect(pdfPath)
+            const images = paths.filter(isImageFile)
+            const printablePath = paths.find(isPrintableFile)
+            if (images.length > 1) {
+              handleFileSelect(images)
+            } else if (printablePath) {
+              handleFileSelect([printablePath])
             } else {
-              toast.error('Please drop a PDF file')
+              toast.error('Please drop a PDF, image, text or source code file')
             }
             setIsDragging(false)
           }
@@ -205,7 +243,7 @@ export default function ModernHomePageV2() {
               <div className="text-center">
                 <div className="text-5xl mb-4">📥</div>
                 <h2 className="text-xl font-semibold text-primary mb-2">
-                  Drop PDF Here
+                  Drop File Here
                 </h2>
                 <p className="text-muted-foreground">
                   Release to upload
@@ -214,7 +252,7 @@ export default function ModernHomePageV2() {
             ) : (
               <div className="text-center">
                 <h2 className="text-xl font-semibold text-foreground mb-2">
-                  Upload PDF Document
+                  Upload Document
                 </h2>
                 <p className="text-muted-foreground mb-6">
                   Drag and drop a file here, or click to browse
@@ -227,7 +265,7 @@ export default function ModernHomePageV2() {
                   Browse Files
                 </Button>
                 <div className="mt-4 text-sm text-muted-foreground/70">
-                  PDF files only · Instant preview
+                  PDF, images, text and source code · Instant preview
                 </div>
               </div>
             )}
@@ -304,7 +342,7 @@ export default function ModernHomePageV2() {
                     key={job.id}
                     onClick={() => {
                       if (job.file_path) {
-                        handleFileSelect(job.file_path)
+                        handleFileSelect([job.file_path])
                       } else {
                         navigate('/jobs')
                       }
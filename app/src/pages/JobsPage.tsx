@@ -1,7 +1,7 @@
 import { useEffect, useCallback, useState } from 'react'
 import { useNavigate } from 'react-router-dom'
 import { usePrinterStore } from '@/store/printer-store'
-import { getAllPrintJobs, cancelPrintJob, deletePrintJob } from '@/lib/printer-api'
+import { getAllPrintJobs, cancelPrintJob, deletePrintJob, continueManualDuplex } from '@/lib/printer-api'
 import { JobDetailDialog } from '@/components/jobs/JobDetailDialog'
 import type { PrintJob } from '@/types/printer'
 import { SimpleCard, SimpleCardHeader, SimpleCardTitle, SimpleCardDescription, SimpleCardContent } from '@/components/ui/simple-card'
@@ -34,6 +34,7 @@ import {
   Eye,
   RefreshCw,
   History,
+  FlipHorizontal,
 } from 'lucide-react'
 import type { PrintJobStatus } from '@/types/printer'
 
@@ -61,6 +62,11 @@ const statusConfig: Record<
     icon: <PrinterIcon className="w-4 h-4" />,
     label: 'Printing',
   },
+  AwaitingReinsert: {
+    color: 'bg-warning text-warning-foreground',
+    icon: <RefreshCw className="w-4 h-4" />,
+    label: 'Reinsert Paper',
+  },
   Completed: {
     color: 'bg-success',
     icon: <CheckCircle2 className="w-4 h-4" />,
@@ -119,6 +125,23 @@ export default function JobsPage() {
     }
   }
 
+  const handleContinueManualDuplex = async (jobId: string) => {
+    if (!sshConfig) {
+      toast.error('Not connected to SSH')
+      return
+    }
+
+    const result = await continueManualDuplex(jobId, sshConfig)
+    if (result.success) {
+      toast.success('Back sides sent to the printer')
+      setDetailDialogOpen(false)
+      loadJobs()
+    } else {
+      toast.error(result.error || 'Failed to print back sides')
+      loadJobs()
+    }
+  }
+
   const handleDeleteJob = async (jobId: string) => {
     const result = await deletePrintJob(jobId)
     if (result.success) {
@@ -134,7 +157,8 @@ export default function JobsPage() {
       job.status === 'Pending' ||
       job.status === 'Uploading' ||
       job.status === 'Queued' ||
-      job.status === 'Printing'
+      job.status === 'Printing' ||
+      job.status === 'AwaitingReinsert'
   )
 
   const completedJobs = printJobs.filter(
@@ -313,6 +337,32 @@ export default function JobsPage() {
                       View
                     </Button>
 
+                    {job.status === 'AwaitingReinsert' && (
+                      <AlertDialog>
+                        <AlertDialogTrigger asChild>
+                          <Button size="sm" className="flex-1">
+                            <FlipHorizontal className="w-4 h-4 mr-2" />
+                            Print Backs
+                          </Button>
+                        </AlertDialogTrigger>
+                        <AlertDialogContent>
+                          <AlertDialogHeader>
+                            <AlertDialogTitle>Print Back Sides?</AlertDialogTitle>
+                            <AlertDialogDescription>
+                              Put the printed front sides back in the tray as shown on the printer,
+                              then continue to print the back sides.
+                            </AlertDialogDescription>
+                          </AlertDialogHeader>
+                          <AlertDialogFooter>
+                            <AlertDialogCancel>Cancel</AlertDialogCancel>
+                            <AlertDialogAction onClick={() => handleContinueManualDuplex(job.id)}>
+                              Print Backs
+                            </AlertDialogAction>
+                          </AlertDialogFooter>
+                        </AlertDialogContent>
+                      </AlertDialog>
+                    )}
+
                     {selectedTab === 'active' ? (
                       <AlertDialog>
                         <AlertDialogTrigger asChild>
@@ -371,6 +421,7 @@ export default function JobsPage() {
         job={selectedJob}
         open={detailDialogOpen}
         onOpenChange={setDetailDialogOpen}
+        onContinueManualDuplex={handleContinueManualDuplex}
       />
     </div>
   )
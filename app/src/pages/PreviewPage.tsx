@@ -7,6 +7,13 @@ import {
   createPrintJob,
   submitPrintJob,
   getPDFInfo,
+  searchPDFText,
+  resolvePageRange,
+  estimatePrintJob,
+  detectOverlays,
+  detectBlankPages,
+  createMergedPrintJob,
+  createSplitPrintJobs,
 } from '@/lib/printer-api'
 import { Button } from '@/components/ui/button'
 import { Switch } from '@/components/ui/switch'
@@ -36,7 +43,17 @@ import {
   AlertDialogHeader,
   AlertDialogTitle,
 } from '@/components/ui/alert-dialog'
-import type { PrintSettings, Printer as PrinterType, PDFInfo, PrinterGroup } from '@/types/printer'
+import type {
+  PrintSettings,
+  Printer as PrinterType,
+  PDFInfo,
+  PrinterGroup,
+  ColorMode,
+  JobEstimate,
+  OverlayAnalysis,
+  BlankPage,
+  ApiResponse,
+} from '@/types/printer'
 import 'react-pdf/dist/Page/AnnotationLayer.css'
 import 'react-pdf/dist/Page/TextLayer.css'
 
@@ -114,7 +131,7 @@ export default function ModernPreviewPage() {
   const [settings, setSettings] = useState<PrintSettings>(draftSettings || {
     copies: 1,
     duplex: 'DuplexLongEdge',
-    orientation: 'Portrait',
+    orientation: 'Auto',
     page_range: { type: 'All' },
     pages_per_sheet: 1,
     booklet: false,
@@ -122,6 +139,8 @@ export default function ModernPreviewPage() {
   })
 
   const [selectedPrinter, setSelectedPrinter] = useState(draftPrinter || '')
+  // Colour printer for pages that use colour; empty prints everything on selectedPrinter
+  const [colorSplitPrinter, setColorSplitPrinter] = useState('')
 
   // Get selected file
   const selectedFile = useMemo(() =>
@@ -129,6 +148,33 @@ export default function ModernPreviewPage() {
     [fileQueue, selectedFileId]
   )
 
+  // Pages of Outline and Keyword ranges, which depend on the document's
+  // bookmarks or text; resolved by the backend so the preview matches what prints
+  const [documentRangePages, setDocumentRangePages] = useState<number[]>([])
+
+  useEffect(() => {
+    const range = settings.page_range
+    if (!selectedFile?.path || (range.type !== 'Outline' && range.type !== 'Keyword')) {
+      setDocumentRangePages([])
+      return
+    }
+    let cancelled = false
+    const request: Promise<ApiResponse<number[]>> = range.type === 'Keyword'
+      ? searchPDFText(selectedFile.path, range.pattern, range.regex, range.case_sensitive).then(result => ({
+          ...result,
+          data: result.data?.filter(page => page.matches > 0).map(page => page.number),
+        }))
+      : resolvePageRange(selectedFile.path, range)
+    request.then(result => {
+      if (!cancelled) {
+        setDocumentRangePages(result.success && result.data ? result.data : [])
+      }
+    })
+    return () => {
+      cancelled = true
+    }
+  }, [selectedFile?.path, settings.page_range])
+
   // Calculate which pages will be printed based on page_range
   const pagesToPrint = useMemo(() => {
     if (!selectedFile?.pdfInfo) return new Set<number>()
@@ -156,10 +202,74 @@ export default function ModernPreviewPage() {
           }
         })
         break
+      case 'Outline':
+      case 'Keyword':
+        documentRangePages.forEach(p => pages.add(p))
+        break
     }
 
     return pages
-  }, [selectedFile, settings.page_range])
+  }, [selectedFile, settings.page_range, documentRangePages])
+
+  // Slide build frames of the selected file, which "Remove Slide Builds" drops
+  const [overlays, setOverlays] = useState<OverlayAnalysis | null>(null)
+
+  useEffect(() => {
+    if (!selectedFile?.pdfInfo) {
+      setOverlays(null)
+      return
+    }
+    let cancelled = false
+    detectOverlays(selectedFile.path).then(result => {
+      if (!cancelled) {
+        setOverlays(result.success && result.data ? result.data : null)
+      }
+    })
+    return () => {
+      cancelled = true
+    }
+  }, [selectedFile?.path, selectedFile?.pdfInfo])
+
+  // Blank pages of the selected file, which "Skip Blank Pages" drops
+  const [blankPages, setBlankPages] = useState<BlankPage[]>([])
+
+  useEffect(() => {
+    if (!selectedFile?.pdfInfo) {
+      setBlankPages([])
+      return
+    }
+    let cancelled = false
+    detectBlankPages(selectedFile.path).then(result => {
+      if (!cancelled) {
+        setBlankPages(result.success && result.data ? result.data : [])
+      }
+    })
+    return () => {
+      cancelled = true
+    }
+  }, [selectedFile?.path, selectedFile?.pdfInfo])
+
+  // Paper and quota for the current settings, worked out by the backend
+  const [estimate, setEstimate] = useState<JobEstimate | null>(null)
+
+  useEffect(() => {
+    const printer = printerGroups
+      .flatMap((g: PrinterGroup) => g.printers)
+      .find((p: PrinterType) => p.queue_name === selectedPrinter)
+    if (!selectedFile?.pdfInfo || !printer) {
+      setEstimate(null)
+      return
+    }
+    let cancelled = false
+    estimatePrintJob(selectedFile.path, settings, printer).then(result => {
+      if (!cancelled) {
+        setEstimate(result.success && result.data ? result.data : null)
+      }
+    })
+    return () => {
+      cancelled = true
+    }
+  }, [selectedFile?.path, selectedFile?.pdfInfo, settings, selectedPrinter, printerGroups])
 
   // Calculate effective sheet count after n-up
   const effectiveSheetCount = useMemo(() => {
@@ -508,7 +618,7 @@ export default function ModernPreviewPage() {
       return
     }
     await printFile(selectedFile)
-  }, [selectedFile, selectedPrinter, sshConfig, settings])
+  }, [selectedFile, selectedPrinter, colorSplitPrinter, sshConfig, settings])
 
   const handlePrintAll = useCallback(async () => {
     if (fileQueue.length === 0) return
@@ -563,6 +673,72 @@ export default function ModernPreviewPage() {
         error: `${successCount} succeeded, ${errorCount} failed`,
       })
     }
+  }, [fileQueue, selectedPrinter, colorSplitPrinter, sshConfig, settings])
+
+  // Print every queued file as a single job, in queue order
+  const handlePrintMerged = useCallback(async () => {
+    if (fileQueue.length < 2) return
+    if (!selectedPrinter) {
+      toast.error('Please select a printer')
+      return
+    }
+    if (!sshConfig) {
+      toast.error('Not connected to server. Please login first.')
+      return
+    }
+
+    const jobName = `${fileQueue.length} files merged`
+    setSubmitting(true)
+    setPrintDialog({
+      open: true,
+      status: 'submitting',
+      jobName,
+      printer: selectedPrinter,
+    })
+
+    try {
+      // Double-sided: pad each file to an even page count so every file starts on a new sheet
+      const createResult = await createMergedPrintJob(
+        jobName,
+        fileQueue.map(f => f.path),
+        selectedPrinter,
+        settings,
+        settings.duplex !== 'Simplex'
+      )
+      if (!createResult.success || !createResult.data) {
+        throw new Error(createResult.error || 'Failed to create merged job')
+      }
+
+      const job = createResult.data
+      addPrintJob(job)
+
+      const submitResult = await submitPrintJob(job.id, sshConfig)
+      if (!submitResult.success) {
+        throw new Error(submitResult.error || 'Submission failed')
+      }
+
+      fileQueue.forEach(f => removeDraftJob(f.id))
+      setPrintDialog({
+        open: true,
+        status: 'success',
+        jobName,
+        printer: selectedPrinter,
+      })
+      setTimeout(() => {
+        setPrintDialog({ open: false, status: 'success' })
+        navigate('/jobs')
+      }, 3000)
+    } catch (error) {
+      setPrintDialog({
+        open: true,
+        status: 'error',
+        jobName,
+        printer: selectedPrinter,
+        error: error instanceof Error ? error.message : String(error),
+      })
+    } finally {
+      setSubmitting(false)
+    }
   }, [fileQueue, selectedPrinter, sshConfig, settings])
 
   const printFile = async (file: QueuedFile, silent = false) => {
@@ -576,7 +752,6 @@ export default function ModernPreviewPage() {
     }
 
     const copies = settings.copies
-    const totalJobs = copies > 1 ? copies : 1
     const jobLabel = copies > 1 ? `${file.name} (${copies} copies)` : file.name
 
     if (!silent) {
@@ -590,31 +765,55 @@ export default function ModernPreviewPage() {
     }
 
     try {
-      // Settings for each job (copies = 1 since we submit multiple jobs)
-      const jobSettings = { ...settings, copies: 1 }
-
-      // Submit multiple jobs for multiple copies
-      for (let copyNum = 1; copyNum <= totalJobs; copyNum++) {
-        // Generate job name with copy number suffix for multiple copies
-        const baseName = file.name.replace(/\.pdf$/i, '')
-        const jobName = copies > 1
-          ? `${baseName}-copy${copyNum}.pdf`
-          : file.name
+      // Colour pages go to their own printer, the rest to the selected one
+      if (colorSplitPrinter && colorSplitPrinter !== selectedPrinter) {
+        const splitResult = await createSplitPrintJobs(
+          file.name,
+          file.path,
+          colorSplitPrinter,
+          selectedPrinter,
+          settings
+        )
+        if (!splitResult.success || !splitResult.data) {
+          throw new Error(splitResult.error || 'Failed to split colour pages')
+        }
+        for (const job of splitResult.data) {
+          addPrintJob(job)
+          const submitResult = await submitPrintJob(job.id, sshConfig)
+          if (!submitResult.success) {
+            throw new Error(submitResult.error || `Submission failed (${job.printer})`)
+          }
+        }
+        removeDraftJob(file.id)
+        if (!silent) {
+          setPrintDialog({
+            open: true,
+            status: 'success',
+            jobName: jobLabel,
+            printer: `${selectedPrinter} + ${colorSplitPrinter}`,
+          })
+          setTimeout(() => {
+            setPrintDialog({ open: false, status: 'success' })
+            navigate('/jobs')
+          }, 3000)
+        }
+        return
+      }
 
-        const createResult = await createPrintJob(jobName, file.path, selectedPrinter, jobSettings)
+      // One job; lpr prints the copies, collated or not
+      const createResult = await createPrintJob(file.name, file.path, selectedPrinter, settings)
 
-        if (!createResult.success || !createResult.data) {
-          throw new Error(createResult.error || `Failed to create job (copy ${copyNum})`)
-        }
+      if (!createResult.success || !createResult.data) {
+        throw new Error(createResult.error || 'Failed to create job')
+      }
 
-        const job = createResult.data
-        addPrintJob(job)
+      const job = createResult.data
+      addPrintJob(job)
 
-        const submitResult = await submitPrintJob(job.id, sshConfig)
+      const submitResult = await submitPrintJob(job.id, sshConfig)
 
-        if (!submitResult.success) {
-          throw new Error(submitResult.error || `Submission failed (copy ${copyNum})`)
-        }
+      if (!submitResult.success) {
+        throw new Error(submitResult.error || 'Submission failed')
       }
 
       // Remove draft after successful print
@@ -1058,6 +1257,34 @@ export default function ModernPreviewPage() {
                 </div>
               </div>
 
+              {/* Collate */}
+              {settings.copies > 1 && (
+                <div className="flex justify-between items-center py-2.5 px-3 rounded-lg hover:bg-muted/50 transition-colors">
+                  <div>
+                    <div className="text-sm text-foreground">Collate</div>
+                    <div className="text-xs text-muted-foreground">
+                      {(settings.collate ?? true) ? '1, 2, 3, 1, 2, 3' : '1, 1, 2, 2, 3, 3'}
+                    </div>
+                  </div>
+                  <Switch
+                    checked={settings.collate ?? true}
+                    onCheckedChange={(checked) => setSettings({ ...settings, collate: checked })}
+                  />
+                </div>
+              )}
+
+              {/* Reverse order */}
+              <div className="flex justify-between items-center py-2.5 px-3 rounded-lg hover:bg-muted/50 transition-colors">
+                <div>
+                  <div className="text-sm text-foreground">Reverse Order</div>
+                  <div className="text-xs text-muted-foreground">Last sheet first</div>
+                </div>
+                <Switch
+                  checked={settings.reverse_order ?? false}
+                  onCheckedChange={(checked) => setSettings({ ...settings, reverse_order: checked })}
+                />
+              </div>
+
               {/* Double-sided */}
               <div className="flex justify-between items-center py-2.5 px-3 rounded-lg hover:bg-muted/50 transition-colors">
                 <div>
@@ -1072,6 +1299,58 @@ export default function ModernPreviewPage() {
                 />
               </div>
 
+              {/* Manual duplex */}
+              {settings.duplex !== 'Simplex' && (
+                <div className="flex justify-between items-center py-2.5 px-3 rounded-lg hover:bg-muted/50 transition-colors">
+                  <div>
+                    <div className="text-sm text-foreground">Manual Double-Sided</div>
+                    <div className="text-xs text-muted-foreground">Reinsert the stack for the backs</div>
+                  </div>
+                  <Switch
+                    checked={!!settings.manual_duplex}
+                    onCheckedChange={(checked) =>
+                      setSettings({ ...settings, manual_duplex: checked ? { reverse_fronts: false } : undefined })
+                    }
+                  />
+                </div>
+              )}
+
+              {/* Slide builds */}
+              {(!!overlays?.pages_saved || settings.remove_overlays) && (
+                <div className="flex justify-between items-center py-2.5 px-3 rounded-lg hover:bg-muted/50 transition-colors">
+                  <div>
+                    <div className="text-sm text-foreground">Remove Slide Builds</div>
+                    <div className="text-xs text-muted-foreground">
+                      {overlays?.pages_saved
+                        ? `${overlays.pages_saved} of ${overlays.num_pages} pages are build steps`
+                        : 'No build steps found'}
+                    </div>
+                  </div>
+                  <Switch
+                    checked={settings.remove_overlays ?? false}
+                    onCheckedChange={(checked) => setSettings({ ...settings, remove_overlays: checked })}
+                  />
+                </div>
+              )}
+
+              {/* Blank pages */}
+              {(blankPages.length > 0 || settings.remove_blank_pages) && (
+                <div className="flex justify-between items-center py-2.5 px-3 rounded-lg hover:bg-muted/50 transition-colors">
+                  <div>
+                    <div className="text-sm text-foreground">Skip Blank Pages</div>
+                    <div className="text-xs text-muted-foreground">
+                      {blankPages.length > 0
+                        ? `${blankPages.length} of ${selectedFile?.pdfInfo?.num_pages} pages look blank`
+                        : 'No blank pages found'}
+                    </div>
+                  </div>
+                  <Switch
+                    checked={settings.remove_blank_pages ?? false}
+                    onCheckedChange={(checked) => setSettings({ ...settings, remove_blank_pages: checked })}
+                  />
+                </div>
+              )}
+
               {/* Paper size */}
               <div className="flex justify-between items-center py-2.5 px-3 rounded-lg hover:bg-muted/50 transition-colors">
                 <label className="text-sm text-foreground">Paper Size</label>
@@ -1093,11 +1372,31 @@ export default function ModernPreviewPage() {
                   value={settings.orientation}
                   onChange={(e) => setSettings({ ...settings, orientation: e.target.value as PrintSettings['orientation'] })}
                 >
+                  <option value="Auto">Auto</option>
                   <option value="Portrait">Portrait</option>
                   <option value="Landscape">Landscape</option>
                 </select>
               </div>
 
+              {/* Colour */}
+              <div className="flex justify-between items-center py-2.5 px-3 rounded-lg hover:bg-muted/50 transition-colors">
+                <div>
+                  <div className="text-sm text-foreground">Colour</div>
+                  <div className="text-xs text-muted-foreground">
+                    {(settings.color_mode ?? 'Color') === 'Color' ? 'As in the file' : 'Converted before upload'}
+                  </div>
+                </div>
+                <select
+                  className="px-3 py-1.5 text-sm border border-border rounded-md bg-background font-medium"
+                  value={settings.color_mode ?? 'Color'}
+                  onChange={(e) => setSettings({ ...settings, color_mode: e.target.value as ColorMode })}
+                >
+                  <option value="Color">As Is</option>
+                  <option value="Auto">Auto</option>
+                  <option value="Grayscale">Grayscale</option>
+                </select>
+              </div>
+
               {/* Pages per sheet */}
               <div className="flex justify-between items-center py-2.5 px-3 rounded-lg hover:bg-muted/50 transition-colors">
                 <div>
@@ -1147,6 +1446,44 @@ export default function ModernPreviewPage() {
                   </optgroup>
                 ))}
               </select>
+
+              {/* Colour pages to a colour printer, the rest to the selected one */}
+              <div className="flex justify-between items-center gap-2 py-2.5 px-3 rounded-lg hover:bg-muted/50 transition-colors">
+                <div>
+                  <div className="text-sm text-foreground">Colour Pages</div>
+                  <div className="text-xs text-muted-foreground">Split into a second job</div>
+                </div>
+                <select
+                  className="px-3 py-1.5 text-sm border border-border rounded-md bg-background font-medium max-w-[9rem]"
+                  value={colorSplitPrinter}
+                  onChange={(e) => setColorSplitPrinter(e.target.value)}
+                >
+                  <option value="">Don't split</option>
+                  {printers
+                    .filter((p: PrinterType) => p.supports_color && p.queue_name !== selectedPrinter)
+                    .map((p: PrinterType) => (
+                      <option key={p.id} value={p.queue_name}>
+                        {p.name} {p.variant && `(${p.variant})`}
+                      </option>
+                    ))}
+                </select>
+              </div>
+
+              {estimate && (
+                <div className="p-3 bg-muted/50 rounded-lg space-y-1">
+                  <div className="text-xs text-muted-foreground font-medium">Estimate</div>
+                  <div className="text-sm text-foreground">
+                    {estimate.sheets} {estimate.sheets === 1 ? 'sheet' : 'sheets'},{' '}
+                    {estimate.front_impressions + estimate.back_impressions}{' '}
+                    {estimate.color ? 'colour' : 'mono'} sides
+                  </div>
+                  <div className="text-xs text-muted-foreground">
+                    {/* Auto is priced as colour throughout; black-and-white pages cost less */}
+                    Quota {settings.color_mode === 'Auto' && estimate.color ? 'up to ' : ''}
+                    {estimate.quota_cost} • Queue {estimate.queue}
+                  </div>
+                </div>
+              )}
             </div>
 
           </div>
@@ -1183,6 +1520,18 @@ export default function ModernPreviewPage() {
               Print All ({fileQueue.length})
             </Button>
           )}
+          {fileQueue.length > 1 && (
+            <Button
+              onClick={handlePrintMerged}
+              disabled={!selectedPrinter || submitting}
+              size="lg"
+              variant="outline"
+              className="px-6"
+            >
+              <FileText className="w-4 h-4 mr-2" />
+              Print as One Job
+            </Button>
+          )}
         </div>
       </div>
 
@@ -23,33 +23,153 @@ export interface PrintJob {
   error?: string
   /** The job ID assigned by lpq (e.g., "psts-123") */
   lpq_job_id?: string
+  /** Files merged into file_path, in print order (empty for single-file jobs) */
+  source_files?: string[]
+  /** The other half of a job split into colour and mono pages */
+  linked_job_id?: string
+  /** Paper and quota the job uses, updated on submit; an upper bound for Auto colour */
+  estimate?: JobEstimate
+}
+
+/** What a job prints: paper, sides and quota */
+export interface JobEstimate {
+  /** Document pages printed, over all copies */
+  printed_pages: number
+  /** Physical sheets of paper, over all copies */
+  sheets: number
+  front_impressions: number
+  back_impressions: number
+  /** Queue the job goes to once the duplex setting is applied */
+  queue: string
+  duplex: boolean
+  color: boolean
+  /** Quota charged, in units of one single-sided mono A4 side */
+  quota_cost: number
 }
 
 export interface PrintSettings {
   copies: number
+  /** Print copies one complete set at a time (default true) */
+  collate?: boolean
+  /** Print the last sheet first */
+  reverse_order?: boolean
   duplex: DuplexMode
   orientation: Orientation
   page_range: PageRange
   pages_per_sheet: number
   booklet: boolean
+  /** Signatures and creep compensation for booklets (one signature when absent) */
+  booklet_settings?: BookletSettings
   paper_size: PaperSize
+  /** How pages are scaled onto the paper (default: 'Fit') */
+  scale_mode?: ScaleMode
+  /** Header/footer text and watermark stamped onto every printed page */
+  stamp?: StampSettings
+  /** Keep only the final frame of slide build sequences (Beamer overlays) */
+  remove_overlays?: boolean
+  /** Drop blank pages before imposition */
+  remove_blank_pages?: boolean
+  /** Print slides as a handout instead of n-up or booklet */
+  handout?: HandoutSettings
+  /** Enlarge each page across several sheets instead of n-up or booklet */
+  poster?: PosterSettings
+  /** Print duplex on a simplex queue in two passes, reinserting the stack in between */
+  manual_duplex?: ManualDuplexSettings
+  /** Crop pages to their content before imposition */
+  trim?: TrimSettings
+  /** Default 'Color': the file is sent unchanged. 'Auto' converts to grayscale on queues without colour support */
+  color_mode?: ColorMode
+  /** Darken coloured text when converting to grayscale */
+  boost_contrast?: boolean
 }
 
 export type DuplexMode = 'Simplex' | 'DuplexLongEdge' | 'DuplexShortEdge'
-export type Orientation = 'Portrait' | 'Landscape'
+/** 'Auto' picks portrait or landscape per page from its aspect ratio */
+export type Orientation = 'Portrait' | 'Landscape' | 'Auto'
 
 export type PageRange =
   | { type: 'All' }
   | { type: 'Range'; start: number; end: number }
   | { type: 'Selection'; pages: number[] }
+  /** Sections under outline entries (bookmarks) with these titles, e.g. "Chapter 3" */
+  | { type: 'Outline'; entries: string[] }
+  /** Pages whose text contains `pattern`, e.g. "Tutorial 5" */
+  | { type: 'Keyword'; pattern: string; regex?: boolean; case_sensitive?: boolean }
 
 export type PaperSize = 'A4' | 'A3'
 
+export type ScaleMode = 'Fit' | 'Fill' | 'ActualSize'
+
+export interface HandoutSettings {
+  layout: HandoutLayout
+  /** Thin border around each slide (default true) */
+  frame?: boolean
+  /** "Slide n" under each slide (default true) */
+  numbering?: boolean
+}
+
+/** ThreeWithNotes: three slides with ruled note lines beside them; TwoStacked: two slides with space below */
+export type HandoutLayout = 'ThreeWithNotes' | 'TwoStacked'
+
+export interface BookletSettings {
+  /** Sheets folded together into each signature; 0 puts every sheet in one */
+  sheets_per_signature?: number
+  /** Shift of the innermost sheet's pages away from the fold, in mm (negative: towards it) */
+  creep_mm?: number
+}
+
+export interface ManualDuplexSettings {
+  /** Print the fronts last page first, for printers that stack output face up */
+  reverse_fronts?: boolean
+}
+
+export interface PosterSettings {
+  /** Sheets across */
+  cols: number
+  /** Sheets down */
+  rows: number
+  /** Content repeated on neighbouring sheets, in mm (default 10) */
+  overlap_mm?: number
+  /** Default true */
+  crop_marks?: boolean
+  /** "row 1, col 2" in the margin of each sheet (default true) */
+  labels?: boolean
+}
+
+export interface TrimSettings {
+  /** Space kept around the content, in mm (default 5) */
+  padding_mm?: number
+  /** Crop every page to its own content; by default pages of the same size share one crop box */
+  per_page?: boolean
+}
+
+export type ColorMode = 'Auto' | 'Color' | 'Grayscale'
+
+export interface StampSettings {
+  /** Supports {username}, {date}, {name}, {n} (page number) and {N} (page count) */
+  template?: string
+  position?: StampPosition
+  font_size?: number
+  /** Watermark opacity, 0 to 1 */
+  opacity?: number
+  /** Large diagonal text across each page, e.g. "DRAFT" */
+  watermark?: string
+}
+
+export type StampPosition =
+  | 'TopLeft'
+  | 'TopCenter'
+  | 'TopRight'
+  | 'BottomLeft'
+  | 'BottomCenter'
+  | 'BottomRight'
+
 export type PrintJobStatus =
   | 'Pending'
   | 'Uploading'
   | 'Queued'
   | 'Printing'
+  | 'AwaitingReinsert'
   | 'Completed'
   | 'Failed'
   | 'Cancelled'
@@ -119,14 +239,106 @@ export interface DraftPrintJob {
 // ========== PDF Processing ==========
 export interface PDFInfo {
   num_pages: number
+  /** Displayed size of the first page in points */
   page_size: [number, number]
   file_size: number
+  pages: PageInfo[]
+  mixed_page_sizes: boolean
+  has_non_a4_pages: boolean
+  encrypted: boolean
+  /** The file cannot be opened without a password; page details are empty */
+  password_required: boolean
+  permissions?: PdfPermissions
+  /** Bookmarks, top level first */
+  outline?: OutlineEntry[]
+}
+
+export interface OutlineEntry {
+  title: string
+  /** 1-based page the entry points to */
+  page: number | null
+  /** Last page of the entry's section */
+  last_page: number | null
+  children: OutlineEntry[]
 }
 
+export interface PageTextMatch {
+  number: number
+  /** Occurrences on the page; 0 when it does not match */
+  matches: number
+  /** Text around the first occurrence */
+  excerpt: string | null
+}
+
+export interface BlankPage {
+  number: number
+  reason: BlankReason
+}
+
+/** Empty: no content; Invisible: draws nothing visible; Placeholder: e.g. "This page intentionally left blank" */
+export type BlankReason = 'Empty' | 'Invisible' | 'Placeholder'
+
+export interface OverlayAnalysis {
+  num_pages: number
+  /** 1-based numbers of build frames whose content the next page repeats */
+  overlay_pages: number[]
+  /** Pages no longer printed when the overlays are removed */
+  pages_saved: number
+}
+
+export interface TextListing {
+  num_pages: number
+  /** Characters the listing font has no glyph for, printed as '?' */
+  replaced_chars: number
+}
+
+export interface ColorAnalysis {
+  num_pages: number
+  /** 1-based numbers of the pages that use colour */
+  color_pages: number[]
+}
+
+export interface PdfPermissions {
+  print: boolean
+  print_high_quality: boolean
+  modify: boolean
+  copy: boolean
+  annotate: boolean
+  fill_forms: boolean
+  assemble: boolean
+}
+
+export interface PageInfo {
+  number: number
+  media_box: [number, number, number, number]
+  crop_box: [number, number, number, number]
+  rotate: number
+  /** Displayed width/height in points, after CropBox and rotation */
+  width: number
+  height: number
+  orientation: Orientation
+  format: PageFormat
+  /** Page label shown by PDF viewers, e.g. "iv" or "A-3" */
+  label?: string | null
+}
+
+export type PageFormat =
+  | 'A3'
+  | 'A4'
+  | 'A5'
+  | 'Letter'
+  | 'Legal'
+  | 'Slide4x3'
+  | 'Slide16x9'
+  | 'Slide16x10'
+  | 'Other'
+
 export interface BookletLayout {
   total_sheets: number
   pages_per_sheet: number
   page_order: (number | null)[][]
+  /** Sheets in each signature; page_order lists every signature's sheets in turn */
+  signature_sheets: number[]
 }
 
 // ========== Storage Info ==========